@@ -1,4 +1,5 @@
-use std::{env, fs, io::{Read, Write}, path::PathBuf};
+use std::{env, fs, io::{Read, Write}, path::{Path, PathBuf}, process::Command};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 fn main() {
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
@@ -11,13 +12,68 @@ fn main() {
     // Export env vars for the main crate
     println!("cargo:rustc-env=BUILD_NUMBER={}", build_number);
     println!(
-        "cargo:rustc-env=PKG_VERSION_WITH_BUILD={}",
-        format!("{} (build {})", pkg_version, build_number)
+        "cargo:rustc-env=PKG_VERSION_WITH_BUILD={} (build {})",
+        pkg_version, build_number
     );
 
+    // Provenance for `--version` / `--version-json`
+    let commit = env::var("GIT_COMMIT").ok().filter(|s| !s.is_empty())
+        .or_else(|| git_commit(&manifest_dir))
+        .unwrap_or_else(|| "unknown".into());
+    let date = build_date();
+    let target = env::var("TARGET").unwrap_or_else(|_| "unknown".into());
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| k.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+    let features = if features.is_empty() { "none".to_string() } else { features.join(",") };
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=BUILD_DATE={}", date);
+    println!("cargo:rustc-env=BUILD_TARGET={}", target);
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features);
+
     // Re-run logic: rebuild if build number file or env changes
     println!("cargo:rerun-if-env-changed=BUILD_NUMBER");
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
     println!("cargo:rerun-if-changed=build/build-number");
+    // New commits move HEAD or the branch it names
+    let git = Path::new(&manifest_dir).join(".git");
+    if git.join("HEAD").exists() {
+        println!("cargo:rerun-if-changed={}", git.join("HEAD").display());
+        if let Some(r) = fs::read_to_string(git.join("HEAD")).ok().and_then(|h| h.strip_prefix("ref: ").map(|r| r.trim().to_string())) {
+            println!("cargo:rerun-if-changed={}", git.join(r).display());
+        }
+    }
+}
+
+/// Short commit hash, with `-dirty` when the work tree has uncommitted changes.
+fn git_commit(manifest_dir: &str) -> Option<String> {
+    let git = |args: &[&str]| {
+        let out = Command::new("git").args(args).current_dir(manifest_dir).output().ok()?;
+        out.status.success().then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+    };
+    let hash = git(&["rev-parse", "--short=12", "HEAD"])?;
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|s| !s.is_empty());
+    Some(if dirty { format!("{}-dirty", hash) } else { hash })
+}
+
+/// UTC build time as RFC 3339; `SOURCE_DATE_EPOCH` pins it for reproducible builds.
+fn build_date() -> String {
+    let secs = env::var("SOURCE_DATE_EPOCH").ok().and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64));
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
 }
 
 fn increment_local_build_number(manifest_dir: &str) -> u64 {
@@ -34,7 +90,7 @@ fn increment_local_build_number(manifest_dir: &str) -> u64 {
     }
     n = n.saturating_add(1);
     if let Ok(mut f) = fs::File::create(&path) {
-        let _ = write!(f, "{}\n", n);
+        let _ = writeln!(f, "{}", n);
     }
     n
 }
@@ -0,0 +1,401 @@
+//! Changing the matched links after a scan: `--delete` (or `--trash`), `--retarget`, `--rewrite-prefix`,
+//! `--make-relative`, `--make-absolute`, and `--dereference-in-place`.
+//!
+//! Each match is confirmed on the terminal (`--yes` skips the prompts), checked to still be a
+//! symlink, and changed; `--dry-run` only lists what would happen, and `--journal` records each
+//! change for `undo`.
+//!
+//! On Windows a symlink can only be created with Developer Mode on or from an elevated prompt;
+//! `check_link_privilege` says so before anything is changed, and with `--junction-fallback` a
+//! link to a directory is made a junction (which needs no privilege) where a symlink is refused.
+
+use std::borrow::Borrow;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use console::Term;
+
+use crate::scan::Match;
+use crate::undo::{Entry, Journal};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Delete,
+    /// `--delete --trash`: move each link to the trash instead
+    Trash,
+    /// Point each link at this absolute path, keeping relative links relative
+    Retarget(PathBuf),
+    /// Point each link that resolves inside `old` (canonically `real`) at the same place inside `new`
+    RewritePrefix { old: PathBuf, real: Option<PathBuf>, new: PathBuf },
+    /// Rewrite absolute link text as relative, keeping the destination
+    MakeRelative,
+    /// Rewrite relative link text as absolute, keeping the destination
+    MakeAbsolute,
+    /// Replace each link with a copy of what it resolves to, if that is at most `max_size` bytes;
+    /// with `verify`, only once the copy hashes the same as the original
+    Dereference { max_size: u64, verify: bool },
+}
+
+/// What an action run did (or, with `--dry-run`, would do).
+#[derive(Default)]
+pub struct Report {
+    /// Each changed link, with its new link text where it has one
+    pub done: Vec<(PathBuf, Option<PathBuf>)>,
+    /// Declined at the prompt
+    pub skipped: usize,
+    /// Already had the new link text
+    pub unchanged: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl Action {
+    /// The verb for prompts and the report: "delete".
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Action::Delete => "delete",
+            Action::Trash => "trash",
+            Action::Retarget(_) => "retarget",
+            Action::RewritePrefix { .. } | Action::MakeRelative | Action::MakeAbsolute => "rewrite",
+            Action::Dereference { .. } => "replace",
+        }
+    }
+
+    /// "Deleted", for the report.
+    pub fn past(&self) -> &'static str {
+        match self {
+            Action::Delete => "Deleted",
+            Action::Trash => "Trashed",
+            Action::Retarget(_) => "Retargeted",
+            Action::RewritePrefix { .. } | Action::MakeRelative | Action::MakeAbsolute => "Rewrote",
+            Action::Dereference { .. } => "Replaced",
+        }
+    }
+
+    /// The link text `p` would get (`None` to remove it): a new destination is relative to the
+    /// link's directory if the link is relative now. For `Dereference`, the file or directory to copy.
+    fn new_text(&self, p: &Path) -> std::io::Result<Option<PathBuf>> {
+        let to = match self {
+            Action::Delete | Action::Trash => return Ok(None),
+            Action::Dereference { max_size, .. } => return copy_source(p, *max_size).map(Some),
+            Action::MakeRelative | Action::MakeAbsolute => {
+                let text = fs::read_link(p)?;
+                return Ok(Some(match self {
+                    Action::MakeRelative if text.is_absolute() => relative_to(&tidy(p, text), &real_dir(p)),
+                    Action::MakeAbsolute if text.is_relative() => tidy(p, real_dir(p).join(text)),
+                    _ => text,
+                }));
+            }
+            Action::Retarget(to) => to.clone(),
+            Action::RewritePrefix { old, real, new } => {
+                // Where the link points now: resolved, or by its text if it dangles
+                let dest = match crate::scan::realpath(p) {
+                    Ok(dest) => dest,
+                    Err(_) => crate::scan::lexical_absolute(&p.parent().unwrap_or(Path::new("")).join(fs::read_link(p)?)),
+                };
+                let rest = real.as_deref().and_then(|r| dest.strip_prefix(r).ok()).or_else(|| dest.strip_prefix(old).ok())
+                    .ok_or_else(|| std::io::Error::other(format!("no longer points inside {}", old.display())))?;
+                if rest.as_os_str().is_empty() { new.clone() } else { new.join(rest) }
+            }
+        };
+        if fs::read_link(p)?.is_absolute() { return Ok(Some(to)); }
+        Ok(Some(relative_to(&to, &real_dir(p))))
+    }
+
+    fn apply(&self, p: &Path, text: Option<&Path>) -> std::io::Result<()> {
+        match (self, text) {
+            (Action::Trash, _) => crate::trash::put(p),
+            (Action::Dereference { verify, .. }, Some(from)) => replace_with_copy(p, from, *verify),
+            (_, Some(text)) => relink(p, text),
+            (_, None) => remove_link(p),
+        }
+    }
+
+    /// Whether applying it creates symlinks (a copied tree may hold some).
+    pub fn creates_links(&self) -> bool {
+        !matches!(self, Action::Delete | Action::Trash)
+    }
+}
+
+/// Point the symlink `path` at `text` by renaming a new link over it, so it never goes missing.
+pub fn relink(path: &Path, text: &Path) -> std::io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.relink-{}", name, std::process::id()));
+    symlink(text, &tmp)?;
+    // Windows cannot rename over a link to a directory (or a junction); it goes just before
+    #[cfg(windows)]
+    if is_dir_link(path) { fs::remove_dir(path)?; }
+    fs::rename(&tmp, path).inspect_err(|_| { let _ = remove_link(&tmp); })
+}
+
+/// What the link at `p` resolves to, checked to be copyable: not holding the link, and at most `max_size` bytes.
+fn copy_source(p: &Path, max_size: u64) -> std::io::Result<PathBuf> {
+    let from = crate::scan::realpath(p).map_err(|e| std::io::Error::other(format!("{:#}", e)))?;
+    if real_dir(p).starts_with(&from) { return Err(std::io::Error::other("the link is inside its target")); }
+    if tree_size(&from, max_size)? > max_size {
+        return Err(std::io::Error::other(format!("target is over {} (--max-copy-size)", crate::reclaim::human_bytes(max_size))));
+    }
+    Ok(from)
+}
+
+/// Bytes in the regular files at or under `p`, counting no further once over `limit`; an error
+/// if there is anything there `copy_tree` cannot copy.
+fn tree_size(p: &Path, limit: u64) -> std::io::Result<u64> {
+    let meta = fs::symlink_metadata(p)?;
+    if meta.is_symlink() { return Ok(0); }
+    if !meta.is_dir() && !meta.is_file() { return Err(not_copyable(p)); }
+    if !meta.is_dir() { return Ok(meta.len()); }
+    let mut total = 0;
+    for e in fs::read_dir(p)? {
+        total += tree_size(&e?.path(), limit - total)?;
+        if total > limit { break; }
+    }
+    Ok(total)
+}
+
+/// Replace the symlink `path` with a copy of `from`, built beside it and renamed into place. A
+/// directory cannot be renamed over a file, so there the link is first moved aside, and put back
+/// if the copy does not land. With `verify`, a copy that does not match the original is discarded and the link left alone.
+fn replace_with_copy(path: &Path, from: &Path, verify: bool) -> std::io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.copy-{}", name, std::process::id()));
+    let placed = copy_tree(from, &tmp).and_then(|()| {
+        if verify { verify_copy(from, &tmp)?; }
+        if !fs::symlink_metadata(&tmp)?.is_dir() { return fs::rename(&tmp, path); }
+        let aside = path.with_file_name(format!(".{}.link-{}", name, std::process::id()));
+        fs::rename(path, &aside)?;
+        match fs::rename(&tmp, path) {
+            Ok(()) => { let _ = remove_link(&aside); Ok(()) }
+            Err(e) => { let _ = fs::rename(&aside, path); Err(e) }
+        }
+    });
+    placed.inspect_err(|_| { let _ = if tmp.is_dir() { fs::remove_dir_all(&tmp) } else { fs::remove_file(&tmp) }; })
+}
+
+/// Copy a file or directory tree with its permissions; symlinks inside it are copied as symlinks.
+fn copy_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+    let kind = meta.file_type();
+    if kind.is_symlink() { return symlink(&fs::read_link(from)?, to); }
+    if kind.is_file() { return fs::copy(from, to).map(|_| ()); }
+    if !kind.is_dir() { return Err(not_copyable(from)); }
+    fs::create_dir(to)?;
+    for e in fs::read_dir(from)? {
+        let e = e?;
+        copy_tree(&e.path(), &to.join(e.file_name()))?;
+    }
+    // Last, so a read-only directory can still be filled
+    fs::set_permissions(to, meta.permissions())
+}
+
+/// Check the copy at `to` against `from`: the same entries, link texts, and file contents (blake3),
+/// so a copy cut short or raced by a writer is caught.
+fn verify_copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    let differs = |what: &str| Err(std::io::Error::other(format!("the copy of {} differs from it ({})", from.display(), what)));
+    let kind = fs::symlink_metadata(from)?.file_type();
+    if kind.is_symlink() {
+        return if fs::read_link(from)? == fs::read_link(to)? { Ok(()) } else { differs("link text") };
+    }
+    if !kind.is_dir() {
+        return if hash_file(from)? == hash_file(to)? { Ok(()) } else { differs("content") };
+    }
+    let names = |dir: &Path| -> std::io::Result<Vec<std::ffi::OsString>> {
+        let mut v = fs::read_dir(dir)?.map(|e| e.map(|e| e.file_name())).collect::<std::io::Result<Vec<_>>>()?;
+        v.sort();
+        Ok(v)
+    };
+    let entries = names(from)?;
+    if entries != names(to)? { return differs("entries"); }
+    for n in entries { verify_copy(&from.join(&n), &to.join(&n))?; }
+    Ok(())
+}
+
+fn hash_file(p: &Path) -> std::io::Result<blake3::Hash> {
+    let mut h = blake3::Hasher::new();
+    h.update_reader(fs::File::open(p)?)?;
+    Ok(h.finalize())
+}
+
+fn not_copyable(p: &Path) -> std::io::Error {
+    std::io::Error::other(format!("{} is not a regular file, directory, or symlink", p.display()))
+}
+
+#[cfg(unix)]
+pub(crate) fn symlink(text: &Path, at: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(text, at)
+}
+
+/// Windows links are typed: a link to a directory is made a directory link, or with
+/// `--junction-fallback` a junction if symlinks are not permitted.
+#[cfg(windows)]
+pub(crate) fn symlink(text: &Path, at: &Path) -> std::io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+    let dest = at.parent().unwrap_or(Path::new("")).join(text);
+    let dir = fs::metadata(&dest).is_ok_and(|m| m.is_dir());
+    match if dir { symlink_dir(text, at) } else { symlink_file(text, at) } {
+        Err(e) if e.raw_os_error() == Some(ERROR_PRIVILEGE_NOT_HELD) => {
+            if dir && JUNCTIONS.load(Ordering::Relaxed) { return junction(&dest, at); }
+            Err(std::io::Error::new(e.kind(), NO_PRIVILEGE))
+        }
+        made => made,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn symlink(_text: &Path, _at: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "symlinks cannot be created on this platform"))
+}
+
+/// A junction at `at` to the directory `dest`. Junctions take an absolute target and need no
+/// privilege; `mklink /J` is there on every Windows.
+#[cfg(windows)]
+fn junction(dest: &Path, at: &Path) -> std::io::Result<()> {
+    let dest = crate::scan::realpath(dest).map_err(|e| std::io::Error::other(format!("{:#}", e)))?;
+    // `mklink` does not take the `\\?\` form `realpath` gives
+    let plain = dest.to_str().and_then(|d| d.strip_prefix(r"\\?\")).map(PathBuf::from);
+    let dest = plain.unwrap_or(dest);
+    let status = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"]).arg(at).arg(&dest)
+        .stdin(std::process::Stdio::null()).stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null())
+        .status()?;
+    if status.success() { Ok(()) } else { Err(std::io::Error::other(format!("mklink /J exited with {}", status))) }
+}
+
+/// `--junction-fallback`: make links to directories junctions where symlinks are refused (Windows).
+static JUNCTIONS: AtomicBool = AtomicBool::new(false);
+
+pub fn allow_junctions(on: bool) {
+    JUNCTIONS.store(on, Ordering::Relaxed);
+}
+
+#[cfg(windows)]
+const ERROR_PRIVILEGE_NOT_HELD: i32 = 1314;
+
+#[cfg(windows)]
+const NO_PRIVILEGE: &str = "creating symlinks needs Developer Mode (Settings > System > For developers) or an elevated prompt";
+
+/// Fail early, before any link is changed, if symlinks cannot be created; with junctions allowed
+/// only warn, since links to directories can still be made. Probes with a link in the temp
+/// directory; a no-op outside Windows.
+pub fn check_link_privilege() -> Result<()> {
+    #[cfg(windows)]
+    {
+        let probe = std::env::temp_dir().join(format!(".find-symlinks-probe-{}", std::process::id()));
+        match std::os::windows::fs::symlink_file("probe", &probe) {
+            Ok(()) => { let _ = fs::remove_file(&probe); }
+            Err(e) if e.raw_os_error() == Some(ERROR_PRIVILEGE_NOT_HELD) => {
+                if !JUNCTIONS.load(Ordering::Relaxed) {
+                    anyhow::bail!("{}; or pass --junction-fallback to make links to directories junctions instead", NO_PRIVILEGE);
+                }
+                eprintln!("warning: {}; links to directories will be made junctions, links to files will fail", NO_PRIVILEGE);
+            }
+            // Anything else (a read-only temp directory) is left for the changes themselves to report
+            Err(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Remove the link at `p`; on Windows a link to a directory (or a junction) is removed as a directory.
+pub(crate) fn remove_link(p: &Path) -> std::io::Result<()> {
+    #[cfg(windows)]
+    if is_dir_link(p) { return fs::remove_dir(p); }
+    fs::remove_file(p)
+}
+
+#[cfg(windows)]
+pub(crate) fn is_dir_link(p: &Path) -> bool {
+    use std::os::windows::fs::FileTypeExt;
+    fs::symlink_metadata(p).is_ok_and(|m| m.file_type().is_symlink_dir())
+}
+
+/// The directory a link at `p` is really in, which its relative text resolves from.
+fn real_dir(p: &Path) -> PathBuf {
+    p.parent().map_or_else(|| PathBuf::from("."), |d| crate::scan::realpath(d).unwrap_or_else(|_| crate::scan::lexical_absolute(d)))
+}
+
+/// Absolute link text for the link at `p` without `.` and `..`, if it resolves and that keeps the destination.
+fn tidy(p: &Path, text: PathBuf) -> PathBuf {
+    let tidy = crate::scan::lexical_absolute(&text);
+    // `..` after a symlinked directory leaves that directory's target, which dropping it lexically would not
+    let same = crate::scan::realpath(p).is_ok_and(|r| crate::scan::realpath(&tidy).is_ok_and(|t| t == r));
+    if same { tidy } else { text }
+}
+
+/// Absolute `to` as a path from `base`, a real directory (no symlinks, `.`, or `..` in it).
+fn relative_to(to: &Path, base: &Path) -> PathBuf {
+    let (to, base): (Vec<Component>, Vec<Component>) = (to.components().collect(), base.components().collect());
+    let common = to.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut rel: PathBuf = base[common..].iter().map(|_| Component::ParentDir).collect();
+    rel.extend(&to[common..]);
+    if rel.as_os_str().is_empty() { rel.push("."); }
+    rel
+}
+
+/// Whether prompts can be answered: both stdin and stderr are a terminal.
+pub fn can_prompt() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Ask `question` on the terminal; only `y` or `yes` agrees.
+pub(crate) fn confirm(term: &Term, question: &str) -> Result<bool> {
+    term.write_str(&format!("{} [y/N] ", question))?;
+    let answer = term.read_line()?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Apply `action` to each match in turn, recording each change in `journal`; `describe` gives the
+/// text shown after the path in prompts.
+pub fn run(action: &Action, matches: impl IntoIterator<Item = impl Borrow<Match>>, yes: bool, dry_run: bool, mut journal: Option<&mut Journal>, describe: impl Fn(&Match) -> String) -> Result<Report> {
+    let term = Term::stderr();
+    let mut report = Report::default();
+    for m in matches {
+        let m = m.borrow();
+        let text = match action.new_text(&m.path) {
+            Ok(text) => text,
+            Err(e) => { report.failed.push((m.path.clone(), e.to_string())); continue; }
+        };
+        let copy = matches!(action, Action::Dereference { .. });
+        if !copy && text.is_some() && text == fs::read_link(&m.path).ok() {
+            report.unchanged += 1;
+            continue;
+        }
+        if dry_run {
+            report.done.push((m.path.clone(), text));
+            continue;
+        }
+        let to = text.as_ref().map(|t| format!(" {} {}", if copy { "with a copy of" } else { "to" }, t.display())).unwrap_or_default();
+        let question = format!("{} {}{}{}?", capitalize(action.verb()), m.path.display(), describe(m), to);
+        if !yes && !confirm(&term, &question)? {
+            report.skipped += 1;
+            continue;
+        }
+        // The link may have been replaced since the scan; never touch anything else
+        let result = match fs::symlink_metadata(&m.path) {
+            Ok(meta) if meta.file_type().is_symlink() => fs::read_link(&m.path)
+                .and_then(|old| action.apply(&m.path, text.as_deref()).map(|()| old))
+                .map_err(|e| e.to_string()),
+            Ok(_) => Err("no longer a symlink".to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(old) => {
+                if let Some(j) = journal.as_deref_mut() {
+                    // Absolute, through the link's real directory, so `undo` works from anywhere
+                    let path = m.path.file_name().map_or_else(|| crate::scan::lexical_absolute(&m.path), |n| real_dir(&m.path).join(n));
+                    j.record(&Entry { path, old, new: if copy { None } else { text.clone() }, copy })?;
+                }
+                report.done.push((m.path.clone(), text));
+            }
+            Err(e) => report.failed.push((m.path.clone(), e)),
+        }
+    }
+    Ok(report)
+}
+
+pub(crate) fn capitalize(s: &str) -> String {
+    let mut c = s.chars();
+    c.next().map_or_else(String::new, |f| f.to_uppercase().chain(c).collect())
+}
@@ -0,0 +1,130 @@
+//! `--one-filesystem` boundaries: where the walk stops, and why.
+//!
+//! Device changes are found from `st_dev`, like `find -xdev`. With `--subvolumes`, btrfs
+//! subvolumes and ZFS datasets also stop the walk when they share the parent's `st_dev`
+//! (a bind mount, or a subvolume reached through one); they are recognized by what they
+//! are (inode 256 on btrfs, a `zfs` entry in the mount table) rather than by device number.
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::cli::WalkOpts;
+use crate::scan;
+
+/// A directory the walk did not descend into.
+pub struct Boundary {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+pub struct Boundaries {
+    /// Each walk root and its device; a directory is compared with the root it was reached from
+    roots: Vec<(PathBuf, u64)>,
+    subvolumes: bool,
+    /// Mount point -> (filesystem type, source)
+    mounts: HashMap<PathBuf, (String, String)>,
+    pub stops: Mutex<Vec<Boundary>>,
+}
+
+impl Boundaries {
+    /// Boundary checks for a walk of `roots`; `None` when `st_dev` alone (the walker's own check) will do.
+    pub fn new(opts: &WalkOpts, roots: &[PathBuf]) -> Option<Boundaries> {
+        if cfg!(not(unix)) || !opts.one_filesystem || !(opts.subvolumes || opts.list_boundaries) { return None; }
+        let roots = roots.iter().filter_map(|r| Some((r.clone(), dev_ino(&std::fs::metadata(r).ok()?).0))).collect();
+        Some(Boundaries { roots, subvolumes: opts.subvolumes, mounts: mounts(), stops: Mutex::new(Vec::new()) })
+    }
+
+    /// Whether the walk should stop at directory `p`; stops are recorded with their reason.
+    pub fn stops_at(&self, p: &Path, meta: &Metadata) -> bool {
+        let Some(reason) = self.reason(p, meta) else { return false };
+        if let Ok(mut v) = self.stops.lock() { v.push(Boundary { path: p.to_path_buf(), reason }); }
+        true
+    }
+
+    fn reason(&self, p: &Path, meta: &Metadata) -> Option<String> {
+        let mount = self.mounts.get(&scan::lexical_absolute(p));
+        let zfs = mount.filter(|(fs, _)| fs == "zfs").map(|(_, src)| format!("ZFS dataset {}", src));
+        let btrfs = is_btrfs_subvolume(p, meta).then(|| "btrfs subvolume".to_string());
+        let (dev, _) = dev_ino(meta);
+        let root_dev = self.roots.iter().find(|(r, _)| p.starts_with(r)).map_or(dev, |(_, d)| *d);
+        if dev != root_dev {
+            let kind = zfs.or(btrfs).or_else(|| mount.map(|(fs, src)| format!("{} mount of {}", fs, src)));
+            let devs = format!("device {} -> {}", dev_name(root_dev), dev_name(dev));
+            return Some(kind.map_or_else(|| devs.clone(), |k| format!("{} ({})", k, devs)));
+        }
+        if !self.subvolumes { return None; }
+        zfs.or(btrfs).map(|k| format!("{} (same device number)", k))
+    }
+}
+
+#[cfg(unix)]
+fn dev_ino(meta: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (meta.dev(), meta.ino())
+}
+
+#[cfg(not(unix))]
+fn dev_ino(_meta: &Metadata) -> (u64, u64) {
+    (0, 0)
+}
+
+#[cfg(target_os = "linux")]
+fn dev_name(dev: u64) -> String {
+    format!("{}:{}", libc::major(dev), libc::minor(dev))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn dev_name(dev: u64) -> String {
+    format!("{:#x}", dev)
+}
+
+/// The root directory of a btrfs subvolume (or snapshot) always has inode 256.
+#[cfg(target_os = "linux")]
+fn is_btrfs_subvolume(p: &Path, meta: &Metadata) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    const BTRFS_SUPER_MAGIC: i64 = 0x9123683E;
+    const BTRFS_FIRST_FREE_OBJECTID: u64 = 256;
+    if dev_ino(meta).1 != BTRFS_FIRST_FREE_OBJECTID { return false; }
+    let Ok(c) = std::ffi::CString::new(p.as_os_str().as_bytes()) else { return false };
+    let mut st: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c` is NUL-terminated and `st` is a writable statfs
+    let rc = unsafe { libc::statfs(c.as_ptr(), &mut st) };
+    rc == 0 && st.f_type as i64 == BTRFS_SUPER_MAGIC
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_btrfs_subvolume(_p: &Path, _meta: &Metadata) -> bool {
+    false
+}
+
+/// Mount points from `/proc/self/mountinfo` (Linux); empty elsewhere.
+pub(crate) fn mounts() -> HashMap<PathBuf, (String, String)> {
+    let Ok(text) = std::fs::read_to_string("/proc/self/mountinfo") else { return HashMap::new() };
+    text.lines()
+        .filter_map(|l| {
+            // id parent major:minor root mountpoint options [optional...] - fstype source superoptions
+            let (head, tail) = l.split_once(" - ")?;
+            let point = head.split(' ').nth(4)?;
+            let mut t = tail.split(' ');
+            let (fs, src) = (t.next()?, t.next()?);
+            Some((PathBuf::from(unescape(point)), (fs.to_string(), unescape(src))))
+        })
+        .collect()
+}
+
+/// mountinfo escapes space, tab, newline, and backslash as `\ooo`.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        match u8::from_str_radix(rest.get(i + 1..i + 4).unwrap_or(""), 8) {
+            Ok(c) => { out.push(c as char); rest = &rest[i + 4..]; }
+            Err(_) => { out.push('\\'); rest = &rest[i + 1..]; }
+        }
+    }
+    out.push_str(rest);
+    out
+}
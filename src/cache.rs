@@ -0,0 +1,77 @@
+//! `--cache`: reuse the last result set for the same roots, options, and targets while nothing it walked changed.
+//!
+//! An entry is keyed by a hash of the working directory, the scan options, and the resolved
+//! targets, and holds the whole scan result, including every walked directory's mtime. It is
+//! reused only if each of those directories still has its recorded mtime (adding, removing, or
+//! replacing a link always touches its parent) and the `--ignore-file`s are unchanged.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use crate::cli::ScanOpts;
+use crate::scan::{ScanResult, Target};
+use crate::snapshot::{mtime_ns, target_keys};
+
+const VERSION: u32 = 1;
+
+#[derive(Deserialize)]
+struct Entry {
+    version: u32,
+    /// `--ignore-file`s and their mtimes (0 when missing)
+    ignore_files: Vec<(PathBuf, u64)>,
+    result: ScanResult,
+}
+
+/// The cache entry for one set of roots, options, and targets.
+pub struct Cache {
+    path: PathBuf,
+}
+
+fn ignore_mtimes(opts: &ScanOpts) -> Vec<(PathBuf, u64)> {
+    opts.walk.ignore_files.iter()
+        .map(|f| (f.clone(), fs::metadata(f).and_then(|m| m.modified()).map_or(0, mtime_ns)))
+        .collect()
+}
+
+impl Cache {
+    /// Entries live in `<cache dir>/find-symlinks/results`.
+    pub fn new(opts: &ScanOpts, targets: &[Target]) -> Result<Cache> {
+        let dir = dirs::cache_dir().context("no cache directory on this platform")?;
+        let mut key = blake3::Hasher::new();
+        key.update(std::env::current_dir()?.as_os_str().as_encoded_bytes());
+        key.update(format!("{:?}", opts).as_bytes());
+        for t in target_keys(targets) {
+            key.update(b"\0");
+            key.update(t.as_bytes());
+        }
+        let name = format!("{}.json", &key.finalize().to_hex()[..32]);
+        Ok(Cache { path: dir.join("find-symlinks").join("results").join(name) })
+    }
+
+    /// The cached result, if there is one and no directory it walked has changed since.
+    pub fn load(&self, opts: &ScanOpts) -> Option<ScanResult> {
+        let text = fs::read(&self.path).ok()?;
+        let entry: Entry = serde_json::from_slice(&text).ok()?;
+        if entry.version != VERSION || entry.ignore_files != ignore_mtimes(opts) { return None; }
+        let unchanged = entry.result.dir_mtimes.par_iter()
+            .all(|(d, t)| fs::symlink_metadata(d).and_then(|m| m.modified()).is_ok_and(|now| now == *t));
+        unchanged.then_some(entry.result)
+    }
+
+    /// Store `result` (from a scan run with `record_dirs`) via a temporary file.
+    pub fn save(&self, opts: &ScanOpts, result: &ScanResult) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        }
+        let entry = serde_json::json!({ "version": VERSION, "ignore_files": ignore_mtimes(opts), "result": result });
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, serde_json::to_vec(&entry)?).with_context(|| format!("write {}", self.path.display()))?;
+        fs::rename(&tmp, &self.path).with_context(|| format!("write {}", self.path.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+//! Stopping a scan from outside it: `--timeout`, Ctrl-C, and quitting `--interactive` mid-scan.
+//!
+//! A `Cancel` is a shared flag the walk checks at each entry and the resolve at each link, so a
+//! cancelled scan returns within one filesystem call and reports what it found so far, marked
+//! partial. Clones share the flag; `armed` gives a clone that also trips at a deadline.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Why a scan was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// `Cancel::cancel` from the embedding code (the results browser)
+    Requested,
+    /// Ctrl-C
+    Interrupted,
+    /// `--timeout` ran out
+    TimedOut,
+}
+
+impl Reason {
+    fn code(self) -> u8 {
+        match self {
+            Reason::Requested => 1,
+            Reason::Interrupted => 2,
+            Reason::TimedOut => 3,
+        }
+    }
+
+    fn from_code(c: u8) -> Option<Reason> {
+        match c {
+            1 => Some(Reason::Requested),
+            2 => Some(Reason::Interrupted),
+            3 => Some(Reason::TimedOut),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Reason::Requested => "cancelled",
+            Reason::Interrupted => "interrupted",
+            Reason::TimedOut => "timed out (--timeout)",
+        })
+    }
+}
+
+/// A cancellation token; the default one is never cancelled unless asked.
+#[derive(Debug, Clone, Default)]
+pub struct Cancel {
+    /// 0 while running, else a `Reason` code; the first reason sticks
+    state: Arc<AtomicU8>,
+    deadline: Option<Instant>,
+}
+
+impl Cancel {
+    /// This token, also cancelled once `limit` (if any) has passed from now.
+    pub fn armed(&self, limit: Option<Duration>) -> Cancel {
+        Cancel { state: Arc::clone(&self.state), deadline: limit.map(|l| Instant::now() + l) }
+    }
+
+    /// Ask every scan holding this token (or a clone of it) to stop.
+    pub fn cancel(&self, why: Reason) {
+        let _ = self.state.compare_exchange(0, why.code(), Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        if self.state.load(Ordering::Relaxed) != 0 { return true; }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            self.cancel(Reason::TimedOut);
+            return true;
+        }
+        false
+    }
+
+    /// Why it was cancelled, if it was; a deadline counts once a check has seen it pass, so a scan
+    /// that finished before looking again is complete.
+    pub fn reason(&self) -> Option<Reason> {
+        Reason::from_code(self.state.load(Ordering::Relaxed))
+    }
+}
+
+/// Cancel `token` on Ctrl-C until the guard is dropped; a second Ctrl-C exits at once. Unix only:
+/// elsewhere Ctrl-C still ends the process.
+pub fn on_interrupt(token: &Cancel) -> InterruptGuard {
+    #[cfg(unix)]
+    {
+        // Leaked: the handler may still be reading it when the guard drops
+        INTERRUPT.store(Arc::into_raw(Arc::clone(&token.state)).cast_mut(), Ordering::Release);
+        // SAFETY: the handler only does atomic operations and `_exit`, all async-signal-safe
+        unsafe { libc::signal(libc::SIGINT, interrupted as extern "C" fn(libc::c_int) as libc::sighandler_t); }
+    }
+    #[cfg(not(unix))]
+    let _ = token;
+    InterruptGuard(())
+}
+
+/// Restores the default Ctrl-C behaviour when dropped.
+pub struct InterruptGuard(());
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            // SAFETY: resets the disposition set in `on_interrupt`
+            unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL); }
+            INTERRUPT.store(std::ptr::null_mut(), Ordering::Release);
+        }
+    }
+}
+
+/// The state of the token Ctrl-C cancels; a handler cannot take a lock, so it is a bare pointer
+#[cfg(unix)]
+static INTERRUPT: std::sync::atomic::AtomicPtr<AtomicU8> = std::sync::atomic::AtomicPtr::new(std::ptr::null_mut());
+
+#[cfg(unix)]
+extern "C" fn interrupted(_: libc::c_int) {
+    // SAFETY: set from a leaked `Arc`, so it is never freed
+    let Some(state) = (unsafe { INTERRUPT.load(Ordering::Acquire).as_ref() }) else { return };
+    if state.compare_exchange(0, Reason::Interrupted.code(), Ordering::Relaxed, Ordering::Relaxed).is_err() {
+        // SAFETY: async-signal-safe; a second Ctrl-C means "now"
+        unsafe { libc::_exit(130) };
+    }
+}
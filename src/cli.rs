@@ -0,0 +1,920 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use crate::actions::Action;
+use crate::cancel::Cancel;
+use crate::filter::Filter;
+use crate::icons::IconMode;
+use crate::notify::NotifyMode;
+use crate::output::{PathEncoding, Quote};
+use crate::perm::{Perm, PermOf};
+use crate::preset::Preset;
+use crate::progress::ProgressMode;
+use crate::template::Template;
+
+/// `--version`: the build counter plus where the binary came from.
+const LONG_VERSION: &str = concat!(
+    env!("PKG_VERSION_WITH_BUILD"),
+    "\ncommit: ", env!("GIT_COMMIT"),
+    "\nbuilt: ", env!("BUILD_DATE"),
+    "\ntarget: ", env!("BUILD_TARGET"),
+    "\nfeatures: ", env!("BUILD_FEATURES"),
+);
+
+#[derive(Parser, Debug)]
+#[command(version = env!("PKG_VERSION_WITH_BUILD"), long_version = LONG_VERSION, about = "Fast symlink finder (Rust)")]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalOpts,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Find symlinks that resolve to a target (the default: `find-symlinks <TARGET>...`)
+    Find(Box<FindOpts>),
+    /// List every symlink under the roots with its link text and where it resolves (`find --all`)
+    List(Box<FindOpts>),
+    /// Report every symlink under the roots that does not resolve, and why (`find --broken`)
+    Check(Box<FindOpts>),
+    /// Change the matched symlinks: `find` with one of --delete, --retarget, --rewrite-prefix, --make-relative, --make-absolute, or --dereference-in-place required
+    Fix(Box<FindOpts>),
+    /// Scan, then report links that come to match, stop matching, or change their link text until stopped (`find --watch`)
+    Watch(Box<FindOpts>),
+    /// Time repeated scans of the same tree without printing matches
+    Bench(Box<BenchOpts>),
+    /// Build and query a persistent symlink index (SQLite)
+    Index(IndexOpts),
+    /// Combine --json reports from several hosts into one document keyed by host
+    Merge(MergeOpts),
+    /// Score the symlinks under the scan root (--root, default the current directory): broken, escaping, cross-device, and deep chains
+    Health(HealthOpts),
+    /// Time walk and resolve settings on the scan root and save the fastest as `[tuning]` in the machine-wide config file
+    Tune(TuneOpts),
+    /// Query the index through the socket of `index watch --socket` or `daemon` (one JSON response per line)
+    Client(ClientOpts),
+    /// Keep an in-memory index of the symlinks under the roots, updated from filesystem events, and answer `client` queries on a Unix socket until stopped
+    Daemon(DaemonOpts),
+    /// Put back the symlinks changed by a `find` run with --journal, newest change first
+    Undo(UndoOpts),
+}
+
+/// Options shared by every subcommand.
+#[derive(Args, Debug)]
+#[command(next_help_heading = "Global Options")]
+pub struct GlobalOpts {
+    /// Color output: auto, always, or never
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+    /// Prefix matches and summary lines with icons: auto, nerd, emoji, or none
+    #[arg(long, global = true, value_enum, default_value_t = IconMode::Auto)]
+    pub icons: IconMode,
+    /// Use plain ASCII for boxes, spinners, and ellipses (auto on non-UTF-8 terminals)
+    #[arg(long, global = true, action = ArgAction::SetTrue)]
+    pub ascii: bool,
+    /// Config file (default: $FIND_SYMLINKS_CONFIG or <config dir>/find-symlinks/config.toml)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Hold an advisory lock on FILE while running; exit with status 75 if another instance holds it
+    #[arg(long, global = true, value_name = "FILE")]
+    pub lock: Option<PathBuf>,
+    /// With --lock, wait for the other instance instead of exiting
+    #[arg(long, global = true, action = ArgAction::SetTrue, requires = "lock")]
+    pub lock_wait: bool,
+    /// Print version, commit, build date, target, and enabled features as JSON
+    #[arg(long, global = true, action = ArgAction::SetTrue)]
+    pub version_json: bool,
+}
+
+/// What to match and how to walk.
+#[derive(Args, Debug)]
+pub struct ScanOpts {
+    /// Absolute path(s) to target(s) to match against
+    #[arg(value_name = "TARGET", required_unless_present_any = ["samefile", "lname", "ilname", "targets_file", "link_text", "target_regex", "broken", "all"], num_args = 1..)]
+    pub targets: Vec<String>,
+    /// Same as a TARGET argument (GNU find `-samefile`)
+    #[arg(long, value_name = "FILE")]
+    pub samefile: Vec<String>,
+    /// Read more targets from a file (`-` for stdin), one per line or NUL-separated (`find -print0`)
+    #[arg(long, value_name = "PATH")]
+    pub targets_file: Option<PathBuf>,
+    /// Also match links whose text matches a glob, like GNU find `-lname` (`*` matches `/` too). Repeatable.
+    #[arg(long, value_name = "PATTERN")]
+    pub lname: Vec<String>,
+    /// Case-insensitive --lname (GNU find `-ilname`). Repeatable.
+    #[arg(long, value_name = "PATTERN")]
+    pub ilname: Vec<String>,
+    /// Also match links whose raw text (as `readlink` prints it, never resolved) is PATTERN, e.g. `../shared/config`. Repeatable.
+    #[arg(long, value_name = "PATTERN")]
+    pub link_text: Vec<String>,
+    /// How --link-text compares: the exact text, a prefix of it, or a glob (as --lname)
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = LinkTextMode::Exact, requires = "link_text")]
+    pub link_text_mode: LinkTextMode,
+    /// Also match links whose destination (resolved, or lexical if dangling) matches a regex in full, e.g. `.*/python3\.\d+/bin/python`. Repeatable.
+    #[arg(long, value_name = "RE")]
+    pub target_regex: Vec<String>,
+    /// Count links to anything inside a directory TARGET, not just to the directory itself (prefix match on canonical paths)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub under: bool,
+    /// Drop matches that point at or below PREFIX (resolved, or by link text if dangling), e.g. /nix/store. Repeatable.
+    #[arg(long = "exclude-target", value_name = "PREFIX", value_parser = target_prefix)]
+    pub exclude_targets: Vec<PathBuf>,
+    /// Keep matches whose target's mode passes MODE, as find's -perm: `644` exactly, `-644` all of these bits, `/022` any of them; symbolic modes like `/o+w` work too. Repeatable (all must pass)
+    #[arg(long = "perm", value_name = "MODE", value_parser = Perm::parse, allow_hyphen_values = true)]
+    pub perms: Vec<Perm>,
+    /// Which inode --perm tests: the link's target, or the link itself
+    #[arg(long, value_enum, value_name = "WHICH", default_value_t = PermOf::Target, requires = "perms")]
+    pub perm_of: PermOf,
+    /// Report every symlink that does not resolve (missing, loop, permission denied) and why; needs no TARGET
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub broken: bool,
+    /// Inventory: report every symlink with its link text and where it resolves; needs no TARGET
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub all: bool,
+    /// Resolve dangling links whose text is a Windows path (`C:\Users\me`, `\??\C:\...`) through the WSL drive mounts (`/mnt/c/Users/me`); Windows-form TARGETs are accepted with it, or whenever running under WSL
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub wsl_links: bool,
+    /// With --wsl-links, also resolve `\\wsl$\<distro>\...` and `\\wsl.localhost\<distro>\...` text (Windows-side links into this distro)
+    #[arg(long, action = ArgAction::SetTrue, requires = "wsl_links")]
+    pub wsl_unc: bool,
+    /// Stop after N matches: links are resolved as the walk finds them, and the walk quits at the limit
+    #[arg(short = 'm', long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_count: Option<usize>,
+    /// Walk DIR before the other roots, resolving and reporting its links as they are found (e.g. /etc ahead of bulk storage). Repeatable; a DIR inside a --root is skipped by that root's walk
+    #[arg(long = "priority-root", value_name = "DIR", value_parser = scan_root)]
+    pub priority_roots: Vec<PathBuf>,
+    /// Skip directories holding more than N entries, listing each one skipped; guards interactive scans against directories with millions of files
+    #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_dir_entries: Option<usize>,
+    /// Retry a symlink's stat up to N times when it fails with an error network filesystems return transiently (EAGAIN, EIO, ESTALE)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    /// With --retries, wait DURATION before the first retry of a link and twice as long before each one after
+    #[arg(long, value_name = "DURATION", default_value = "100ms", value_parser = duration)]
+    pub retry_backoff: Duration,
+    /// Symlinks per resolve work unit outside link farms (directories with 64+ links, which get their own units); larger batches cut scheduling overhead on fast storage (default: `[tuning]` in the config, else 1)
+    #[arg(long, value_name = "N")]
+    pub resolve_batch: Option<usize>,
+    /// Keep the matches in a memory-mapped temporary file in DIR (default: the system temp directory) instead of in memory, for inventories larger than RAM; DIR should not be a tmpfs
+    #[arg(long, value_name = "DIR", num_args = 0..=1, require_equals = true)]
+    pub mmap_results: Option<Option<PathBuf>>,
+    #[command(flatten)]
+    pub walk: WalkOpts,
+    /// Keep only matches satisfying an expression, e.g. 'depth > 3 && owner == "root" && target_under("/opt")'
+    #[arg(long = "where", value_name = "EXPR", value_parser = Filter::parse)]
+    pub filter: Option<Filter>,
+    /// Also report regular files whose content is identical to a target (size filter, then blake3)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub copies: bool,
+    /// Also report regular files that are hard links to a file TARGET (same device and inode); `--hardlinks=only` skips symlink matching
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "also")]
+    pub hardlinks: Option<HardlinkMode>,
+    /// Record every symlink's resolution in the result (set by exports, not a flag)
+    #[arg(skip)]
+    pub record_links: bool,
+    /// Record every directory's mtime in the result (set by snapshots, not a flag)
+    #[arg(skip)]
+    pub record_dirs: bool,
+}
+
+impl ScanOpts {
+    /// Target-less options for walking every symlink (index, health); records links.
+    pub fn walk_only(walk: WalkOpts) -> ScanOpts {
+        ScanOpts {
+            targets: Vec::new(), samefile: Vec::new(), targets_file: None, lname: Vec::new(), ilname: Vec::new(), link_text: Vec::new(), link_text_mode: LinkTextMode::Exact, target_regex: Vec::new(), under: false, exclude_targets: Vec::new(), perms: Vec::new(), perm_of: PermOf::Target, broken: false, all: false, wsl_links: false, wsl_unc: false,
+            max_count: None, priority_roots: Vec::new(), max_dir_entries: None, retries: 0, retry_backoff: Duration::ZERO, resolve_batch: None, mmap_results: None, walk, filter: None, copies: false, hardlinks: None, record_links: true, record_dirs: false,
+        }
+    }
+
+    /// Fold `--samefile` and the entries of `--targets-file` into the TARGET arguments.
+    fn gather_targets(&mut self) -> std::io::Result<()> {
+        use std::io::{Error, ErrorKind, Read};
+        self.targets.append(&mut self.samefile);
+        let Some(path) = &self.targets_file else { return Ok(()) };
+        let data = if path.as_os_str() == "-" {
+            let mut buf = Vec::new();
+            std::io::stdin().read_to_end(&mut buf)?;
+            buf
+        } else {
+            std::fs::read(path)?
+        };
+        // NUL-separated if there are any NULs (a path cannot contain one), else one per line
+        let nul = data.contains(&0);
+        let before = self.targets.len();
+        for entry in data.split(|&b| b == if nul { 0 } else { b'\n' }) {
+            let entry = if nul { entry } else { entry.strip_suffix(b"\r").unwrap_or(entry) };
+            if entry.is_empty() { continue; }
+            let s = String::from_utf8(entry.to_vec())
+                .map_err(|_| Error::new(ErrorKind::InvalidData, format!("not UTF-8: {}", String::from_utf8_lossy(entry))))?;
+            self.targets.push(s);
+        }
+        if self.targets.len() == before { return Err(Error::new(ErrorKind::InvalidData, "no targets listed")); }
+        Ok(())
+    }
+
+    /// Fold `--priority-root`s into the roots: one outside every `--root` becomes a root of its own,
+    /// and a `--root` inside one is walked as part of it.
+    fn merge_priority_roots(&mut self) {
+        if self.priority_roots.is_empty() { return; }
+        self.priority_roots = dedupe_roots(&self.priority_roots);
+        let prio: Vec<PathBuf> = self.priority_roots.iter().map(|p| real_root(p)).collect();
+        let rest: Vec<PathBuf> = std::mem::take(&mut self.walk.roots).into_iter()
+            .filter(|r| !prio.iter().any(|p| real_root(r).starts_with(p)))
+            .collect();
+        let mut roots: Vec<PathBuf> = self.priority_roots.iter().zip(&prio)
+            .filter(|(_, p)| !rest.iter().any(|r| p.starts_with(real_root(r))))
+            .map(|(p, _)| p.clone())
+            .collect();
+        roots.extend(rest);
+        self.walk.roots = roots;
+    }
+
+    /// `gather_targets`, exiting like a clap usage error if the file cannot be read.
+    fn gather_targets_or_exit(&mut self) {
+        if let Err(e) = self.gather_targets() {
+            let name = self.targets_file.as_deref().unwrap_or(std::path::Path::new("-")).display().to_string();
+            Cli::command().error(clap::error::ErrorKind::Io, format!("--targets-file {}: {}", name, e)).exit();
+        }
+    }
+}
+
+impl WalkOpts {
+    /// Drop repeated roots and roots inside another: the enclosing root's walk already covers them.
+    fn dedupe_roots(&mut self) {
+        self.roots = dedupe_roots(&self.roots);
+    }
+}
+
+fn real_root(r: &Path) -> PathBuf {
+    crate::scan::realpath(r).unwrap_or_else(|_| r.to_path_buf())
+}
+
+fn dedupe_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let real: Vec<PathBuf> = roots.iter().map(|r| real_root(r)).collect();
+    let mut keep = Vec::new();
+    for (i, r) in real.iter().enumerate() {
+        // Name the outermost root that covers it; that one is kept
+        let covered = real.iter().enumerate()
+            .filter(|&(j, o)| j != i && r.starts_with(o) && (o != r || j < i))
+            .min_by_key(|(_, o)| o.components().count());
+        match covered {
+            Some((j, o)) => {
+                let how = if o == r { "the same directory as" } else { "inside" };
+                eprintln!("warning: root {} is {} {}; scanning it once", roots[i].display(), how, roots[j].display());
+            }
+            None => keep.push(roots[i].clone()),
+        }
+    }
+    keep
+}
+
+/// Which entries the walk visits.
+#[derive(Args, Debug, Clone)]
+pub struct WalkOpts {
+    /// Directory to scan (default: the working directory). Repeatable: each root gets its own parallel walker, so roots on different disks are read at the same time, and a root inside another is scanned once
+    #[arg(long = "root", value_name = "PATH", default_value = ".", value_parser = scan_root)]
+    pub roots: Vec<PathBuf>,
+    /// Scan hidden files and folders (on by default, matches `find`)
+    #[arg(long, action = ArgAction::SetFalse, default_value_t = true)]
+    pub hidden: bool,
+    /// Maximum depth to recurse
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+    /// Respect .gitignore during scan (off by default); also reads the global gitignore and .git/info/exclude
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub respect_gitignore: bool,
+    /// With --respect-gitignore, skip the global gitignore (core.excludesFile)
+    #[arg(long, action = ArgAction::SetTrue, requires = "respect_gitignore")]
+    pub no_global_gitignore: bool,
+    /// With --respect-gitignore, skip the repository's .git/info/exclude
+    #[arg(long, action = ArgAction::SetTrue, requires = "respect_gitignore")]
+    pub no_git_exclude: bool,
+    /// Do not cross filesystem boundaries
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub one_filesystem: bool,
+    /// With --one-filesystem, also stop at btrfs subvolumes and ZFS datasets that share the parent's device number
+    #[arg(long, action = ArgAction::SetTrue, requires = "one_filesystem")]
+    pub subvolumes: bool,
+    /// With --one-filesystem, print each directory the walk stopped at and why to stderr
+    #[arg(long, action = ArgAction::SetTrue, requires = "one_filesystem")]
+    pub list_boundaries: bool,
+    /// Abort once more than N walk/resolve errors (I/O, permissions, stale mounts) occur; results so far are reported as partial
+    #[arg(long, value_name = "N")]
+    pub max_errors: Option<usize>,
+    /// Give up on a directory or symlink that takes longer than DURATION to open or stat (`500ms`, `5s`, `1m`; plain numbers are seconds), logging it instead of letting a dead NFS or FUSE mount stall the walk
+    #[arg(long, value_name = "DURATION", value_parser = duration)]
+    pub op_timeout: Option<Duration>,
+    /// Stop the whole scan after DURATION (`30s`, `5m`) and report what it found so far as partial, as Ctrl-C does
+    #[arg(long, value_name = "DURATION", value_parser = duration)]
+    pub timeout: Option<Duration>,
+    /// Thread count for traversal (default: `[tuning]` in the config, else auto)
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+    /// Additional ignore glob(s) (gitignore-style). Repeatable.
+    #[arg(long = "ignore", value_name = "GLOB")]
+    pub ignores: Vec<String>,
+    /// Additional ignore file(s) to load patterns from. Repeatable.
+    #[arg(long = "ignore-file", value_name = "PATH")]
+    pub ignore_files: Vec<PathBuf>,
+    /// Do not descend into git submodules or nested repositories (directories with their own .git)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub skip_submodules: bool,
+    /// Filesystem access: std, or cap (cap-std handles confined to the scan root; needs the `cap-std` build feature)
+    #[arg(long, value_enum, default_value_t = Backend::Std)]
+    pub backend: Backend,
+    /// Include heavy directories like node_modules, .cache, target (off by default)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub include_heavy: bool,
+    /// Stops the walk and resolve when cancelled (set by Ctrl-C handling and the results browser, not a flag)
+    #[arg(skip)]
+    pub cancel: Cancel,
+}
+
+#[derive(Args, Debug)]
+pub struct FindOpts {
+    #[command(flatten)]
+    pub scan: ScanOpts,
+    /// Disable TUI progress output (same as `--progress none`)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub no_tui: bool,
+    /// Progress output: auto, bar, exact (bar after a counting pass, for a true walk percentage), plain (one status line per interval, for CI/screen readers), or none
+    #[arg(long, value_enum, default_value_t = ProgressMode::Auto)]
+    pub progress: ProgressMode,
+    /// Seconds between status lines with `--progress plain`
+    #[arg(long, value_name = "SECS", default_value_t = 5)]
+    pub progress_interval: u64,
+    /// When no progress would be shown (--no-tui, or stderr not a terminal), still print a status line every SECS seconds
+    #[arg(long, value_name = "SECS")]
+    pub heartbeat: Option<u64>,
+    /// Print nothing; exit 0 as soon as a match is confirmed, 1 if there is none, 2 on error (like `grep -q`)
+    #[arg(short = 'q', long, action = ArgAction::SetTrue, conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "output", "count_by_target", "via", "quote", "with_xattrs", "with_quarantine",
+        "post_filter", "preset", "changed_since", "save_snapshot", "export_sqlite", "stats_json", "copies", "hardlinks", "reclaim", "checks", "notify",
+    ])]
+    pub quiet: bool,
+    /// Remove the matched symlinks (never what they point to), asking y/N for each one
+    #[arg(long, action = ArgAction::SetTrue, group = "change", conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "from_manifest", "preset", "changed_since",
+    ])]
+    pub delete: bool,
+    /// With --delete, move each link to the trash (Recycle Bin on Windows) instead of removing it
+    #[arg(long, action = ArgAction::SetTrue, requires = "delete")]
+    pub trash: bool,
+    /// Point the matched symlinks at NEWPATH instead, asking y/N for each one; relative links stay relative
+    #[arg(long, value_name = "NEWPATH", value_parser = absolute_path, group = "change", conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "from_manifest", "preset", "changed_since",
+    ])]
+    pub retarget: Option<PathBuf>,
+    /// Match links that resolve to OLD or anything inside it (instead of TARGETs), and point each at the same place under NEW, asking y/N for each one
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"], value_parser = absolute_path, group = "change", conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "from_manifest", "preset", "changed_since",
+        "targets", "samefile", "targets_file", "lname", "ilname", "link_text", "target_regex", "broken", "all",
+    ])]
+    pub rewrite_prefix: Option<Vec<PathBuf>>,
+    /// Rewrite matched symlinks with absolute text as relative ones to the same place, asking y/N for each one
+    #[arg(long, action = ArgAction::SetTrue, group = "change", conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "from_manifest", "preset", "changed_since",
+    ])]
+    pub make_relative: bool,
+    /// Rewrite matched symlinks with relative text as absolute ones to the same place, asking y/N for each one
+    #[arg(long, action = ArgAction::SetTrue, group = "change", conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "from_manifest", "preset", "changed_since",
+    ])]
+    pub make_absolute: bool,
+    /// Replace the matched symlinks with copies of the files or directories they resolve to, asking y/N for each one
+    #[arg(long, action = ArgAction::SetTrue, group = "change", conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "from_manifest", "preset", "changed_since",
+    ])]
+    pub dereference_in_place: bool,
+    /// With --dereference-in-place, leave links whose target is larger than SIZE (bytes, or with K, M, G, T)
+    #[arg(long, value_name = "SIZE", default_value = "1G", value_parser = size, requires = "dereference_in_place")]
+    pub max_copy_size: u64,
+    /// With --dereference-in-place, hash each copy and its original (blake3) before putting the copy in place, and leave the link if they differ
+    #[arg(long, action = ArgAction::SetTrue, requires = "dereference_in_place")]
+    pub verify_copies: bool,
+    /// With a change (--delete, --retarget, --rewrite-prefix, --make-relative, --make-absolute, --dereference-in-place), change every match without asking
+    #[arg(short = 'y', long, action = ArgAction::SetTrue, requires = "change")]
+    pub yes: bool,
+    /// With a change (--delete, --retarget, --rewrite-prefix, --make-relative, --make-absolute, --dereference-in-place), list what would change and change nothing
+    #[arg(long, action = ArgAction::SetTrue, requires = "change")]
+    pub dry_run: bool,
+    /// With a change, append each link changed and its old link text to FILE (JSON lines), for `find-symlinks undo FILE`
+    #[arg(long, value_name = "FILE", requires = "change", conflicts_with = "dry_run")]
+    pub journal: Option<PathBuf>,
+    /// With a change on Windows, make links to directories junctions where creating symlinks is not permitted (no Developer Mode or elevated prompt)
+    #[arg(long, action = ArgAction::SetTrue, requires = "change")]
+    pub junction_fallback: bool,
+    /// Emit JSON matches (same as `--format json`)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+    /// JSON document version: 2 is an object with "schema" and a record per match; 1 is the older array of paths
+    #[arg(long, value_name = "N", default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..=2))]
+    pub json_schema: u32,
+    /// With --json, write each match as it is confirmed instead of the whole document, sorted by path, at the end; matches then come in the order they are found
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["no_stream", "count_by_target", "post_filter", "from_manifest"])]
+    pub stream_json: bool,
+    /// Emit one JSON object per match as it is found, for piping into `jq` (same as `--format json-lines`)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["json", "raw0"])]
+    pub json_lines: bool,
+    /// Output format; `parquet` writes a Parquet file to stdout (needs the `parquet` build feature)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// Write the results to FILE instead of stdout (replaced only once the run succeeds); text stats stay on the terminal
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["preset", "changed_since"])]
+    pub output: Option<PathBuf>,
+    /// Write raw path bytes, NUL-terminated, with no styling, box, or stats (for `xargs -0`)
+    #[arg(long, visible_alias = "print0", action = ArgAction::SetTrue, conflicts_with = "json")]
+    pub raw0: bool,
+    /// JSON encoding for non-UTF-8 paths: lossy strings, or objects with a base64/bytes `raw` field
+    #[arg(long, value_enum, value_name = "ENC", default_value_t = PathEncoding::Lossy)]
+    pub path_encoding: PathEncoding,
+    /// Shell-quote printed paths that need it, so they can be pasted into a shell: sh (default) or powershell
+    #[arg(long, value_enum, value_name = "SHELL", num_args = 0..=1, require_equals = true, default_missing_value = "sh", conflicts_with_all = ["json", "raw0", "preset"])]
+    pub quote: Option<Quote>,
+    /// Show security-relevant extended attributes (security.*, ACLs, trusted.*, user.*) of each matching link and its target
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["raw0", "preset"])]
+    pub with_xattrs: bool,
+    /// macOS: show the com.apple.quarantine flag (agent, date, approval) of each matching link and its target
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["raw0", "preset"])]
+    pub with_quarantine: bool,
+    /// Print one line per match from a template instead of the box and stats, e.g. "{path}\t{target}\t{depth}"
+    #[arg(long, value_name = "FORMAT", value_parser = Template::parse, conflicts_with_all = ["json", "json_lines", "raw0", "quote", "via", "count_by_target", "with_xattrs", "with_quarantine", "preset", "changed_since"])]
+    pub template: Option<Template>,
+    /// Run CMD for each match instead of printing it, several at a time; ARGs take the --template placeholders (`{}` is the path), and the path is appended if none has any. End with `;` when more options or TARGETs follow
+    #[arg(long, value_name = "CMD", num_args = 1.., allow_hyphen_values = true, value_terminator = ";", value_parser = Template::parse_arg, conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "output", "change", "from_manifest", "preset", "changed_since",
+    ])]
+    pub exec: Option<Vec<Template>>,
+    /// Run CMD once with the paths of all matches, in place of an ARG that is just `{}` or after the ARGs; split into several runs if they would not fit on one command line. End with `;` when more options or TARGETs follow
+    #[arg(long, value_name = "CMD", num_args = 1.., allow_hyphen_values = true, value_terminator = ";", conflicts_with_all = [
+        "exec", "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "output", "change", "from_manifest", "preset", "changed_since",
+    ])]
+    pub exec_batch: Option<Vec<OsString>>,
+    /// With --exec, how many commands run at once (default: one per CPU)
+    #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..), requires = "exec")]
+    pub exec_jobs: Option<usize>,
+    /// Browse the matches in a full-screen list as they are found: inspect link text against target, select, delete, retarget, or open in $EDITOR (needs the `tui` build feature)
+    #[arg(short = 'i', long, action = ArgAction::SetTrue, conflicts_with_all = [
+        "json", "json_lines", "format", "raw0", "template", "quiet", "count_by_target", "output", "change", "exec", "exec_batch", "from_manifest", "preset", "changed_since",
+        "post_filter", "no_stream", "export_sqlite", "save_snapshot", "cache", "estimate", "stats_json", "profile_ignores", "reclaim", "checks", "notify",
+    ])]
+    pub interactive: bool,
+    /// After the scan, keep watching the roots and report links that come to match, stop matching, or change their link text as it happens (Ctrl-C to stop); with --json or --json-lines, one JSON line per change
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = [
+        "interactive", "format", "raw0", "template", "quiet", "count_by_target", "output", "change", "exec", "exec_batch", "from_manifest", "preset", "changed_since", "max_count",
+    ])]
+    pub watch: bool,
+    /// Show the intermediate symlinks each match resolves through (`via` in JSON), to tell direct links from links to links
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["raw0", "preset", "changed_since", "from_manifest"])]
+    pub via: bool,
+    /// Filter the collected matches before output (same expressions as --where); snapshots and exports still get every match
+    #[arg(long, value_name = "EXPR", value_parser = Filter::parse, conflicts_with_all = ["preset", "changed_since"])]
+    pub post_filter: Option<Filter>,
+    /// Disable streaming matches; only show final boxed summary
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub no_stream: bool,
+    /// Print only a target -> inbound link count table, busiest first (with --all, one row per destination)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["raw0", "preset", "changed_since"])]
+    pub count_by_target: bool,
+    /// Also write matches, every symlink, errors, and stats to a SQLite database
+    #[arg(long, value_name = "FILE")]
+    pub export_sqlite: Option<PathBuf>,
+    /// Scan a well-known set of directories instead of the working directory
+    #[arg(long, value_enum, value_name = "NAME", conflicts_with_all = ["from_manifest", "changed_since", "save_snapshot", "export_sqlite", "roots", "broken", "all", "max_count", "max_dir_entries"])]
+    pub preset: Option<Preset>,
+    /// Match against an mtree or `find -ls` listing instead of walking the filesystem
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["roots", "all", "max_count", "max_dir_entries", "op_timeout", "retries"])]
+    pub from_manifest: Option<PathBuf>,
+    /// Save the directory mtimes and matches of this run, for a later --changed-since
+    #[arg(long, value_name = "FILE")]
+    pub save_snapshot: Option<PathBuf>,
+    /// Reuse the last results for the same roots, options, and targets when no walked directory has changed since (kept in <cache dir>/find-symlinks)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["preset", "from_manifest", "changed_since", "copies"])]
+    pub cache: bool,
+    /// Re-scan only directories changed since a saved snapshot and report added/removed/retargeted matches
+    #[arg(long, value_name = "SNAPSHOT", conflicts_with_all = ["from_manifest", "export_sqlite", "raw0", "copies", "hardlinks", "max_count", "max_dir_entries"])]
+    pub changed_since: Option<PathBuf>,
+    /// Before scanning, predict entries and duration (from the index, or a quick sampled walk) and show walk progress against it
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["from_manifest", "changed_since", "preset"])]
+    pub estimate: bool,
+    /// Write scan counts, timings, and walk/resolve rates as JSON to FILE ("-" for stderr)
+    #[arg(long, value_name = "FILE")]
+    pub stats_json: Option<PathBuf>,
+    /// After the scan, time extra walks with the ignore rules added one layer at a time (report on stderr)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub profile_ignores: bool,
+    /// Report the disk space the matched links occupy (and how much of it is in broken links)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["from_manifest", "changed_since", "preset"])]
+    pub reclaim: bool,
+    /// With --reclaim, also size targets that nothing but the matched links refers to
+    #[arg(long, action = ArgAction::SetTrue, requires = "reclaim")]
+    pub reclaim_orphans: bool,
+    /// Also look for references to the targets outside symlinks: mounts-config (/etc/fstab and systemd mount units). Repeatable.
+    #[arg(long = "check", value_enum, value_name = "CHECK", conflicts_with_all = ["changed_since", "preset"])]
+    pub checks: Vec<Check>,
+    /// When the scan finishes, send a desktop notification and/or ring the terminal bell with the match count
+    #[arg(long, value_enum, value_name = "HOW", num_args = 0..=1, require_equals = true, default_missing_value = "auto", conflicts_with = "preset")]
+    pub notify: Option<NotifyMode>,
+}
+
+impl FindOpts {
+    /// The change to make to each match after the scan, if any.
+    pub fn action(&self) -> Option<Action> {
+        if self.delete { return Some(if self.trash { Action::Trash } else { Action::Delete }); }
+        if self.make_relative { return Some(Action::MakeRelative); }
+        if self.make_absolute { return Some(Action::MakeAbsolute); }
+        if self.dereference_in_place { return Some(Action::Dereference { max_size: self.max_copy_size, verify: self.verify_copies }); }
+        if let Some([old, new]) = self.rewrite_prefix.as_deref() {
+            let real = crate::scan::realpath(old).ok();
+            return Some(Action::RewritePrefix { old: old.clone(), real, new: new.clone() });
+        }
+        self.retarget.clone().map(Action::Retarget)
+    }
+
+    /// `--rewrite-prefix OLD NEW` selects the links to OLD and everything inside it.
+    fn merge_rewrite_prefix(&mut self) {
+        let Some(old) = self.rewrite_prefix.as_ref().and_then(|p| p.first()) else { return };
+        self.scan.targets = vec![old.to_string_lossy().into_owned()];
+        self.scan.under = true;
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BenchOpts {
+    #[command(flatten)]
+    pub scan: ScanOpts,
+    /// Number of timed runs
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub runs: usize,
+    /// Untimed runs first, to warm the OS caches
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub warmup: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct IndexOpts {
+    /// Index database (default: <data dir>/find-symlinks/index.sqlite)
+    #[arg(long, value_name = "FILE")]
+    pub db: Option<PathBuf>,
+    #[command(subcommand)]
+    pub action: IndexAction,
+}
+
+#[derive(Args, Debug)]
+pub struct HealthOpts {
+    #[command(flatten)]
+    pub walk: WalkOpts,
+    /// Chains longer than this many symlink hops count as deep
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub deep: usize,
+    /// Print the score and counts as a JSON object
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TuneOpts {
+    #[command(flatten)]
+    pub walk: WalkOpts,
+    /// Print the timings and recommendation without writing the config file
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub dry_run: bool,
+    /// Save to your own config file instead of the machine-wide one (for when you cannot write that)
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub user: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct WatchOpts {
+    #[command(flatten)]
+    pub walk: WalkOpts,
+    /// Answer JSON queries on a Unix socket (default: <data dir>/find-symlinks/index.sock)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true)]
+    pub socket: Option<Option<PathBuf>>,
+}
+
+#[derive(Args, Debug)]
+pub struct DaemonOpts {
+    #[command(flatten)]
+    pub walk: WalkOpts,
+    /// Unix socket to answer queries on (default: <data dir>/find-symlinks/index.sock)
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ClientOpts {
+    /// Socket of `index watch --socket` or `daemon` (default: <data dir>/find-symlinks/index.sock)
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+    /// At most N links per response; a response cut short carries "next" for --cursor
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+    /// Start after this path (the "next" of the previous page)
+    #[arg(long, value_name = "PATH")]
+    pub cursor: Option<String>,
+    /// Request page after page until the last, printing one response line per page
+    #[arg(long, action = ArgAction::SetTrue, requires = "limit")]
+    pub follow: bool,
+    #[command(subcommand)]
+    pub request: ClientRequest,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ClientRequest {
+    /// Links that resolve to PATH (`{"op": "target"}`)
+    Target {
+        path: PathBuf,
+        /// Also links that resolve to anything inside PATH
+        #[arg(long, action = ArgAction::SetTrue)]
+        under: bool,
+    },
+    /// Links at or below PATH (`{"op": "prefix"}`)
+    Prefix { path: PathBuf },
+    /// Links whose absolute path matches a glob; `*` stays within a component, `**` crosses them (`{"op": "glob"}`)
+    Glob { pattern: String },
+    /// Print a line per batch of index changes until the watcher stops (`{"op": "subscribe"}`)
+    Subscribe,
+    /// Remove the symlink at PATH through the watcher (`{"op": "delete"}`; needs `write_uids`)
+    Delete { path: PathBuf },
+    /// Point the symlink at PATH to TO, replacing it atomically (`{"op": "relink"}`; needs `write_uids`)
+    Relink { path: PathBuf, to: PathBuf },
+    /// Send REQUEST, a JSON object, as is
+    Raw { request: String },
+}
+
+#[derive(Args, Debug)]
+pub struct UndoOpts {
+    /// Journal written by `find --journal`
+    #[arg(value_name = "JOURNAL")]
+    pub journal: PathBuf,
+    /// Restore every link without asking
+    #[arg(short = 'y', long, action = ArgAction::SetTrue)]
+    pub yes: bool,
+    /// List what would be restored and change nothing
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub dry_run: bool,
+    /// On Windows, restore links to directories as junctions where creating symlinks is not permitted
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub junction_fallback: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct MergeOpts {
+    /// Reports written by `find --json`; the host is the report's "host" field or the file name without extension
+    #[arg(required = true, num_args = 1.., value_name = "REPORT")]
+    pub reports: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IndexAction {
+    /// Walk the scan root (--root, default the current directory) and replace the index with every symlink found
+    Build(WalkOpts),
+    /// Build the index, then keep it up to date from filesystem events until interrupted
+    Watch(WatchOpts),
+    /// Run a read-only SQL query against the index (tables: symlinks, errors, stats)
+    Sql {
+        /// e.g. "SELECT resolved, count(*) n FROM symlinks GROUP BY resolved ORDER BY n DESC LIMIT 10"
+        query: String,
+        /// Print rows as a JSON array of objects
+        #[arg(long, action = ArgAction::SetTrue)]
+        json: bool,
+    },
+    /// Write every indexed symlink (path, link_text, resolved, error) to stdout, sorted by path
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// RFC 4180 CSV with a header row; NULLs are empty fields
+    Csv,
+    /// One JSON object per line; NULLs are null
+    Jsonl,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LinkTextMode {
+    /// The whole text, byte for byte
+    Exact,
+    /// Text starting with PATTERN
+    Prefix,
+    /// A glob over the text (`*` matches `/` too)
+    Glob,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HardlinkMode {
+    /// Hard links as well as symlinks
+    Also,
+    /// Hard links only; symlinks are counted but not matched
+    Only,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// Parallel walk with the `ignore` crate and ordinary path lookups
+    Std,
+    /// Walk and resolve through cap-std `Dir` handles; nothing outside the scan root is reachable
+    Cap,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Streamed lines, boxed summary, and stats
+    Text,
+    /// JSON array of matches
+    Json,
+    /// Parquet with one row per match and metadata columns
+    Parquet,
+    /// mtree specification: one `type=link link=...` entry per match
+    Mtree,
+    /// NDJSON: one object per match (`path`, `target`, `matched_target`, `broken`), written as it is found
+    JsonLines,
+}
+
+/// Extra reference checks for `--check`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Check {
+    /// Bind-mount sources and mount points in /etc/fstab and systemd .mount/.automount units
+    MountsConfig,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ColorChoice { Auto, Always, Never }
+
+/// Parse argv, treating a bare `find-symlinks [OPTIONS] <TARGET>` as `find-symlinks find ...`.
+pub fn parse() -> Cli {
+    let args = find_compat(std::env::args_os().collect());
+    // Handled ahead of clap, like --version: it must work without a subcommand or targets
+    if args.iter().skip(1).take_while(|a| *a != "--").any(|a| a == "--version-json") {
+        println!("{}", serde_json::to_string_pretty(&version_json()).unwrap_or_default());
+        std::process::exit(0);
+    }
+    let mut cmd = Cli::command();
+    for name in ["find", "list", "check", "fix", "watch"] {
+        // `--rewrite-prefix OLD` stands in for TARGET, but only `find` has it (`bench` shares TARGET)
+        cmd = cmd.mut_subcommand(name, |c| c.mut_arg("targets", |a| a.required_unless_present_any(["rewrite_prefix"])));
+    }
+    for (name, flag) in FIND_WITH {
+        // Given anyway, the implied flag is not an error
+        cmd = cmd.mut_subcommand(name, |c| c.args_override_self(true).mut_arg(flag, |a| a.hide(true)));
+    }
+    let mut cli = Cli::from_arg_matches(&cmd.get_matches_from(with_default_subcommand(args))).unwrap_or_else(|e| e.exit());
+    if let Command::Fix(o) = &cli.command {
+        if o.action().is_none() {
+            let msg = "fix needs a change: --delete, --retarget, --rewrite-prefix, --make-relative, --make-absolute, or --dereference-in-place";
+            Cli::command().error(clap::error::ErrorKind::MissingRequiredArgument, msg).exit();
+        }
+    }
+    match &mut cli.command {
+        Command::Find(o) | Command::List(o) | Command::Check(o) | Command::Fix(o) | Command::Watch(o) => {
+            o.scan.gather_targets_or_exit();
+            o.scan.walk.dedupe_roots();
+            o.scan.merge_priority_roots();
+            o.merge_rewrite_prefix();
+        }
+        Command::Bench(o) => { o.scan.gather_targets_or_exit(); o.scan.walk.dedupe_roots(); o.scan.merge_priority_roots(); }
+        Command::Health(o) => o.walk.dedupe_roots(),
+        Command::Tune(o) => o.walk.dedupe_roots(),
+        Command::Daemon(o) => o.walk.dedupe_roots(),
+        Command::Index(IndexOpts { action: IndexAction::Build(w) | IndexAction::Watch(WatchOpts { walk: w, .. }), .. }) => w.dedupe_roots(),
+        _ => {}
+    }
+    cli
+}
+
+/// `find`'s scan arguments on their own (TARGETs, `--root`, and the walk and match options), without
+/// the program name, for `Scanner`.
+pub fn parse_scan<I, T>(args: I) -> anyhow::Result<ScanOpts>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let cmd = ScanOpts::augment_args(clap::Command::new("find-symlinks").no_binary_name(true));
+    let mut opts = ScanOpts::from_arg_matches(&cmd.try_get_matches_from(args)?)?;
+    opts.gather_targets().map_err(|e| anyhow::anyhow!("--targets-file: {}", e))?;
+    opts.walk.dedupe_roots();
+    opts.merge_priority_roots();
+    Ok(opts)
+}
+
+/// `--root`: an existing directory.
+fn scan_root(s: &str) -> Result<PathBuf, String> {
+    match std::fs::metadata(s) {
+        Ok(m) if m.is_dir() => Ok(PathBuf::from(s)),
+        Ok(_) => Err("not a directory".into()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err("no such directory".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `--retarget`, `--rewrite-prefix`: made absolute but not resolved, so a symlink given as NEWPATH stays the link's target.
+fn absolute_path(s: &str) -> Result<PathBuf, String> {
+    Ok(crate::scan::lexical_absolute(Path::new(s)))
+}
+
+/// `500ms`, `5s`, `2m`, or a plain number of seconds.
+fn duration(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let n: f64 = n.parse().map_err(|_| format!("invalid duration {:?}", s))?;
+    let secs = match unit {
+        "ms" => n / 1000.0,
+        "" | "s" => n,
+        "m" => n * 60.0,
+        _ => return Err(format!("invalid duration {:?}: use ms, s, or m", s)),
+    };
+    Duration::try_from_secs_f64(secs).ok().filter(|d| !d.is_zero()).ok_or_else(|| format!("invalid duration {:?}", s))
+}
+
+/// `512`, `64K`, `1.5G`: bytes, or binary K/M/G/T (a trailing `B` or `iB` is allowed).
+fn size(s: &str) -> Result<u64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let n: f64 = n.parse().map_err(|_| format!("invalid size {:?}", s))?;
+    let shift = match unit.trim_end_matches("iB").trim_end_matches('B').to_ascii_uppercase().as_str() {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("invalid size {:?}: use K, M, G, or T", s)),
+    };
+    Ok((n * (1u64 << shift) as f64) as u64)
+}
+
+/// `--exclude-target`: canonical if it exists (links are compared by where they resolve), else absolute.
+fn target_prefix(s: &str) -> Result<PathBuf, String> {
+    Ok(crate::scan::realpath(Path::new(s)).unwrap_or_else(|_| crate::scan::lexical_absolute(Path::new(s))))
+}
+
+/// `--version-json`: the same fields as `--version`, for fleet inventories.
+fn version_json() -> serde_json::Value {
+    let features = env!("BUILD_FEATURES");
+    serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "build": env!("BUILD_NUMBER").parse::<u64>().unwrap_or(0),
+        "commit": env!("GIT_COMMIT"),
+        "build_date": env!("BUILD_DATE"),
+        "target": env!("BUILD_TARGET"),
+        "features": if features == "none" { Vec::new() } else { features.split(',').collect() },
+    })
+}
+
+/// Accept GNU find's single-dash spellings (`-samefile`, `-lname`, `-ilname`) before `--`.
+fn find_compat(args: Vec<OsString>) -> Vec<OsString> {
+    let mut seen_dashdash = false;
+    args.into_iter()
+        .map(|a| {
+            if a == "--" { seen_dashdash = true; }
+            match a.to_str() {
+                Some(flag @ ("-samefile" | "-lname" | "-ilname")) if !seen_dashdash => format!("-{}", flag).into(),
+                _ => a,
+            }
+        })
+        .collect()
+}
+
+/// Subcommands that are `find` with a flag always on, and that flag's argument id.
+const FIND_WITH: [(&str, &str); 3] = [("list", "all"), ("check", "broken"), ("watch", "watch")];
+
+/// Insert `find` before a bare `[OPTIONS] <TARGET>`, and the flag of a `FIND_WITH` subcommand
+/// after its name.
+fn with_default_subcommand(mut args: Vec<OsString>) -> Vec<OsString> {
+    let mut cmd = Cli::command();
+    cmd.build();
+    // Only top-level options (the global ones, --help, --version) can come before a subcommand,
+    // so the first argument that is not one of them or its value decides
+    let mut i = 1;
+    while let Some(a) = args.get(i).and_then(|a| a.to_str()).filter(|a| a.starts_with('-') && *a != "-" && *a != "--") {
+        let (name, inline) = a.split_once('=').map_or((a, false), |(name, _)| (name, true));
+        let arg = cmd.get_arguments().find(|arg| match name.strip_prefix("--") {
+            Some(long) => arg.get_long() == Some(long),
+            None => name.len() == 2 && arg.get_short() == name.chars().nth(1),
+        });
+        let Some(arg) = arg else { break };
+        let takes_value = arg.get_action().takes_values() && !arg.is_require_equals_set() && !inline;
+        i += if takes_value { 2 } else { 1 };
+    }
+    let top_level_only = matches!(
+        args.get(1).and_then(|a| a.to_str()),
+        None | Some("-h" | "--help" | "-V" | "--version")
+    );
+    let explicit = args.get(i).and_then(|a| a.to_str()).filter(|a| *a == "help" || cmd.find_subcommand(a).is_some());
+    match explicit {
+        Some(name) => {
+            if let Some((_, flag)) = FIND_WITH.iter().find(|(n, _)| *n == name) { args.insert(i + 1, format!("--{}", flag).into()); }
+        }
+        None if !top_level_only => args.insert(1, "find".into()),
+        None => {}
+    }
+    args
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{ScanOpts, WalkOpts};
+
+/// User configuration loaded from `config.toml`.
+///
+/// Looked up in this order: `--config`, `$FIND_SYMLINKS_CONFIG`, then
+/// `<config dir>/find-symlinks/config.toml`. A missing file is not an error. Each `[tuning]`
+/// setting it leaves unset is taken from the machine-wide file (see [`system_path`]).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub progress: ProgressConfig,
+    pub tuning: TuningConfig,
+    pub socket: SocketConfig,
+}
+
+/// `[socket]` section: which peer UIDs may use the socket of `index watch --socket`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SocketConfig {
+    /// UIDs allowed to query and subscribe; unset allows any client that can open the socket.
+    pub query_uids: Option<Vec<u32>>,
+    /// UIDs allowed to `delete` and `relink` links, besides the watcher's own.
+    pub write_uids: Vec<u32>,
+}
+
+/// `[tuning]` section, written by `tune`: defaults for settings the command line leaves unset.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuningConfig {
+    /// Walk threads (`--threads`).
+    pub threads: Option<usize>,
+    /// Threads for resolving links; `RAYON_NUM_THREADS` takes precedence.
+    pub resolve_threads: Option<usize>,
+    /// Symlinks per resolve work unit (`--resolve-batch`).
+    pub resolve_batch: Option<usize>,
+}
+
+/// The machine-wide file `tune` writes: only a `[tuning]` section.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SystemConfig {
+    tuning: TuningConfig,
+}
+
+impl TuningConfig {
+    /// Each setting from `self`, else from `base`.
+    fn or(self, base: TuningConfig) -> TuningConfig {
+        TuningConfig {
+            threads: self.threads.or(base.threads),
+            resolve_threads: self.resolve_threads.or(base.resolve_threads),
+            resolve_batch: self.resolve_batch.or(base.resolve_batch),
+        }
+    }
+
+    pub fn apply_walk(&self, walk: &mut WalkOpts) {
+        walk.threads = walk.threads.or(self.threads);
+    }
+
+    pub fn apply(&self, scan: &mut ScanOpts) {
+        self.apply_walk(&mut scan.walk);
+        scan.resolve_batch = scan.resolve_batch.or(self.resolve_batch);
+    }
+
+    /// Size the global resolve pool; a no-op once it has been used.
+    pub fn init_pool(&self) {
+        let Some(n) = self.resolve_threads else { return };
+        if std::env::var_os("RAYON_NUM_THREADS").is_some() { return; }
+        let _ = rayon::ThreadPoolBuilder::new().num_threads(n).build_global();
+    }
+}
+
+/// `[progress]` section: indicatif templates and redraw rate for the bars.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProgressConfig {
+    /// Template for the walk spinner. `{pos}` is entries walked, `{prefix}` the current directory.
+    pub walk_template: String,
+    /// Template for the walk bar with `--estimate`. `{pos}`/`{len}` are entries walked/estimated.
+    pub estimate_template: String,
+    /// Template for the resolve bar. `{pos}`/`{len}` are symlinks checked/total.
+    pub resolve_template: String,
+    /// Characters used to draw the resolve bar (filled, current, empty).
+    pub progress_chars: String,
+    /// Spinner redraw interval in milliseconds.
+    pub tick_ms: u64,
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        ProgressConfig {
+            walk_template: "{spinner:.green} {msg}".into(),
+            estimate_template: "{bar:40.green/blue} {pos}/~{len} {msg} (eta {eta})".into(),
+            resolve_template: "{bar:40.cyan/blue} {pos}/{len} {msg}".into(),
+            progress_chars: "##-".into(),
+            tick_ms: 80,
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    if let Some(p) = std::env::var_os("FIND_SYMLINKS_CONFIG") { return Some(PathBuf::from(p)); }
+    dirs::config_dir().map(|d| d.join("find-symlinks").join("config.toml"))
+}
+
+/// The machine-wide config file: `/etc/find-symlinks/config.toml`, or
+/// `%ProgramData%\find-symlinks\config.toml` on Windows.
+pub fn system_path() -> Option<PathBuf> {
+    #[cfg(unix)]
+    { Some(PathBuf::from("/etc/find-symlinks/config.toml")) }
+    #[cfg(windows)]
+    { std::env::var_os("ProgramData").map(|d| PathBuf::from(d).join("find-symlinks").join("config.toml")) }
+    #[cfg(not(any(unix, windows)))]
+    { None }
+}
+
+/// Where the config file is (or would be): `--config`, else the default location.
+pub fn path(explicit: Option<&Path>) -> Option<PathBuf> {
+    explicit.map(Path::to_path_buf).or_else(default_path)
+}
+
+/// Replace the `[tuning]` section of the config file at `path` (creating it if needed), keeping the rest as written.
+pub fn save_tuning(path: &Path, tuning: &TuningConfig) -> Result<()> {
+    let old = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("read config {}", path.display())),
+    };
+    let mut doc: toml_edit::DocumentMut = old.parse().with_context(|| format!("parse config {}", path.display()))?;
+    let body: toml_edit::DocumentMut = toml::to_string(tuning).context("serialize [tuning]")?.parse().context("serialize [tuning]")?;
+    // Refill an existing table in place, so its header and the comments around it stay
+    match doc.get_mut("tuning").and_then(toml_edit::Item::as_table_mut) {
+        Some(table) => {
+            table.clear();
+            table.extend(body.iter().map(|(k, v)| (k.to_string(), v.clone())));
+        }
+        None => doc["tuning"] = toml_edit::Item::Table(body.as_table().clone()),
+    }
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    fs::write(path, doc.to_string()).with_context(|| format!("write config {}", path.display()))
+}
+
+impl Config {
+    /// Load the config file over the machine-wide tuning; an explicit path must exist, the default
+    /// locations may not.
+    pub fn load(explicit: Option<&Path>) -> Result<Config> {
+        let system: SystemConfig = match system_path().filter(|p| p.is_file()) {
+            Some(p) => read(&p)?,
+            None => SystemConfig::default(),
+        };
+        let path = match explicit {
+            Some(p) => p.to_path_buf(),
+            None => match default_path() {
+                Some(p) if p.is_file() => p,
+                _ => return Ok(Config { tuning: system.tuning, ..Config::default() }),
+            },
+        };
+        let mut config: Config = read(&path)?;
+        config.tuning = config.tuning.or(system.tuning);
+        Ok(config)
+    }
+}
+
+fn read<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = fs::read_to_string(path).with_context(|| format!("read config {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("parse config {}", path.display()))
+}
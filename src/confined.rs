@@ -0,0 +1,195 @@
+//! `--backend cap`: walk and resolve through cap-std `Dir` handles.
+//!
+//! The scan root is the only directory opened with ambient authority; every later
+//! lookup is relative to it, so links (or directories swapped for links mid-scan)
+//! cannot lead the scan outside the root, whatever they point at.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use cap_std::{ambient_authority, fs::Dir};
+use rayon::prelude::*;
+
+use crate::cancel::Cancel;
+use crate::cli::{ScanOpts, WalkOpts};
+use crate::hooks::{Hooks, Phase};
+use crate::scan::{self, Match, ScanError, ScanResult, Target, HEAVY_DIRS};
+
+/// Options that look paths up outside the capability model or need the `ignore` walker.
+fn check_supported(opts: &ScanOpts) -> Result<()> {
+    let w = &opts.walk;
+    let unsupported = [
+        (w.respect_gitignore, "--respect-gitignore"),
+        (!w.ignores.is_empty(), "--ignore"),
+        (!w.ignore_files.is_empty(), "--ignore-file"),
+        (w.skip_submodules, "--skip-submodules"),
+        (w.one_filesystem, "--one-filesystem"),
+        (opts.filter.is_some(), "--where"),
+        (!opts.exclude_targets.is_empty(), "--exclude-target"),
+        (!opts.perms.is_empty(), "--perm"),
+        (opts.copies, "--copies"),
+        (opts.hardlinks.is_some(), "--hardlinks"),
+        (opts.wsl_links, "--wsl-links"),
+        (opts.max_dir_entries.is_some(), "--max-dir-entries"),
+        (w.op_timeout.is_some(), "--op-timeout"),
+        (opts.retries > 0, "--retries"),
+        (opts.record_links, "--export-sqlite, --format mtree, and the index"),
+    ];
+    if let Some((_, name)) = unsupported.iter().find(|(on, _)| *on) {
+        anyhow::bail!("{} is not supported with --backend cap", name);
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Walked {
+    dirs: usize,
+    files: usize,
+    symlinks: Vec<PathBuf>,
+    errors: Vec<ScanError>,
+}
+
+/// Past `--max-errors`, or cancelled: stop walking.
+fn aborted(opts: &WalkOpts, out: &Walked) -> bool {
+    opts.max_errors.is_some_and(|m| out.errors.len() > m) || opts.cancel.is_cancelled()
+}
+
+/// Depth-first walk of `dir` (at `rel`, `depth` below the root).
+fn visit(dir: &Dir, rel: &Path, depth: usize, opts: &WalkOpts, progress: &dyn Hooks, out: &mut Walked) {
+    let entries = match dir.entries() {
+        Ok(e) => e,
+        Err(e) => return out.errors.push(ScanError { path: Some(rel.to_path_buf()), message: e.to_string() }),
+    };
+    let child_depth = depth + 1;
+    for ent in entries {
+        if aborted(opts, out) { return; }
+        let ent = match ent {
+            Ok(e) => e,
+            Err(e) => { out.errors.push(ScanError { path: Some(rel.to_path_buf()), message: e.to_string() }); continue; }
+        };
+        let name = ent.file_name();
+        if !opts.hidden && name.to_string_lossy().starts_with('.') { continue; }
+        let Ok(ft) = ent.file_type() else { continue };
+        if ft.is_dir() && !opts.include_heavy && HEAVY_DIRS.contains(&name.to_string_lossy().as_ref()) { continue; }
+        let path = rel.join(&name);
+        progress.walked(&path, ft.is_dir(), ft.is_symlink());
+        if ft.is_symlink() {
+            out.symlinks.push(path);
+        } else if ft.is_dir() {
+            out.dirs += 1;
+            if opts.max_depth.is_some_and(|m| child_depth >= m) { continue; }
+            match ent.open_dir() {
+                Ok(sub) => visit(&sub, &path, child_depth, opts, progress, out),
+                Err(e) => out.errors.push(ScanError { path: Some(path), message: e.to_string() }),
+            }
+        } else if ft.is_file() {
+            out.files += 1;
+        }
+    }
+}
+
+/// Index of the target a symlink resolves to (or, if dangling, names).
+///
+/// cap-std treats absolute link text as an escape; text naming a path under the
+/// root (`root_abs`) is retried relative to the root.
+fn match_link(root: &Dir, root_abs: &Path, root_path: &Path, p: &Path, targets: &[Target], rel_targets: &[Option<PathBuf>], under: bool) -> Option<(usize, bool)> {
+    let rel = p.strip_prefix(root_path).unwrap_or(p);
+    let text = || root.read_link_contents(rel);
+    let live = root.canonicalize(rel).or_else(|e| match text()?.strip_prefix(root_abs) {
+        Ok(inside) => root.canonicalize(inside),
+        Err(_) => Err(e),
+    });
+    let hit = match &live {
+        Ok(c) => rel_targets.iter().enumerate()
+            .filter(|(_, t)| t.as_ref().is_some_and(|t| if under { c.starts_with(t) } else { c == t }))
+            .max_by_key(|(_, t)| t.as_ref().map(|t| t.components().count()))
+            .map(|(t, _)| (t, false)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => scan::match_link_text(p, &text().ok()?, targets).map(|t| (t, true)),
+        // Includes links that escape the root: they cannot reach a reachable target
+        Err(_) => None,
+    };
+    hit.or_else(|| {
+        let text = text().ok()?;
+        // Regexes see the absolute destination: the canonical path under the root, or the text if dangling
+        let dest = match &live {
+            Ok(c) => root_abs.join(c),
+            Err(_) => scan::lexical_absolute(&p.parent().unwrap_or(Path::new("")).join(&text)),
+        };
+        scan::match_link_pattern(&text, targets).or_else(|| scan::match_dest_regex(&dest, targets)).map(|t| (t, live.is_err()))
+    })
+        .or_else(|| scan::match_catch_all(targets, live.is_err()).map(|t| (t, live.is_err())))
+}
+
+pub fn scan(
+    opts: &ScanOpts,
+    targets: &[Target],
+    progress: &dyn Hooks,
+    on_match: &(dyn Fn(&Match) + Sync),
+    cancel: &Cancel,
+) -> Result<ScanResult> {
+    check_supported(opts)?;
+    let [root_path] = opts.walk.roots.as_slice() else { anyhow::bail!("--backend cap scans a single --root") };
+    let root = Dir::open_ambient_dir(root_path, ambient_authority()).context("open scan root")?;
+    let cwd = scan::realpath(root_path)?;
+    // Targets as canonical paths relative to the root, comparable with `Dir::canonicalize`
+    let rel_targets: Vec<Option<PathBuf>> = targets.iter()
+        .map(|t| {
+            let rel = t.resolved.as_deref().and_then(|r| r.strip_prefix(&cwd).ok()).map(Path::to_path_buf);
+            if t.resolved.is_some() && rel.is_none() {
+                eprintln!("warning: target {} is outside the scan root; --backend cap can only find broken links to it", t.given);
+            }
+            rel
+        })
+        .collect();
+
+    progress.phase(Phase::Walking);
+    let walk_start = Instant::now();
+    let mut walked = Walked { dirs: 1, ..Walked::default() };
+    // The walk sees the armed token (with its `--timeout` deadline)
+    let walk = WalkOpts { cancel: cancel.clone(), ..opts.walk.clone() };
+    if walk.max_depth != Some(0) { visit(&root, root_path, 0, &walk, progress, &mut walked); }
+    let walk_time = walk_start.elapsed();
+
+    let total = walked.symlinks.len();
+    let stopped = aborted(&walk, &walked);
+    progress.phase(Phase::Resolving { total });
+    let resolve_start = Instant::now();
+    let matches_out = Mutex::new(Vec::<Match>::new());
+    walked.symlinks.par_iter().for_each(|p| {
+        if cancel.is_cancelled() { return; }
+        if let Some((target, broken)) = match_link(&root, &cwd, root_path, p, targets, &rel_targets, opts.under) {
+            let m = Match { path: p.clone(), target, broken };
+            progress.matched(&m);
+            on_match(&m);
+            if let Ok(mut v) = matches_out.lock() { v.push(m); }
+        }
+        progress.resolved();
+    });
+    let resolve_time = resolve_start.elapsed();
+    progress.phase(Phase::Finished);
+
+    let mut matches = matches_out.into_inner().unwrap();
+    matches.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(ScanResult {
+        dirs: walked.dirs,
+        files: walked.files,
+        symlinks: total,
+        matches: matches.into(),
+        copies: Vec::new(),
+        hardlinks: Vec::new(),
+        links: Vec::new(),
+        errors: walked.errors,
+        dir_mtimes: Vec::new(),
+        walk_time,
+        resolve_time,
+        aborted: stopped || cancel.reason().is_some(),
+        cancelled: cancel.reason(),
+        limited: false,
+        roots: Vec::new(),
+        skipped_dirs: Vec::new(),
+        retries: 0,
+    })
+}
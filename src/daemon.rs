@@ -0,0 +1,213 @@
+//! `daemon`: the symlinks under the roots held in memory, kept up to date from filesystem events,
+//! and answered from over the query socket (the protocol of `index watch --socket`).
+//!
+//! Each link is kept by absolute path, and indexed again by where it resolves, so "who points at
+//! X" is a lookup rather than a scan. Batches of events are applied as `index watch` applies them
+//! to its database.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use ::notify::{RecursiveMode, Watcher};
+use anyhow::{Context, Result};
+use console::style;
+use num_format::{Locale, ToFormattedString};
+
+use crate::cli::{DaemonOpts, ScanOpts, WalkOpts};
+use crate::config::Config;
+use crate::index::{self, Batch};
+use crate::progress::{Progress, ProgressMode};
+use crate::scan::{self, LinkRecord};
+use crate::{socket, term};
+
+/// One symlink, as the `symlinks` table of the index has it.
+pub struct Entry {
+    pub link_text: Option<String>,
+    pub resolved: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Every symlink under the roots, by absolute path and by where it resolves.
+#[derive(Default)]
+pub struct MemIndex {
+    links: BTreeMap<String, Entry>,
+    /// Paths of the links resolving to each destination
+    by_dest: BTreeMap<String, BTreeSet<String>>,
+}
+
+fn text(p: &Path) -> String {
+    scan::lexical_absolute(p).to_string_lossy().into_owned()
+}
+
+/// `key/`, without doubling the slash of `/`.
+fn below(key: &str) -> String {
+    if key.ends_with('/') { key.to_string() } else { format!("{}/", key) }
+}
+
+impl MemIndex {
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Entry> {
+        self.links.get(path)
+    }
+
+    /// Every path, in order.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.links.keys().map(String::as_str)
+    }
+
+    /// Paths of the links at `key` or below it, in order.
+    pub fn at_or_below<'a>(&'a self, key: &str) -> impl Iterator<Item = &'a str> {
+        let (key, below) = (key.to_string(), below(key));
+        self.links.range(key.clone()..).map(|(p, _)| p.as_str()).take_while(move |p| *p == key || p.starts_with(&below))
+    }
+
+    /// Paths of the links resolving to `key`, or with `under` to anything below it, in order.
+    pub fn pointing_at(&self, key: &str, under: bool) -> Vec<&str> {
+        let mut paths: Vec<&str> = self.by_dest.get(key).into_iter().flatten().map(String::as_str).collect();
+        if under {
+            let below = below(key);
+            paths.extend(self.by_dest.range(below.clone()..).take_while(|(d, _)| d.starts_with(&below)).flat_map(|(_, v)| v.iter().map(String::as_str)));
+            paths.sort_unstable();
+        }
+        paths
+    }
+
+    /// Add or replace the link `l`; its key.
+    fn insert(&mut self, l: &LinkRecord) -> String {
+        let key = text(&l.path);
+        self.remove(&key);
+        // Link text is kept as written; only paths are made absolute
+        let e = Entry { link_text: l.link_text.as_deref().map(|t| t.to_string_lossy().into_owned()), resolved: l.resolved.as_deref().map(text), error: l.error.clone() };
+        if let Some(r) = &e.resolved { self.by_dest.entry(r.clone()).or_default().insert(key.clone()); }
+        self.links.insert(key.clone(), e);
+        key
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let e = self.links.remove(key)?;
+        if let Some(r) = &e.resolved {
+            if let Some(v) = self.by_dest.get_mut(r) {
+                v.remove(key);
+                if v.is_empty() { self.by_dest.remove(r); }
+            }
+        }
+        Some(e)
+    }
+
+    /// The link at `key`, and with `tree` everything below it; the paths removed.
+    fn remove_tree(&mut self, key: &str, tree: bool) -> Vec<String> {
+        let gone: Vec<String> = if tree { self.at_or_below(key).map(String::from).collect() } else { vec![key.to_string()] };
+        gone.into_iter().filter(|k| self.remove(k).is_some()).collect()
+    }
+}
+
+/// Paths a batch wrote and removed (a path removed and written again counts as written).
+#[derive(Default)]
+struct Changes {
+    updated: BTreeSet<String>,
+    removed: BTreeSet<String>,
+}
+
+/// Apply a batch of events, as `index watch` applies one to its database.
+fn apply(index: &mut MemIndex, batch: &Batch, roots: &[PathBuf], walk: &WalkOpts) -> Changes {
+    let mut changes = Changes::default();
+    // Links may resolve through a changed path, or through a changed link's old destination
+    let mut stale = BTreeSet::new();
+    for p in batch.changed.iter().filter(|p| index::reachable(p, roots, walk)) {
+        let key = text(p);
+        stale.extend(index.get(&key).and_then(|e| e.resolved.clone()));
+        match fs::symlink_metadata(p) {
+            Ok(m) if m.file_type().is_symlink() => { changes.updated.insert(index.insert(&scan::link_record(p))); }
+            Ok(m) if m.is_dir() && batch.created.contains(p) => {
+                changes.removed.extend(index.remove_tree(&key, true));
+                for l in scan::walk_symlinks(&WalkOpts { roots: vec![p.clone()], ..walk.clone() }) {
+                    changes.updated.insert(index.insert(&scan::link_record(&l)));
+                }
+            }
+            Ok(m) if m.is_dir() => {}
+            Ok(_) => changes.removed.extend(index.remove_tree(&key, false)),
+            Err(_) => changes.removed.extend(index.remove_tree(&key, true)),
+        }
+        stale.insert(key);
+    }
+    let mut recheck: BTreeSet<String> = BTreeSet::new();
+    for s in &stale { recheck.extend(index.pointing_at(s, true).into_iter().map(String::from)); }
+    // Something new may be what a broken link was waiting for
+    if !batch.created.is_empty() {
+        recheck.extend(index.links.iter().filter(|(_, e)| e.error.is_some()).map(|(p, _)| p.clone()));
+    }
+    for key in &recheck {
+        let p = Path::new(key);
+        if fs::symlink_metadata(p).is_ok_and(|m| m.file_type().is_symlink()) {
+            changes.updated.insert(index.insert(&scan::link_record(p)));
+        } else {
+            changes.removed.extend(index.remove_tree(key, true));
+        }
+    }
+    changes.removed.retain(|p| !changes.updated.contains(p));
+    changes
+}
+
+/// Walk the roots into a fresh index.
+fn build(walk: &WalkOpts, config: &Config, glyphs: &'static term::Glyphs) -> Result<MemIndex> {
+    let progress = Progress::new(ProgressMode::Auto, Duration::from_secs(5), &config.progress, glyphs)?;
+    let result = scan::scan(&ScanOpts::walk_only(walk.clone()), &[], &progress, &|_| {})?;
+    if result.aborted { return Err(crate::aborted_error(&result)); }
+    let mut index = MemIndex::default();
+    for l in &result.links { index.insert(l); }
+    Ok(index)
+}
+
+pub fn run(opts: DaemonOpts, config: &Config, glyphs: &'static term::Glyphs) -> Result<()> {
+    let walk = opts.walk;
+    let roots: Vec<PathBuf> = walk.roots.iter().map(|r| scan::lexical_absolute(r)).collect();
+    let Some(path) = opts.socket.or_else(socket::default_path) else { anyhow::bail!("no data directory on this platform; pass --socket") };
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = ::notify::recommended_watcher(tx).context("start filesystem watcher")?;
+    // Watching starts before the walk, so nothing changed during it is missed
+    for r in &roots {
+        watcher.watch(r, RecursiveMode::Recursive).with_context(|| format!("watch {}", r.display()))?;
+    }
+    let start = Instant::now();
+    let index = Arc::new(RwLock::new(build(&walk, config, glyphs)?));
+    let server = socket::Server::start(&path, socket::Source::Memory(Arc::clone(&index)), &roots, &config.socket)?;
+    let count = index.read().map_or(0, |i| i.len());
+    println!(
+        "{} {} {} {:.2}s",
+        style("Indexed").dim(),
+        style(count.to_formatted_string(&Locale::en)).bold().cyan(),
+        style("symlinks in memory in").dim(),
+        start.elapsed().as_secs_f64(),
+    );
+    println!("{} {}", style("Answering queries on").dim(), style(path.display()).bold());
+    println!("{}", style("Watching for changes (Ctrl-C to stop)").dim());
+    while let Ok(first) = rx.recv() {
+        let batch = Batch::gather(first, &rx);
+        if batch.rescan {
+            eprintln!("warning: watch: events were dropped; rebuilding the index");
+            // Queries keep getting the old index while the new one is walked
+            let fresh = build(&walk, config, glyphs)?;
+            *index.write().map_err(|_| anyhow::anyhow!("index lock poisoned"))? = fresh;
+            server.publish(&serde_json::json!({ "event": "rebuilt" }));
+            continue;
+        }
+        if batch.changed.is_empty() { continue; }
+        let changes = apply(&mut *index.write().map_err(|_| anyhow::anyhow!("index lock poisoned"))?, &batch, &roots, &walk);
+        if changes.updated.is_empty() && changes.removed.is_empty() { continue; }
+        server.publish(&serde_json::json!({ "event": "changed", "updated": changes.updated, "removed": changes.removed }));
+        println!(
+            "{} {} {} {}",
+            style("Updated").dim(),
+            style(changes.updated.len().to_formatted_string(&Locale::en)).bold().cyan(),
+            style("symlinks, removed").dim(),
+            style(changes.removed.len().to_formatted_string(&Locale::en)).bold().cyan(),
+        );
+    }
+    Ok(())
+}
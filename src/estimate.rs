@@ -0,0 +1,119 @@
+//! `--estimate`: predict a scan's size and duration before running it.
+//!
+//! The index (`find-symlinks index build`) is used when it was built from the
+//! (single) scan root. Otherwise random root-to-leaf probes estimate the tree
+//! size (Knuth's estimator): each probe lists one directory per level and
+//! weights the counts by the branching factors along its path.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::cli::WalkOpts;
+use crate::{index, scan};
+
+/// Stop sampling after this many probes or this long, whichever comes first.
+const MAX_PROBES: usize = 2_000;
+const SAMPLE_BUDGET: Duration = Duration::from_millis(500);
+
+pub struct Estimate {
+    pub entries: u64,
+    pub symlinks: u64,
+    pub secs: f64,
+    /// "index" or "sampled N probes"
+    pub source: String,
+}
+
+pub fn estimate(opts: &WalkOpts) -> Estimate {
+    let index = match opts.roots.as_slice() { [root] => from_index(root), _ => None };
+    index.unwrap_or_else(|| sample(opts))
+}
+
+/// The index's recorded counts and timing, if it was built for this scan root.
+fn from_index(root: &Path) -> Option<Estimate> {
+    let db = Connection::open_with_flags(index::default_path()?, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    let stat = |key: &str| db.query_row("SELECT value FROM stats WHERE key = ?1", [key], |r| r.get::<_, rusqlite::types::Value>(0)).ok();
+    let root = scan::realpath(root).ok()?;
+    // Indexes built before --root record only the working directory
+    match stat("root").or_else(|| stat("cwd"))? {
+        rusqlite::types::Value::Text(t) if Path::new(&t) == root => {}
+        _ => return None,
+    }
+    let int = |key: &str| match stat(key) { Some(rusqlite::types::Value::Integer(n)) => n.max(0) as u64, _ => 0 };
+    let secs = match stat("elapsed_secs") { Some(rusqlite::types::Value::Real(s)) => s, _ => 0.0 };
+    Some(Estimate {
+        entries: int("folders") + int("files") + int("symlinks"),
+        symlinks: int("symlinks"),
+        secs,
+        source: "index".into(),
+    })
+}
+
+/// One directory's immediate entries, after the walk's ignore rules.
+struct Listing {
+    entries: u64,
+    symlinks: u64,
+    dirs: Vec<PathBuf>,
+}
+
+fn list(opts: &WalkOpts, dir: &Path) -> Listing {
+    let mut wb = scan::build_subtree_walker(opts, dir);
+    wb.max_depth(Some(1));
+    let mut l = Listing { entries: 0, symlinks: 0, dirs: Vec::new() };
+    for e in wb.build().flatten().filter(|e| e.depth() > 0) {
+        let Some(ft) = e.file_type() else { continue };
+        l.entries += 1;
+        if ft.is_symlink() { l.symlinks += 1; } else if ft.is_dir() { l.dirs.push(e.into_path()); }
+    }
+    l
+}
+
+fn sample(opts: &WalkOpts) -> Estimate {
+    let start = Instant::now();
+    // xorshift64*, seeded from the clock: probes only need to be spread out, not unpredictable
+    let mut seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64) | 1;
+    let mut next = move |n: usize| {
+        seed ^= seed >> 12;
+        seed ^= seed << 25;
+        seed ^= seed >> 27;
+        (seed.wrapping_mul(0x2545_F491_4F6C_DD1D) % n as u64) as usize
+    };
+    let mut cache: HashMap<PathBuf, Listing> = HashMap::new();
+    let (mut entries, mut symlinks, mut probes) = (0.0, 0.0, 0);
+    let mut listed = 0u64;
+    while probes < MAX_PROBES && (probes == 0 || start.elapsed() < SAMPLE_BUDGET) {
+        // Several roots hang off a virtual root that is not itself counted
+        let n = opts.roots.len();
+        let (mut dir, mut depth, mut weight) = (opts.roots[next(n)].clone(), 0, n as f64);
+        // Each root counts as one directory, as in the walk
+        let (mut e, mut s) = (weight, 0.0);
+        while opts.max_depth.is_none_or(|m| depth < m) {
+            let l = cache.entry(dir.clone()).or_insert_with(|| {
+                let l = list(opts, &dir);
+                listed += l.entries;
+                l
+            });
+            e += weight * l.entries as f64;
+            s += weight * l.symlinks as f64;
+            depth += 1;
+            if l.dirs.is_empty() { break; }
+            weight *= l.dirs.len() as f64;
+            dir = l.dirs[next(l.dirs.len())].clone();
+        }
+        entries += e;
+        symlinks += s;
+        probes += 1;
+    }
+    let entries = (entries / probes as f64).round() as u64;
+    // The walk lists directories on every core; sampling lists them one at a time
+    let threads = opts.threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())).max(1);
+    let rate = listed as f64 / start.elapsed().as_secs_f64().max(1e-6) * threads as f64;
+    Estimate {
+        entries,
+        symlinks: (symlinks / probes as f64).round() as u64,
+        secs: entries as f64 / rate.max(1.0),
+        source: format!("sampled {} probes", probes),
+    }
+}
@@ -0,0 +1,114 @@
+//! `--exec CMD [ARG]... ;`: run a command for each match, several at a time.
+//!
+//! Arguments take the `--template` placeholders (`{}` is the path); a command with none gets the
+//! path as its last argument, as in `fd -x`. Each command's output is collected and printed in
+//! one piece when it exits, so output from parallel runs does not interleave.
+//!
+//! `--exec-batch CMD [ARG]... ;` runs the command once with every path, in place of a `{}`
+//! argument or at the end, splitting the paths over several runs (one after another) when they
+//! would not fit in the system's argument size limit.
+
+use std::borrow::Borrow;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+use rayon::prelude::*;
+
+use crate::scan::Match;
+use crate::template::{Context, Template};
+
+/// The command line for `m`.
+fn command_line(cmd: &[Template], m: &Match, cx: &Context) -> Vec<OsString> {
+    let mut argv: Vec<OsString> = cmd.iter()
+        .map(|t| if t.is_path() { m.path.clone().into_os_string() } else { t.render(m, cx).into() })
+        .collect();
+    if !cmd.iter().any(Template::has_fields) { argv.push(m.path.clone().into_os_string()); }
+    argv
+}
+
+/// Run `cmd` for each match, `jobs` at a time; returns how many failed to start or exited non-zero.
+pub fn run(cmd: &[Template], matches: impl Iterator<Item = impl Borrow<Match> + Send> + Send, cx: &Context, jobs: usize) -> Result<usize> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?;
+    let failed = pool.install(|| matches.par_bridge().filter(|m| !run_one(&command_line(cmd, m.borrow(), cx), m.borrow())).count());
+    Ok(failed)
+}
+
+/// Run one command line, passing its output on; whether it succeeded.
+fn run_one(argv: &[OsString], m: &Match) -> bool {
+    let Some((program, args)) = argv.split_first() else { return false };
+    let name = program.to_string_lossy();
+    match Command::new(program).args(args).stdin(Stdio::null()).output() {
+        Ok(out) => {
+            let _ = std::io::stdout().lock().write_all(&out.stdout);
+            let mut err = std::io::stderr().lock();
+            let _ = err.write_all(&out.stderr);
+            if !out.status.success() { let _ = writeln!(err, "exec: {}  {} failed ({})", m.path.display(), name, out.status); }
+            out.status.success()
+        }
+        Err(e) => {
+            eprintln!("exec: {}  could not run {}: {}", m.path.display(), name, e);
+            false
+        }
+    }
+}
+
+/// Run `cmd` with the paths of all `matches`, in as few runs as the argument limit allows; returns
+/// how many runs failed.
+pub fn run_batch(cmd: &[OsString], matches: impl IntoIterator<Item = impl Borrow<Match>>) -> usize {
+    let Some((program, args)) = cmd.split_first() else { return 0 };
+    let at = args.iter().position(|a| a == "{}");
+    let mut fixed = args.to_vec();
+    if let Some(i) = at { fixed.remove(i); }
+    let budget = arg_max().saturating_sub(env_size() + cmd.iter().map(|a| arg_size(a)).sum::<usize>());
+    let mut failed = 0;
+    let mut rest = matches.into_iter().peekable();
+    while rest.peek().is_some() {
+        // At least one path per run, so an oversized one is tried (and reported) rather than skipped
+        let mut chunk: Vec<OsString> = Vec::new();
+        let mut used = 0;
+        while let Some(m) = rest.next_if(|m| chunk.is_empty() || used + arg_size(m.borrow().path.as_os_str()) <= budget) {
+            let path = m.borrow().path.as_os_str();
+            used += arg_size(path);
+            chunk.push(path.to_os_string());
+        }
+        let n = chunk.len();
+        let mut command = Command::new(program);
+        match at {
+            Some(i) => command.args(&fixed[..i]).args(&chunk).args(&fixed[i..]),
+            None => command.args(&fixed).args(&chunk),
+        };
+        let name = program.to_string_lossy();
+        match command.stdin(Stdio::null()).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => { eprintln!("exec-batch: {} failed ({}) for {} paths", name, status, n); failed += 1; }
+            Err(e) => { eprintln!("exec-batch: could not run {}: {}", name, e); failed += 1; }
+        }
+    }
+    failed
+}
+
+/// What an argument takes out of the limit: its bytes, the NUL, and the pointer to it.
+fn arg_size(a: &OsStr) -> usize {
+    a.len() + 1 + std::mem::size_of::<usize>()
+}
+
+/// The environment shares the limit with the arguments.
+fn env_size() -> usize {
+    std::env::vars_os().map(|(k, v)| arg_size(&k) + v.len() + 1).sum()
+}
+
+/// Bytes of arguments and environment a new process may get, less room for the loader to spare.
+#[cfg(unix)]
+fn arg_max() -> usize {
+    // SAFETY: sysconf only reads a system limit
+    let n = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    usize::try_from(n).unwrap_or(128 * 1024).saturating_sub(4096)
+}
+
+/// Windows caps the whole command line at 32767 UTF-16 units.
+#[cfg(not(unix))]
+fn arg_max() -> usize {
+    32 * 1024 - 1024
+}
@@ -0,0 +1,318 @@
+//! `--where` predicate language evaluated per matching symlink.
+//!
+//! ```text
+//! expr    := or
+//! or      := and ("||" and)*
+//! and     := unary ("&&" unary)*
+//! unary   := "!" unary | compare
+//! compare := primary (("==" | "!=" | "<" | "<=" | ">" | ">=") primary)?
+//! primary := NUMBER | STRING | "true" | "false" | FIELD | FUNC "(" STRING ")" | "(" expr ")"
+//! ```
+//!
+//! Fields: `path`, `name`, `depth`, `target`, `link_text`, `broken`, `chain`, `uid`, `gid`, `owner`, `group`.
+//! Functions: `target_under(dir)`, `path_under(dir)`, `name_matches(glob)`, `path_matches(glob)`.
+
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use globset::{Glob, GlobMatcher};
+
+use crate::scan::{depth, lexical_absolute, link_chain};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field { Path, Name, Depth, Target, LinkText, Broken, Chain, Uid, Gid, Owner, Group }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp { Eq, Ne, Lt, Le, Gt, Ge }
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Cmp(Box<Expr>, CmpOp, Box<Expr>),
+    Num(i64),
+    Str(String),
+    Bool(bool),
+    Field(Field),
+    TargetUnder(PathBuf),
+    PathUnder(PathBuf),
+    NameMatches(GlobMatcher),
+    PathMatches(GlobMatcher),
+}
+
+#[derive(Debug, PartialEq)]
+enum Value { Num(i64), Str(String), Bool(bool), Null }
+
+/// A compiled `--where` expression.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Arc<Expr>,
+}
+
+/// The symlink a filter is evaluated against. Metadata and link text are read lazily.
+pub struct Candidate<'a> {
+    pub path: &'a Path,
+    /// Resolved destination (or, for broken links, the lexical one)
+    pub target: &'a Path,
+    pub broken: bool,
+    meta: OnceCell<Option<fs::Metadata>>,
+    link_text: OnceCell<Option<PathBuf>>,
+}
+
+impl<'a> Candidate<'a> {
+    pub fn new(path: &'a Path, target: &'a Path, broken: bool) -> Self {
+        Candidate { path, target, broken, meta: OnceCell::new(), link_text: OnceCell::new() }
+    }
+
+    fn meta(&self) -> Option<&fs::Metadata> {
+        self.meta.get_or_init(|| fs::symlink_metadata(self.path).ok()).as_ref()
+    }
+}
+
+impl Filter {
+    /// Parse an expression; usable as a clap `value_parser`.
+    pub fn parse(src: &str) -> Result<Filter, String> {
+        let tokens = lex(src)?;
+        let mut p = Parser { tokens, pos: 0 };
+        let expr = p.or()?;
+        if p.pos < p.tokens.len() {
+            return Err(format!("unexpected {} after expression", p.tokens[p.pos]));
+        }
+        Ok(Filter { expr: Arc::new(expr) })
+    }
+
+    /// True when the candidate satisfies the expression.
+    pub fn matches(&self, c: &Candidate) -> bool {
+        eval(&self.expr, c) == Value::Bool(true)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok { Num(i64), Str(String), Ident(String), Op(&'static str), LParen, RParen }
+
+impl std::fmt::Display for Tok {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Num(n) => write!(f, "{}", n),
+            Tok::Str(s) => write!(f, "{:?}", s),
+            Tok::Ident(s) => write!(f, "'{}'", s),
+            Tok::Op(o) => write!(f, "'{}'", o),
+            Tok::LParen => f.write_str("'('"),
+            Tok::RParen => f.write_str("')'"),
+        }
+    }
+}
+
+fn lex(src: &str) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() { i += 1; continue; }
+        if c == '(' { out.push(Tok::LParen); i += 1; continue; }
+        if c == ')' { out.push(Tok::RParen); i += 1; continue; }
+        if c == '"' || c == '\'' {
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string".into()),
+                    Some(&q) if q == c => { i += 1; break; }
+                    Some('\\') if i + 1 < chars.len() => { s.push(chars[i + 1]); i += 2; }
+                    Some(&ch) => { s.push(ch); i += 1; }
+                }
+            }
+            out.push(Tok::Str(s));
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() { i += 1; }
+            let s: String = chars[start..i].iter().collect();
+            out.push(Tok::Num(s.parse().map_err(|_| format!("number out of range: {}", s))?));
+            continue;
+        }
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1; }
+            out.push(Tok::Ident(chars[start..i].iter().collect()));
+            continue;
+        }
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let op = ["&&", "||", "==", "!=", "<=", ">="].into_iter().find(|o| *o == two)
+            .or_else(|| ["!", "<", ">"].into_iter().find(|o| o.starts_with(c)));
+        match op {
+            Some(o) => { out.push(Tok::Op(o)); i += o.len(); }
+            None => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+    Ok(out)
+}
+
+struct Parser {
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> { self.tokens.get(self.pos) }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Tok::Op(o)) if *o == op) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.and()?;
+        while self.eat_op("||") { lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?)); }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while self.eat_op("&&") { lhs = Expr::And(Box::new(lhs), Box::new(self.unary()?)); }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat_op("!") { return Ok(Expr::Not(Box::new(self.unary()?))); }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let lhs = self.primary()?;
+        let op = match self.peek() {
+            Some(Tok::Op("==")) => CmpOp::Eq,
+            Some(Tok::Op("!=")) => CmpOp::Ne,
+            Some(Tok::Op("<")) => CmpOp::Lt,
+            Some(Tok::Op("<=")) => CmpOp::Le,
+            Some(Tok::Op(">")) => CmpOp::Gt,
+            Some(Tok::Op(">=")) => CmpOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        Ok(Expr::Cmp(Box::new(lhs), op, Box::new(self.primary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let tok = self.peek().cloned().ok_or("unexpected end of expression")?;
+        self.pos += 1;
+        match tok {
+            Tok::Num(n) => Ok(Expr::Num(n)),
+            Tok::Str(s) => Ok(Expr::Str(s)),
+            Tok::LParen => {
+                let e = self.or()?;
+                if self.peek() != Some(&Tok::RParen) { return Err("expected ')'".into()); }
+                self.pos += 1;
+                Ok(e)
+            }
+            Tok::Ident(name) if self.peek() == Some(&Tok::LParen) => {
+                self.pos += 1;
+                let arg = match self.peek() {
+                    Some(Tok::Str(s)) => s.clone(),
+                    _ => return Err(format!("{}() takes one string argument", name)),
+                };
+                self.pos += 1;
+                if self.peek() != Some(&Tok::RParen) { return Err(format!("expected ')' after {}(...)", name)); }
+                self.pos += 1;
+                let glob = |g: &str| Glob::new(g).map(|g| g.compile_matcher()).map_err(|e| e.to_string());
+                match name.as_str() {
+                    "target_under" => Ok(Expr::TargetUnder(lexical_absolute(Path::new(&arg)))),
+                    "path_under" => Ok(Expr::PathUnder(lexical_absolute(Path::new(&arg)))),
+                    "name_matches" => Ok(Expr::NameMatches(glob(&arg)?)),
+                    "path_matches" => Ok(Expr::PathMatches(glob(&arg)?)),
+                    _ => Err(format!("unknown function '{}'", name)),
+                }
+            }
+            Tok::Ident(name) => Ok(match name.as_str() {
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                "path" => Expr::Field(Field::Path),
+                "name" => Expr::Field(Field::Name),
+                "depth" => Expr::Field(Field::Depth),
+                "target" => Expr::Field(Field::Target),
+                "link_text" => Expr::Field(Field::LinkText),
+                "broken" => Expr::Field(Field::Broken),
+                "chain" => Expr::Field(Field::Chain),
+                "uid" => Expr::Field(Field::Uid),
+                "gid" => Expr::Field(Field::Gid),
+                "owner" => Expr::Field(Field::Owner),
+                "group" => Expr::Field(Field::Group),
+                _ => return Err(format!("unknown field '{}'", name)),
+            }),
+            t => Err(format!("unexpected {}", t)),
+        }
+    }
+}
+
+fn eval(e: &Expr, c: &Candidate) -> Value {
+    match e {
+        Expr::Or(a, b) => Value::Bool(eval(a, c) == Value::Bool(true) || eval(b, c) == Value::Bool(true)),
+        Expr::And(a, b) => Value::Bool(eval(a, c) == Value::Bool(true) && eval(b, c) == Value::Bool(true)),
+        Expr::Not(a) => match eval(a, c) { Value::Bool(b) => Value::Bool(!b), _ => Value::Null },
+        Expr::Cmp(a, op, b) => {
+            let ord = match (eval(a, c), eval(b, c)) {
+                (Value::Num(x), Value::Num(y)) => x.cmp(&y),
+                (Value::Str(x), Value::Str(y)) => x.cmp(&y),
+                (Value::Bool(x), Value::Bool(y)) if matches!(op, CmpOp::Eq | CmpOp::Ne) => x.cmp(&y),
+                _ => return Value::Null,
+            };
+            Value::Bool(match op {
+                CmpOp::Eq => ord == Ordering::Equal,
+                CmpOp::Ne => ord != Ordering::Equal,
+                CmpOp::Lt => ord == Ordering::Less,
+                CmpOp::Le => ord != Ordering::Greater,
+                CmpOp::Gt => ord == Ordering::Greater,
+                CmpOp::Ge => ord != Ordering::Less,
+            })
+        }
+        Expr::Num(n) => Value::Num(*n),
+        Expr::Str(s) => Value::Str(s.clone()),
+        Expr::Bool(b) => Value::Bool(*b),
+        Expr::Field(f) => field(*f, c),
+        Expr::TargetUnder(dir) => Value::Bool(c.target.starts_with(dir)),
+        Expr::PathUnder(dir) => Value::Bool(lexical_absolute(c.path).starts_with(dir)),
+        Expr::NameMatches(g) => Value::Bool(c.path.file_name().is_some_and(|n| g.is_match(n))),
+        Expr::PathMatches(g) => Value::Bool(g.is_match(c.path)),
+    }
+}
+
+fn field(f: Field, c: &Candidate) -> Value {
+    let s = |p: &Path| Value::Str(p.to_string_lossy().into_owned());
+    match f {
+        Field::Path => s(c.path),
+        Field::Name => c.path.file_name().map_or(Value::Null, |n| Value::Str(n.to_string_lossy().into_owned())),
+        Field::Depth => Value::Num(depth(c.path) as i64),
+        Field::Target => s(c.target),
+        Field::LinkText => c.link_text.get_or_init(|| fs::read_link(c.path).ok()).as_deref().map_or(Value::Null, s),
+        Field::Broken => Value::Bool(c.broken),
+        // Symlinks followed: 1 for a direct link
+        Field::Chain => Value::Num(1 + link_chain(c.path).len() as i64),
+        Field::Uid | Field::Gid | Field::Owner | Field::Group => owner_field(f, c.meta()),
+    }
+}
+
+#[cfg(unix)]
+fn owner_field(f: Field, meta: Option<&fs::Metadata>) -> Value {
+    use std::os::unix::fs::MetadataExt;
+    let Some(m) = meta else { return Value::Null };
+    match f {
+        Field::Uid => Value::Num(m.uid() as i64),
+        Field::Gid => Value::Num(m.gid() as i64),
+        Field::Owner => crate::users::user_name(m.uid()).map_or(Value::Null, Value::Str),
+        _ => crate::users::group_name(m.gid()).map_or(Value::Null, Value::Str),
+    }
+}
+
+#[cfg(not(unix))]
+fn owner_field(_f: Field, _meta: Option<&fs::Metadata>) -> Value {
+    Value::Null
+}
@@ -0,0 +1,69 @@
+//! macOS firmlinks and `/etc/synthetic.conf`: directories reachable under two paths.
+//!
+//! Since Catalina the read-only system volume and the data volume are stitched
+//! together: `/Users` is a firmlink to `/System/Volumes/Data/Users`, and so on for
+//! each line of `/usr/share/firmlinks`. Both paths name the same files, but
+//! `realpath` keeps whichever one it was given, so paths are folded to the short
+//! form before they are compared. synthetic.conf adds root-level links
+//! (`name<TAB>target`), expanded the same way when link text is compared lexically.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const DATA: &str = "/System/Volumes/Data";
+
+#[derive(Default)]
+struct Table {
+    /// (firmlink on the system volume, the data-volume directory it shows)
+    firmlinks: Vec<(PathBuf, PathBuf)>,
+    /// (`/name`, where it points)
+    synthetic: Vec<(PathBuf, PathBuf)>,
+}
+
+fn table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        if !Path::new(DATA).is_dir() { return Table::default(); }
+        let lines = |p: &str| fs::read_to_string(p).unwrap_or_default();
+        let firmlinks = lines("/usr/share/firmlinks").lines()
+            .filter_map(|l| {
+                let (sys, data) = l.split_once('\t')?;
+                Some((PathBuf::from(sys.trim()), Path::new(DATA).join(data.trim())))
+            })
+            .collect();
+        let synthetic = lines("/etc/synthetic.conf").lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            // A name without a target is an empty mount point, not a link
+            .filter_map(|l| {
+                let (name, target) = l.split_once('\t')?;
+                Some((Path::new("/").join(name.trim()), Path::new(DATA).join(target.trim())))
+            })
+            .collect();
+        Table { firmlinks, synthetic }
+    })
+}
+
+/// `p` with a data-volume prefix (`/System/Volumes/Data/Users/...`) replaced by its firmlink (`/Users/...`).
+pub fn fold(p: PathBuf) -> PathBuf {
+    for (sys, data) in &table().firmlinks {
+        if let Ok(rest) = p.strip_prefix(data) { return if rest.as_os_str().is_empty() { sys.clone() } else { sys.join(rest) }; }
+    }
+    p
+}
+
+/// Lexical form of absolute `p` for comparing link text: synthetic.conf links expanded, then folded.
+pub fn fold_lexical(p: PathBuf) -> PathBuf {
+    for (name, target) in &table().synthetic {
+        if let Ok(rest) = p.strip_prefix(name) { return fold(target.join(rest)); }
+    }
+    fold(p)
+}
+
+/// Data-volume directories a walk of `roots` (canonical) would visit twice: those whose firmlink is under a root.
+pub fn aliases(roots: &[PathBuf]) -> Vec<PathBuf> {
+    table().firmlinks.iter()
+        .filter(|(sys, _)| roots.iter().any(|r| sys.starts_with(r)))
+        .map(|(_, data)| data.clone())
+        .collect()
+}
@@ -0,0 +1,120 @@
+//! `health`: score every symlink under the scan root by how likely it is to cause trouble.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use console::style;
+use num_format::{Locale, ToFormattedString};
+use rayon::prelude::*;
+
+use crate::cli::{HealthOpts, ScanOpts};
+use crate::config::Config;
+use crate::progress::{Progress, ProgressMode};
+use crate::scan::{self, LinkRecord};
+use crate::term::Glyphs;
+
+/// Score penalty per link in each category; a link's total penalty is capped at 1.
+const WEIGHTS: Weights = Weights { broken: 1.0, escaping: 0.5, cross_device: 0.25, deep: 0.25 };
+
+struct Weights {
+    broken: f64,
+    escaping: f64,
+    cross_device: f64,
+    deep: f64,
+}
+
+#[derive(Default)]
+struct Flags {
+    broken: bool,
+    escaping: bool,
+    cross_device: bool,
+    deep: bool,
+}
+
+impl Flags {
+    fn penalty(&self) -> f64 {
+        let w = &WEIGHTS;
+        let sum = [(self.broken, w.broken), (self.escaping, w.escaping), (self.cross_device, w.cross_device), (self.deep, w.deep)]
+            .iter().filter(|(on, _)| *on).map(|(_, w)| w).sum::<f64>();
+        sum.min(1.0)
+    }
+}
+
+/// Symlink hops from `p` to a non-link (a loop counts as the kernel's limit).
+fn chain_length(p: &Path) -> usize {
+    let mut cur = p.to_path_buf();
+    for hops in 0..40 {
+        let Ok(text) = fs::read_link(&cur) else { return hops };
+        cur = cur.parent().unwrap_or(Path::new("")).join(text);
+    }
+    40
+}
+
+#[cfg(unix)]
+fn cross_device(link: &Path, dest: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let parent = link.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    match (fs::metadata(parent), fs::metadata(dest)) {
+        (Ok(a), Ok(b)) => a.dev() != b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn cross_device(_link: &Path, _dest: &Path) -> bool {
+    false
+}
+
+fn classify(l: &LinkRecord, roots: &[PathBuf], deep: usize) -> Flags {
+    // Where the link points: its resolution, or lexically for dangling links
+    let dest: Option<PathBuf> = l.resolved.clone().or_else(|| {
+        let text = l.link_text.as_ref()?;
+        Some(scan::lexical_absolute(&l.path.parent().unwrap_or(Path::new("")).join(text)))
+    });
+    Flags {
+        broken: l.resolved.is_none(),
+        escaping: dest.as_ref().is_some_and(|d| !roots.iter().any(|r| d.starts_with(r))),
+        cross_device: l.resolved.as_ref().is_some_and(|d| cross_device(&l.path, d)),
+        deep: chain_length(&l.path) > deep,
+    }
+}
+
+pub fn run(opts: HealthOpts, config: &Config, glyphs: &'static Glyphs) -> Result<()> {
+    let progress = Progress::new(ProgressMode::Auto, Duration::from_secs(5), &config.progress, glyphs)?;
+    let roots = opts.walk.roots.iter().map(|r| scan::realpath(r)).collect::<Result<Vec<_>>>()?;
+    let scan_opts = ScanOpts::walk_only(opts.walk);
+    let result = scan::scan(&scan_opts, &[], &progress, &|_| {})?;
+    // A score over part of the tree would look better or worse than it is
+    if result.aborted { return Err(crate::aborted_error(&result)); }
+    let flags: Vec<Flags> = result.links.par_iter().map(|l| classify(l, &roots, opts.deep)).collect();
+
+    let count = |f: fn(&Flags) -> bool| flags.iter().filter(|x| f(x)).count();
+    let total = flags.len();
+    let penalty: f64 = flags.iter().map(Flags::penalty).sum();
+    let score = if total == 0 { 100.0 } else { 100.0 * (1.0 - penalty / total as f64) };
+    let rows = [
+        ("broken", count(|f| f.broken)),
+        ("escaping", count(|f| f.escaping)),
+        ("cross_device", count(|f| f.cross_device)),
+        ("deep_chains", count(|f| f.deep)),
+    ];
+
+    if opts.json {
+        let mut doc = serde_json::json!({ "score": (score * 10.0).round() / 10.0, "symlinks": total });
+        for (k, n) in rows { doc[k] = n.into(); }
+        println!("{}", serde_json::to_string_pretty(&doc)?);
+        return Ok(());
+    }
+    let labels = ["Broken:", "Escaping the tree:", "Cross-device:", &format!("Chains over {} hops:", opts.deep)];
+    println!("{} {}", style("Symlinks checked:").dim(), style(total.to_formatted_string(&Locale::en)).bold().cyan());
+    for (label, (_, n)) in labels.iter().zip(rows) {
+        let n_s = n.to_formatted_string(&Locale::en);
+        println!("{} {}", style(label).dim(), if n > 0 { style(n_s).bold().red() } else { style(n_s).dim() });
+    }
+    let score_s = format!("{:.1}/100", score);
+    let score_s = if score >= 90.0 { style(score_s).bold().green() } else if score >= 70.0 { style(score_s).bold().yellow() } else { style(score_s).bold().red() };
+    println!("{} {}", style("Health score:").dim(), score_s);
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! What a scan reports while it runs: phase changes, walked entries, resolved links, and matches.
+//!
+//! `scan::scan` (and the cap and manifest scans) call a `Hooks` from their worker threads, so
+//! whatever renders progress only has to implement it. `Progress` is the CLI's (indicatif bars or
+//! plain status lines); the results browser counts entries for its status line; `Quiet` ignores
+//! everything.
+
+use std::path::Path;
+
+use crate::scan::Match;
+
+/// The stages of a scan, in the order they are entered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// A pass counting the entries ahead of the walk (`--progress exact`)
+    Counting,
+    /// Walking the roots (or reading a manifest) for symlinks
+    Walking,
+    /// Resolving the `total` symlinks the walk left to check
+    Resolving { total: usize },
+    /// Done, cancelled, or aborted; nothing more is reported
+    Finished,
+}
+
+/// Callbacks for a running scan. Every method has an empty default; all may be called from many
+/// threads at once, so they should be cheap.
+pub trait Hooks: Sync {
+    fn phase(&self, _phase: Phase) {}
+
+    /// The walk is expected to see `entries` entries: counted when `exact`, else predicted.
+    fn estimate(&self, _entries: u64, _exact: bool) {}
+
+    fn walked(&self, _path: &Path, _is_dir: bool, _is_symlink: bool) {}
+
+    /// One symlink checked, matched or not (links resolved during the walk included).
+    fn resolved(&self) {}
+
+    /// A confirmed match, just before the scan's `on_match` sees it.
+    fn matched(&self, _m: &Match) {}
+
+    /// Run `f`, which writes to the terminal, without it tearing whatever progress is drawn there.
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        f()
+    }
+}
+
+/// Hooks that report nothing.
+pub struct Quiet;
+
+impl Hooks for Quiet {}
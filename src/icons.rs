@@ -0,0 +1,64 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+
+use crate::term;
+
+/// Symbol set used to prefix result and summary lines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IconMode {
+    /// Emoji on a UTF-8 terminal, none when piped or on other locales
+    Auto,
+    /// Nerd Font glyphs (requires a patched font)
+    Nerd,
+    /// Standard emoji
+    Emoji,
+    /// No icons
+    None,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Icon {
+    Link,
+    Broken,
+    Copy,
+    Hardlink,
+    Warning,
+}
+
+/// Resolved icon set; `prefix` returns an empty string when icons are off.
+#[derive(Copy, Clone, Debug)]
+pub struct Icons {
+    mode: IconMode,
+}
+
+impl Icons {
+    /// Resolve `Auto` and downgrade to `None` when output must stay ASCII.
+    pub fn new(mode: IconMode, ascii: bool) -> Icons {
+        let mode = match mode {
+            IconMode::None => IconMode::None,
+            _ if ascii || !term::supports_utf8() => IconMode::None,
+            IconMode::Auto if std::io::stdout().is_terminal() => IconMode::Emoji,
+            IconMode::Auto => IconMode::None,
+            m => m,
+        };
+        Icons { mode }
+    }
+
+    /// The icon followed by a space, ready to prepend to a line.
+    pub fn prefix(&self, icon: Icon) -> &'static str {
+        match (self.mode, icon) {
+            (IconMode::Nerd, Icon::Link) => "\u{f0c1} ",
+            (IconMode::Nerd, Icon::Broken) => "\u{f127} ",
+            (IconMode::Nerd, Icon::Copy) => "\u{f0c5} ",
+            (IconMode::Nerd, Icon::Hardlink) => "\u{f0c6} ",
+            (IconMode::Nerd, Icon::Warning) => "\u{f071} ",
+            (IconMode::Emoji, Icon::Link) => "🔗 ",
+            (IconMode::Emoji, Icon::Broken) => "💔 ",
+            (IconMode::Emoji, Icon::Copy) => "📄 ",
+            (IconMode::Emoji, Icon::Hardlink) => "📎 ",
+            (IconMode::Emoji, Icon::Warning) => "❗ ",
+            _ => "",
+        }
+    }
+}
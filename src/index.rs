@@ -0,0 +1,340 @@
+//! Persistent symlink index: a SQLite database (the `--export-sqlite` schema) with absolute paths.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use ::notify::event::{EventKind, ModifyKind};
+use ::notify::{RecursiveMode, Watcher};
+use anyhow::{Context, Result};
+use console::style;
+use num_format::{Locale, ToFormattedString};
+use rusqlite::{params, types::ValueRef, Connection, OpenFlags, OptionalExtension, Transaction};
+
+use crate::cli::{ExportFormat, IndexAction, IndexOpts, ScanOpts, WalkOpts, WatchOpts};
+use crate::progress::{Progress, ProgressMode};
+use crate::{scan, socket, sqlite, term};
+
+/// `<data dir>/find-symlinks/index.sqlite`
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("find-symlinks").join("index.sqlite"))
+}
+
+pub fn run(opts: IndexOpts, config: &crate::config::Config, glyphs: &'static term::Glyphs) -> Result<()> {
+    let db = match opts.db.or_else(default_path) {
+        Some(p) => p,
+        None => anyhow::bail!("no data directory on this platform; pass --db"),
+    };
+    match opts.action {
+        IndexAction::Build(walk) => build(&db, walk, config, glyphs),
+        IndexAction::Watch(opts) => watch(&db, opts, config, glyphs),
+        IndexAction::Sql { query, json } => sql(&db, &query, json),
+        IndexAction::Export { format } => export(&db, format),
+    }
+}
+
+fn build(db: &Path, walk: WalkOpts, config: &crate::config::Config, glyphs: &'static term::Glyphs) -> Result<()> {
+    let start = Instant::now();
+    let progress = Progress::new(ProgressMode::Auto, Duration::from_secs(5), &config.progress, glyphs)?;
+    let scan_opts = ScanOpts::walk_only(walk);
+    let result = scan::scan(&scan_opts, &[], &progress, &|_| {})?;
+    if result.aborted { return Err(crate::aborted_error(&result)); }
+    let secs = start.elapsed().as_secs_f64();
+    sqlite::export(db, &scan_opts.walk.roots, &[], &result, secs, true)?;
+    println!(
+        "{} {} {} {} {:.2}s",
+        style("Indexed").dim(),
+        style(result.links.len().to_formatted_string(&Locale::en)).bold().cyan(),
+        style("symlinks into").dim(),
+        style(db.display()).bold(),
+        secs,
+    );
+    Ok(())
+}
+
+/// Quiet period that ends a batch of filesystem events
+const SETTLE: Duration = Duration::from_millis(500);
+/// A batch is applied after this long even if events keep coming
+const MAX_BATCH: Duration = Duration::from_secs(5);
+
+/// Paths touched by one batch of events.
+#[derive(Default)]
+pub(crate) struct Batch {
+    pub changed: BTreeSet<PathBuf>,
+    /// Created or renamed into place: a directory among them is walked for the links it brought
+    pub created: BTreeSet<PathBuf>,
+    /// The watcher dropped events; only a rebuild is reliable
+    pub rescan: bool,
+}
+
+impl Batch {
+    /// `first` and the events after it, until they settle for `SETTLE` or `MAX_BATCH` has passed.
+    pub(crate) fn gather(first: ::notify::Result<::notify::Event>, rx: &mpsc::Receiver<::notify::Result<::notify::Event>>) -> Batch {
+        let start = Instant::now();
+        let mut batch = Batch::default();
+        let mut next = Some(first);
+        while let Some(ev) = next {
+            match ev {
+                Ok(ev) => batch.add(ev),
+                Err(e) => eprintln!("warning: watch: {}", e),
+            }
+            next = if start.elapsed() < MAX_BATCH { rx.recv_timeout(SETTLE).ok() } else { None };
+        }
+        batch
+    }
+
+    fn add(&mut self, ev: ::notify::Event) {
+        if ev.need_rescan() { self.rescan = true; }
+        match ev.kind {
+            EventKind::Access(_) => {}
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)) => {
+                self.created.extend(ev.paths.iter().cloned());
+                self.changed.extend(ev.paths);
+            }
+            _ => self.changed.extend(ev.paths),
+        }
+    }
+}
+
+/// Whether a walk with `walk`'s hidden, heavy-directory, and depth rules would reach `p`.
+pub(crate) fn reachable(p: &Path, roots: &[PathBuf], walk: &WalkOpts) -> bool {
+    let Some(rel) = roots.iter().find_map(|r| p.strip_prefix(r).ok()) else { return false };
+    if walk.max_depth.is_some_and(|m| rel.components().count() > m) { return false; }
+    rel.components().all(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        (walk.hidden || !name.starts_with('.')) && (walk.include_heavy || !scan::HEAVY_DIRS.contains(&name.as_ref()))
+    })
+}
+
+fn text(p: &Path) -> String {
+    scan::lexical_absolute(p).to_string_lossy().into_owned()
+}
+
+/// Index paths a batch wrote and removed (a path removed and written again counts as written).
+#[derive(Default)]
+struct Changes {
+    updated: BTreeSet<String>,
+    removed: BTreeSet<String>,
+}
+
+fn upsert(tx: &Transaction, p: &Path, changes: &mut Changes) -> Result<()> {
+    let l = scan::link_record(p);
+    let key = text(&l.path);
+    tx.execute(
+        "INSERT OR REPLACE INTO symlinks (path, link_text, resolved, error) VALUES (?1, ?2, ?3, ?4)",
+        params![key, l.link_text.as_deref().map(|t| t.to_string_lossy().into_owned()), l.resolved.as_deref().map(text), l.error],
+    )?;
+    changes.updated.insert(key);
+    Ok(())
+}
+
+/// Rows for `key`, and with `tree` everything below it.
+fn delete(tx: &Transaction, key: &str, tree: bool, changes: &mut Changes) -> Result<()> {
+    let mut st = tx.prepare("DELETE FROM symlinks WHERE path = ?1 OR (?3 AND substr(path, 1, length(?2)) = ?2) RETURNING path")?;
+    let gone = st.query_map(params![key, format!("{}/", key), tree], |r| r.get::<_, String>(0))?;
+    for p in gone { changes.removed.insert(p?); }
+    Ok(())
+}
+
+/// Apply a batch to the `symlinks` table.
+fn apply(conn: &mut Connection, batch: &Batch, roots: &[PathBuf], walk: &WalkOpts) -> Result<Changes> {
+    let tx = conn.transaction()?;
+    let mut changes = Changes::default();
+    // Links may resolve through a changed path, or through a changed link's old destination
+    let mut stale = BTreeSet::new();
+    for p in batch.changed.iter().filter(|p| reachable(p, roots, walk)) {
+        let key = text(p);
+        let old: Option<String> = tx.query_row("SELECT resolved FROM symlinks WHERE path = ?1", [&key], |r| r.get(0)).optional()?.flatten();
+        stale.extend(old);
+        match fs::symlink_metadata(p) {
+            Ok(m) if m.file_type().is_symlink() => upsert(&tx, p, &mut changes)?,
+            Ok(m) if m.is_dir() && batch.created.contains(p) => {
+                delete(&tx, &key, true, &mut changes)?;
+                for l in scan::walk_symlinks(&WalkOpts { roots: vec![p.clone()], ..walk.clone() }) { upsert(&tx, &l, &mut changes)?; }
+            }
+            Ok(m) if m.is_dir() => {}
+            Ok(_) => delete(&tx, &key, false, &mut changes)?,
+            Err(_) => delete(&tx, &key, true, &mut changes)?,
+        }
+        stale.insert(key);
+    }
+    let mut recheck = BTreeSet::new();
+    {
+        let mut st = tx.prepare("SELECT path FROM symlinks WHERE resolved = ?1 OR substr(resolved, 1, length(?2)) = ?2")?;
+        for s in &stale {
+            recheck.extend(st.query_map(params![s, format!("{}/", s)], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?);
+        }
+        // Something new may be what a broken link was waiting for
+        if !batch.created.is_empty() {
+            let mut st = tx.prepare("SELECT path FROM symlinks WHERE error IS NOT NULL")?;
+            recheck.extend(st.query_map([], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?);
+        }
+    }
+    for p in recheck.iter().map(Path::new) {
+        if fs::symlink_metadata(p).is_ok_and(|m| m.file_type().is_symlink()) { upsert(&tx, p, &mut changes)?; } else { delete(&tx, &text(p), true, &mut changes)?; }
+    }
+    tx.execute("UPDATE stats SET value = (SELECT count(*) FROM symlinks) WHERE key = 'symlinks'", [])?;
+    tx.commit()?;
+    changes.removed.retain(|p| !changes.updated.contains(p));
+    Ok(changes)
+}
+
+/// `index watch`: build, then apply filesystem events in batches until killed, answering `--socket` queries meanwhile.
+fn watch(db: &Path, opts: WatchOpts, config: &crate::config::Config, glyphs: &'static term::Glyphs) -> Result<()> {
+    let walk = opts.walk;
+    let roots: Vec<PathBuf> = walk.roots.iter().map(|r| scan::lexical_absolute(r)).collect();
+    let server = match opts.socket {
+        Some(path) => match path.or_else(socket::default_path) {
+            Some(p) => Some((socket::Server::start(&p, socket::Source::Db(db.to_path_buf()), &roots, &config.socket)?, p)),
+            None => anyhow::bail!("no data directory on this platform; pass --socket=PATH"),
+        },
+        None => None,
+    };
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = ::notify::recommended_watcher(tx).context("start filesystem watcher")?;
+    // Watching starts before the build, so nothing changed during it is missed
+    for r in &roots {
+        watcher.watch(r, RecursiveMode::Recursive).with_context(|| format!("watch {}", r.display()))?;
+    }
+    build(db, walk.clone(), config, glyphs)?;
+    let open_rw = || -> Result<Connection> {
+        let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_WRITE).with_context(|| format!("open index {}", db.display()))?;
+        // `index sql` readers hold the database only briefly
+        conn.busy_timeout(Duration::from_secs(30))?;
+        Ok(conn)
+    };
+    let mut conn = open_rw()?;
+    let server = server.map(|(server, p)| {
+        println!("{} {}", style("Answering queries on").dim(), style(p.display()).bold());
+        server
+    });
+    println!("{}", style("Watching for changes (Ctrl-C to stop)").dim());
+    while let Ok(first) = rx.recv() {
+        let batch = Batch::gather(first, &rx);
+        if batch.rescan {
+            eprintln!("warning: watch: events were dropped; rebuilding the index");
+            drop(conn);
+            build(db, walk.clone(), config, glyphs)?;
+            conn = open_rw()?;
+            if let Some(s) = &server { s.publish(&serde_json::json!({ "event": "rebuilt" })); }
+            continue;
+        }
+        if batch.changed.is_empty() { continue; }
+        let changes = apply(&mut conn, &batch, &roots, &walk)?;
+        let (written, removed) = (changes.updated.len(), changes.removed.len());
+        if written + removed > 0 {
+            if let Some(s) = &server {
+                s.publish(&serde_json::json!({ "event": "changed", "updated": changes.updated, "removed": changes.removed }));
+            }
+            println!(
+                "{} {} {} {}",
+                style("Updated").dim(),
+                style(written.to_formatted_string(&Locale::en)).bold().cyan(),
+                style("symlinks, removed").dim(),
+                style(removed.to_formatted_string(&Locale::en)).bold().cyan(),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn open(db: &Path) -> Result<Connection> {
+    Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("open index {} (run `find-symlinks index build` first)", db.display()))
+}
+
+/// A CSV field, quoted when it holds a comma, quote, or line break.
+fn csv_field(s: &str) -> std::borrow::Cow<'_, str> {
+    if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")).into() } else { s.into() }
+}
+
+/// Stream the `symlinks` table as CSV or JSON lines.
+fn export(db: &Path, format: ExportFormat) -> Result<()> {
+    const COLUMNS: [&str; 4] = ["path", "link_text", "resolved", "error"];
+    let conn = open(db)?;
+    let mut st = conn.prepare("SELECT path, link_text, resolved, error FROM symlinks ORDER BY path")?;
+    let mut rows = st.query([])?;
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    if format == ExportFormat::Csv { writeln!(out, "{}", COLUMNS.join(","))?; }
+    while let Some(row) = rows.next()? {
+        let mut cells: [Option<String>; 4] = Default::default();
+        for (i, cell) in cells.iter_mut().enumerate() { *cell = row.get(i)?; }
+        match format {
+            ExportFormat::Csv => {
+                let line: Vec<_> = cells.iter().map(|c| csv_field(c.as_deref().unwrap_or(""))).collect();
+                writeln!(out, "{}", line.join(","))?;
+            }
+            ExportFormat::Jsonl => {
+                let obj: serde_json::Map<_, _> = COLUMNS.iter().map(|c| c.to_string()).zip(cells.into_iter().map(serde_json::Value::from)).collect();
+                writeln!(out, "{}", serde_json::Value::Object(obj))?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Run a read-only query and print the rows (tab-separated with a header, or JSON objects).
+fn sql(db: &Path, query: &str, json: bool) -> Result<()> {
+    let conn = open(db)?;
+    let mut st = conn.prepare(query).context("invalid query")?;
+    let names: Vec<String> = st.column_names().into_iter().map(String::from).collect();
+    let mut rows = st.query([])?;
+    let mut out: Vec<serde_json::Value> = Vec::new();
+    if !json { println!("{}", style(names.join("\t")).dim()); }
+    while let Some(row) = rows.next()? {
+        let mut cells = Vec::with_capacity(names.len());
+        for i in 0..names.len() {
+            cells.push(match row.get_ref(i)? {
+                ValueRef::Null => serde_json::Value::Null,
+                ValueRef::Integer(n) => n.into(),
+                ValueRef::Real(f) => f.into(),
+                ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned().into(),
+                ValueRef::Blob(b) => format!("<{} bytes>", b.len()).into(),
+            });
+        }
+        if json {
+            out.push(serde_json::Value::Object(names.iter().cloned().zip(cells).collect()));
+        } else {
+            let line: Vec<String> = cells.iter().map(|c| match c {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Null => String::new(),
+                v => v.to_string(),
+            }).collect();
+            println!("{}", line.join("\t"));
+        }
+    }
+    if json { println!("{}", serde_json::to_string_pretty(&out)?); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_link_text_round_trips() {
+        let dir = std::env::temp_dir().join(format!("find-symlinks-index-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("w")).unwrap();
+        fs::create_dir_all(dir.join("t")).unwrap();
+        let link = dir.join("w").join("l");
+        std::os::unix::fs::symlink("../t", &link).unwrap();
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(sqlite::SCHEMA).unwrap();
+        let tx = conn.transaction().unwrap();
+        upsert(&tx, &link, &mut Changes::default()).unwrap();
+        let (text, resolved): (String, String) = tx
+            .query_row("SELECT link_text, resolved FROM symlinks WHERE path = ?1", [link.to_string_lossy()], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap();
+        let real_t = scan::realpath(&dir.join("t")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(text, "../t");
+        assert_eq!(resolved, real_t.to_string_lossy());
+    }
+}
@@ -0,0 +1,1120 @@
+//! find-symlinks: find the symlinks that point at given targets.
+//!
+//! The binary is a thin wrapper around [`run`]. For embedding, [`Scanner`] runs the scan behind
+//! `find` on its own: configured with `find`'s own arguments, reporting progress through
+//! [`Hooks`], stopped through a [`Cancel`], and returning the matches as a [`ScanResult`]. Everything else (output, changes, the index) stays
+//! behind the command line.
+
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use console::{measure_text_width, style};
+use num_format::{Locale, ToFormattedString};
+
+mod actions;
+mod boundary;
+mod cache;
+mod cancel;
+mod cli;
+mod config;
+#[cfg(feature = "cap-std")]
+mod confined;
+mod daemon;
+mod estimate;
+mod exec;
+mod filter;
+mod firmlink;
+mod health;
+mod hooks;
+mod icons;
+mod index;
+mod lock;
+mod manifest;
+mod merge;
+mod mounts_config;
+mod notify;
+mod output;
+mod perm;
+#[cfg(feature = "parquet")]
+mod parquet_out;
+mod preset;
+mod progress;
+mod reclaim;
+mod results;
+mod scan;
+mod scanner;
+mod snapshot;
+mod socket;
+mod sqlite;
+mod template;
+mod term;
+mod timemachine;
+mod timeout;
+mod trash;
+mod tune;
+#[cfg(feature = "tui")]
+mod tui;
+mod undo;
+#[cfg(unix)]
+mod users;
+mod watch;
+mod wsl;
+mod xattr;
+
+pub use cancel::{Cancel, Reason};
+pub use hooks::{Hooks, Phase, Quiet};
+pub use scan::{Match, ScanResult, Target};
+pub use scanner::Scanner;
+
+use cli::{BenchOpts, Check, ColorChoice, Command, FindOpts, GlobalOpts, OutputFormat, ScanOpts};
+use config::Config;
+use icons::{Icon, Icons};
+use output::JsonPath;
+use filter::Candidate;
+use progress::{Progress, ProgressMode};
+use snapshot::{Diff, Snapshot};
+
+/// The `find-symlinks` command line: parse the arguments and run the subcommand.
+pub fn run() -> Result<()> {
+    let cli = cli::parse();
+    let config = Config::load(cli.global.config.as_deref())?;
+    config.tuning.init_pool();
+
+    // Configure ANSI color usage
+    let enable_colors = match cli.global.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => console::colors_enabled(),
+    };
+    console::set_colors_enabled(enable_colors);
+
+    // Single-instance guard, held for the rest of the run
+    let _lock = match &cli.global.lock {
+        Some(path) => match lock::acquire(path, cli.global.lock_wait)? {
+            Some(f) => Some(f),
+            None => {
+                eprintln!("find-symlinks: another instance holds {}; exiting", path.display());
+                std::process::exit(lock::EXIT_LOCKED);
+            }
+        },
+        None => None,
+    };
+
+    match cli.command {
+        Command::Find(mut opts) | Command::List(mut opts) | Command::Check(mut opts) | Command::Fix(mut opts) | Command::Watch(mut opts) => {
+            config.tuning.apply(&mut opts.scan);
+            let quiet = opts.quiet;
+            match run_find(*opts, &cli.global, &config) {
+                // `grep -q`: exit status 1 means "no match", so errors get 2
+                Err(e) if quiet => {
+                    eprintln!("Error: {:?}", e);
+                    std::process::exit(2);
+                }
+                r => r,
+            }
+        }
+        Command::Bench(mut opts) => { config.tuning.apply(&mut opts.scan); run_bench(&opts) }
+        Command::Index(mut opts) => {
+            if let cli::IndexAction::Build(w) | cli::IndexAction::Watch(cli::WatchOpts { walk: w, .. }) = &mut opts.action { config.tuning.apply_walk(w); }
+            index::run(opts, &config, term::glyphs(cli.global.ascii))
+        }
+        Command::Merge(opts) => merge::run(opts),
+        Command::Health(mut opts) => { config.tuning.apply_walk(&mut opts.walk); health::run(opts, &config, term::glyphs(cli.global.ascii)) }
+        Command::Tune(opts) => tune::run(opts, cli.global.config.as_deref()),
+        Command::Client(opts) => socket::client(opts),
+        Command::Daemon(mut opts) => { config.tuning.apply_walk(&mut opts.walk); daemon::run(opts, &config, term::glyphs(cli.global.ascii)) }
+        Command::Undo(opts) => undo::run(opts),
+    }
+}
+
+fn run_find(mut opts: FindOpts, global: &GlobalOpts, config: &Config) -> Result<()> {
+    if opts.json { opts.format = OutputFormat::Json; }
+    if opts.json_lines { opts.format = OutputFormat::JsonLines; }
+    if opts.quiet {
+        (opts.no_tui, opts.heartbeat) = (true, None);
+        // Resolve during the walk, so the first match ends it
+        if opts.from_manifest.is_none() && opts.scan.walk.backend == cli::Backend::Std { opts.scan.max_count = Some(1); }
+    }
+    // Exports, and mtree's `link=` (which manifests can only supply this way), need every link's text
+    opts.scan.record_links = opts.export_sqlite.is_some() || opts.format == OutputFormat::Mtree || opts.save_snapshot.is_some()
+        || opts.reclaim_orphans;
+    opts.scan.record_dirs = opts.save_snapshot.is_some() || opts.cache;
+    if opts.format == OutputFormat::Parquet {
+        if cfg!(not(feature = "parquet")) {
+            anyhow::bail!("--format parquet needs a build with `--features parquet`");
+        }
+        if opts.output.is_none() && std::io::stdout().is_terminal() {
+            anyhow::bail!("--format parquet writes binary data; redirect stdout to a file");
+        }
+    }
+    let json = opts.format == OutputFormat::Json;
+    if opts.output.is_some() && opts.count_by_target && opts.format == OutputFormat::Text {
+        anyhow::bail!("--output with --count-by-target needs --json");
+    }
+    if opts.quote.is_some() && (opts.format != OutputFormat::Text || opts.raw0) {
+        anyhow::bail!("--quote applies to text output only");
+    }
+    if (opts.with_xattrs || opts.with_quarantine) && matches!(opts.format, OutputFormat::Parquet | OutputFormat::Mtree | OutputFormat::JsonLines) {
+        anyhow::bail!("--with-xattrs and --with-quarantine apply to text and JSON output only");
+    }
+    if !opts.checks.is_empty() && (opts.raw0 || matches!(opts.format, OutputFormat::Parquet | OutputFormat::Mtree | OutputFormat::JsonLines)) {
+        anyhow::bail!("--check applies to text and JSON output only");
+    }
+    if opts.count_by_target && matches!(opts.format, OutputFormat::Parquet | OutputFormat::Mtree | OutputFormat::JsonLines) {
+        anyhow::bail!("--count-by-target applies to text and JSON output only");
+    }
+    if opts.via && matches!(opts.format, OutputFormat::Parquet | OutputFormat::Mtree | OutputFormat::JsonLines) {
+        anyhow::bail!("--via applies to text and JSON output only");
+    }
+    if opts.stream_json && !json {
+        anyhow::bail!("--stream-json applies to --json output only");
+    }
+    if opts.template.is_some() && opts.format != OutputFormat::Text {
+        anyhow::bail!("--template replaces text output; it cannot be combined with --format");
+    }
+    if opts.format == OutputFormat::JsonLines {
+        // Each line is final once written; these need the whole scan first
+        let buffered = [
+            (opts.post_filter.is_some(), "--post-filter"),
+            (opts.scan.copies, "--copies"),
+            (opts.scan.hardlinks.is_some(), "--hardlinks"),
+            (opts.reclaim, "--reclaim"),
+        ];
+        if let Some((_, name)) = buffered.iter().find(|(on, _)| *on) {
+            anyhow::bail!("{} is not available with --format json-lines", name);
+        }
+    }
+    if let Some(action) = opts.action().filter(|_| !opts.yes && !opts.dry_run && !actions::can_prompt()) {
+        anyhow::bail!("--{} asks before each change; pass --yes to run without a terminal", action.verb());
+    }
+    if cfg!(not(any(unix, windows))) {
+        if opts.retarget.is_some() { anyhow::bail!("--retarget is only available on Unix and Windows"); }
+        if opts.rewrite_prefix.is_some() { anyhow::bail!("--rewrite-prefix is only available on Unix and Windows"); }
+        if opts.make_relative || opts.make_absolute { anyhow::bail!("--make-relative and --make-absolute are only available on Unix and Windows"); }
+        if opts.dereference_in_place { anyhow::bail!("--dereference-in-place is only available on Unix and Windows"); }
+    }
+    // Windows: without the privilege to create symlinks, say so before scanning rather than per link
+    actions::allow_junctions(opts.junction_fallback);
+    if opts.action().is_some_and(|a| a.creates_links()) && !opts.dry_run { actions::check_link_privilege()?; }
+    if opts.scan.mmap_results.is_some() && cfg!(not(unix)) {
+        anyhow::bail!("--mmap-results is only available on Unix");
+    }
+    if !opts.scan.perms.is_empty() && cfg!(not(unix)) {
+        anyhow::bail!("--perm is only available on Unix");
+    }
+    if opts.with_quarantine && cfg!(not(target_os = "macos")) {
+        anyhow::bail!("--with-quarantine is only available on macOS");
+    }
+    let overall_start = Instant::now();
+    let glyphs = term::glyphs(global.ascii);
+    let icons = Icons::new(global.icons, global.ascii);
+
+    if let Some(preset) = opts.preset {
+        // A link's target in a backup may no longer exist on the live system
+        let targets = if matches!(preset, preset::Preset::Timemachine) { timemachine::targets(&opts.scan)? } else { scan::resolve_targets(&opts.scan)? };
+        return preset::run(preset, &opts.scan, &targets, json, opts.path_encoding);
+    }
+    if let Some(since) = &opts.changed_since {
+        let targets = scan::resolve_targets(&opts.scan)?;
+        let (snap, diff) = Snapshot::load(since, &targets)?.update(&opts.scan, &targets)?;
+        if let Some(path) = &opts.save_snapshot { snap.save(path)?; }
+        let secs = overall_start.elapsed().as_secs_f64();
+        print_diff(&diff, &targets, json, opts.path_encoding, opts.quote, secs)?;
+        if let Some(mode) = opts.notify {
+            let changes = diff.added.len() + diff.removed.len() + diff.retargeted.len();
+            notify::send(mode, "find-symlinks: scan finished", &format!("{} changed matches in {:.1}s", changes, secs));
+        }
+        return Ok(());
+    }
+
+    if opts.interactive {
+        if !actions::can_prompt() { anyhow::bail!("--interactive needs a terminal on stdin and stderr"); }
+        #[cfg(feature = "tui")]
+        return tui::run(&opts, &scan::resolve_targets(&opts.scan)?, glyphs);
+        #[cfg(not(feature = "tui"))]
+        anyhow::bail!("--interactive needs a build with `--features tui`");
+    }
+
+    // No immediate header; will render results in a bordered box
+
+    // TUI: spinner while walking, determinate bar while resolving (or plain status lines)
+    let progress_mode = if opts.no_tui { ProgressMode::None } else { opts.progress };
+    // Heartbeat: plain status lines for headless runs (CI, nohup) that would otherwise be silent
+    let (progress_mode, interval) = match opts.heartbeat {
+        Some(secs) if progress_mode.resolve() == ProgressMode::None => (ProgressMode::Plain, secs),
+        _ => (progress_mode, opts.progress_interval),
+    };
+    let interval = Duration::from_secs(interval.max(1));
+    let progress = Progress::new(progress_mode, interval, &config.progress, glyphs)?;
+    if opts.estimate {
+        let est = estimate::estimate(&opts.scan.walk);
+        eprintln!(
+            "{} ~{} entries, ~{} symlinks, ~{:.1}s {}",
+            style("Estimate:").dim(),
+            est.entries.to_formatted_string(&Locale::en),
+            est.symlinks.to_formatted_string(&Locale::en),
+            est.secs,
+            style(format!("({})", est.source)).dim(),
+        );
+        progress.estimate(est.entries, false);
+    }
+    if progress_mode == ProgressMode::Exact {
+        progress.phase(Phase::Counting);
+        progress.estimate(scan::count_entries(&opts.scan), true);
+    }
+
+    // Stream matches as they are confirmed
+    let streaming_allowed = opts.format == OutputFormat::Text && !opts.raw0 && !opts.no_stream && !opts.count_by_target && opts.post_filter.is_none() && opts.template.is_none() && opts.exec.is_none() && opts.exec_batch.is_none() && opts.output.is_none();
+    let streamed_count = AtomicUsize::new(0);
+    let from_manifest = opts.from_manifest.is_some();
+    // Filesystem targets are resolved up front: the shape of streamed JSON depends on how many there are
+    let fs_targets = if from_manifest { Vec::new() } else { scan::resolve_targets(&opts.scan)? };
+    let sink = output::Sink::new(opts.output.as_deref())
+        .with_context(|| format!("create {}", opts.output.as_deref().unwrap_or(Path::new("-")).display()))?;
+    let json_object = |n_targets: usize| opts.json_schema >= 2 || n_targets > 1 || opts.scan.copies || opts.scan.hardlinks.is_some() || opts.reclaim || !opts.checks.is_empty();
+    let json_stream = (json && opts.stream_json)
+        .then(|| output::JsonStream::new(&sink, json_object(fs_targets.len()).then_some("matches")));
+    let json_lines = (opts.format == OutputFormat::JsonLines).then(|| output::JsonLines::new(&sink));
+    let roots: &[PathBuf] = if from_manifest { &[] } else { &opts.scan.walk.roots };
+    // Matches under a root reached through symlinks are also reported by their physical path
+    let aliases = scan::RootAliases::new(roots);
+    let line_of = |m: &Match| {
+        let line = if (opts.scan.broken || opts.scan.all) && m.broken { broken_line(m, icons, opts.quote, from_manifest) }
+            else if opts.scan.all { inventory_line(m, &opts.scan, icons, opts.quote) }
+            else { match_line(m, icons, opts.quote) };
+        let line = match aliases.physical(&m.path) {
+            Some(p) => format!("{} {}", line, style(format!("(physically {})", output::display_path(&p, opts.quote))).dim()),
+            None => line,
+        };
+        let via = if opts.via { scan::link_chain(&m.path) } else { Vec::new() };
+        if via.is_empty() { return line; }
+        let hops: Vec<String> = via.iter().map(|p| output::display_path(p, opts.quote).into_owned()).collect();
+        format!("{} {}", line, style(format!("(via {})", hops.join(" -> "))).dim())
+    };
+    let on_match = |m: &Match| {
+        if opts.quiet { std::process::exit(0); }
+        if streaming_allowed {
+            // On first streamed line, print a leading blank line to frame the results.
+            let prev = streamed_count.fetch_add(1, Ordering::Relaxed);
+            if prev == 0 { progress.println(""); }
+            progress.println(&line_of(m));
+            for l in attr_lines(&m.path, opts.with_xattrs, opts.with_quarantine) { progress.println(&l); }
+        }
+        if let Some(s) = &json_stream { progress.suspend(|| s.push(json_item(m, &fs_targets[m.target], &opts, roots, &aliases))); }
+        // Manifest targets are only known once the scan returns
+        if let Some(l) = json_lines.as_ref().filter(|_| !from_manifest) {
+            progress.suspend(|| l.push(json_line(m, &fs_targets[m.target], opts.path_encoding)));
+        }
+    };
+    // Started before the scan, so a link created during it is still reported
+    let watcher = if opts.watch { Some(watch::start(&opts.scan.walk)?) } else { None };
+    // Ctrl-C during the scan stops it and reports what it found so far
+    let interrupt = cancel::on_interrupt(&opts.scan.walk.cancel);
+    let (targets, mut result) = match &opts.from_manifest {
+        Some(path) => manifest::scan(path, &opts.scan, &progress, &on_match)?,
+        None => {
+            let cache = if opts.cache { Some(cache::Cache::new(&opts.scan, &fs_targets)?) } else { None };
+            let result = match cache.as_ref().and_then(|c| c.load(&opts.scan)) {
+                Some(result) => {
+                    // The walk and resolve times stay those of the run that filled the cache
+                    progress.suspend(|| eprintln!("{}", style(format!("Reused cached results ({} directories unchanged)", result.dir_mtimes.len())).dim()));
+                    // Replay the matches, so streamed output looks as it would after a walk
+                    for m in &result.matches {
+                        progress.matched(&m);
+                        on_match(&m);
+                    }
+                    result
+                }
+                None => {
+                    let result = scan::scan(&opts.scan, &fs_targets, &progress, &on_match)?;
+                    // A partial result would hide whatever the aborted walk missed
+                    if let Some(c) = cache.as_ref().filter(|_| !result.aborted && !result.limited) { c.save(&opts.scan, &result)?; }
+                    result
+                }
+            };
+            (fs_targets, result)
+        }
+    };
+    drop(interrupt);
+    if opts.quiet {
+        if result.aborted { return Err(aborted_error(&result)); }
+        std::process::exit(1);
+    }
+    if let Some(n) = opts.scan.max_dir_entries {
+        for d in &result.skipped_dirs { eprintln!("skipped: {}  more than {} entries", d.display(), n); }
+    }
+    let total = result.symlinks;
+    let multi = targets.len() > 1;
+
+    if let Some(db) = &opts.export_sqlite {
+        sqlite::export(db, &opts.scan.walk.roots, &targets, &result, overall_start.elapsed().as_secs_f64(), false)?;
+    }
+    if let Some(path) = &opts.save_snapshot {
+        if opts.from_manifest.is_some() { anyhow::bail!("--save-snapshot needs a filesystem scan, not --from-manifest"); }
+        // A partial snapshot would report everything it missed as added next time
+        if result.aborted { eprintln!("warning: scan aborted; not saving snapshot {}", path.display()); }
+        else if result.limited { eprintln!("warning: scan stopped at --max-count; not saving snapshot {}", path.display()); }
+        else { Snapshot::from_scan(&targets, &result).save(path)?; }
+    }
+    // The exports and snapshot above keep the whole scan; everything from here on sees the slice
+    if let Some(f) = &opts.post_filter {
+        result.matches.retain(|m| f.matches(&Candidate::new(&m.path, &scan::filter_target(&m.path, &targets[m.target]), m.broken)));
+    }
+    let matches = &result.matches;
+    // A `--delete --dry-run` report always says what the deletion would free
+    let delete_dry_run = opts.delete && opts.dry_run;
+    let reclaim = (opts.reclaim || delete_dry_run).then(|| reclaim::estimate(&targets, &result, opts.reclaim_orphans));
+    let mount_refs = opts.checks.contains(&Check::MountsConfig).then(|| mounts_config::check(&targets));
+    if let Some(path) = &opts.stats_json {
+        write_stats_json(path, &result, reclaim.as_ref().filter(|_| opts.reclaim), overall_start.elapsed().as_secs_f64())?;
+    }
+
+    if opts.count_by_target {
+        let counts = target_counts(&targets, &result, &opts.scan, from_manifest);
+        if json {
+            sink.write_all(format!("{}\n", serde_json::to_string_pretty(&counts)?).as_bytes())?;
+        } else {
+            print_target_counts(&counts, glyphs);
+            if result.aborted {
+                println!("{}{}", icons.prefix(Icon::Warning), style("Scan aborted: counts are partial.").red().bold());
+            }
+            if result.limited {
+                println!("{}{}", icons.prefix(Icon::Warning), style("Stopped at --max-count: counts are partial.").yellow());
+            }
+        }
+    } else if opts.format == OutputFormat::Parquet {
+        #[cfg(feature = "parquet")]
+        parquet_out::write(std::io::BufWriter::new(sink.writer()), roots, &targets, &result)?;
+    } else if let Some(lines) = json_lines {
+        if from_manifest {
+            for m in matches { lines.push(json_line(&m, &targets[m.target], opts.path_encoding)); }
+        }
+        lines.finish()?;
+    } else if opts.format == OutputFormat::Mtree {
+        let mut out = std::io::BufWriter::new(sink.writer());
+        writeln!(out, "#mtree")?;
+        for m in matches {
+            let text = result.links.binary_search_by(|l| l.path.cmp(&m.path)).ok()
+                .and_then(|i| result.links[i].link_text.clone());
+            let Some(text) = text else { continue };
+            writeln!(out, "{} type=link link={}", output::mtree_escape(&m.path), output::mtree_escape(&text))?;
+        }
+        out.flush()?;
+    } else if json {
+        let mut doc = serde_json::Map::new();
+        if json_object(targets.len()) {
+            // Schema 2, multi-target, copies: an object with the matches plus the extra sections
+            if opts.json_schema >= 2 { doc.insert("schema".into(), opts.json_schema.into()); }
+            if result.aborted { doc.insert("aborted".into(), true.into()); }
+            if let Some(why) = result.cancelled { doc.insert("cancelled".into(), serde_json::to_value(why)?); }
+            if result.limited { doc.insert("limited".into(), true.into()); }
+            if !result.skipped_dirs.is_empty() {
+                let dirs: Vec<JsonPath> = result.skipped_dirs.iter().map(|d| JsonPath { path: d, encoding: opts.path_encoding }).collect();
+                doc.insert("skipped_dirs".into(), serde_json::to_value(dirs)?);
+            }
+            if let Some(r) = &reclaim { doc.insert("reclaim".into(), serde_json::to_value(r)?); }
+            if let Some(r) = &mount_refs { doc.insert("mounts_config".into(), serde_json::to_value(r)?); }
+            if opts.scan.copies {
+                let copies: Vec<JsonPath> = result.copies.iter().map(|c| JsonPath { path: &c.path, encoding: opts.path_encoding }).collect();
+                doc.insert("copies".into(), serde_json::to_value(copies)?);
+            }
+            if opts.scan.hardlinks.is_some() {
+                let links: Vec<JsonPath> = result.hardlinks.iter().map(|h| JsonPath { path: &h.path, encoding: opts.path_encoding }).collect();
+                doc.insert("hardlinks".into(), serde_json::to_value(links)?);
+            }
+            if multi {
+                let summary: serde_json::Map<String, serde_json::Value> = targets.iter()
+                    .zip(result.per_target(targets.len()))
+                    .map(|(t, c)| {
+                        let mut v = serde_json::json!({ "matches": c.matches, "broken": c.broken });
+                        if opts.scan.copies { v["copies"] = c.copies.into(); }
+                        if opts.scan.hardlinks.is_some() { v["hardlinks"] = c.hardlinks.into(); }
+                        (t.given.clone(), v)
+                    })
+                    .collect();
+                doc.insert("targets".into(), summary.into());
+            }
+        }
+        match json_stream {
+            Some(s) => s.finish(doc)?,
+            None => {
+                let items = matches.iter()
+                    .map(|m| json_item(&m, &targets[m.target], &opts, roots, &aliases))
+                    .collect::<Result<Vec<serde_json::Value>, serde_json::Error>>()?;
+                let text = if json_object(targets.len()) {
+                    doc.insert("matches".into(), items.into());
+                    serde_json::to_string_pretty(&doc)?
+                } else {
+                    serde_json::to_string_pretty(&items)?
+                };
+                sink.write_all(format!("{}\n", text).as_bytes())?;
+            }
+        }
+    } else if let Some(template) = &opts.template {
+        let cx = template::Context { opts: &opts.scan, targets: &targets, roots, from_manifest };
+        let mut out = std::io::BufWriter::new(sink.writer());
+        for m in matches { writeln!(out, "{}", template.render(&m, &cx))?; }
+        out.flush()?;
+    } else if let Some(cmd) = &opts.exec {
+        let cx = template::Context { opts: &opts.scan, targets: &targets, roots, from_manifest };
+        let jobs = opts.exec_jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        let failed = exec::run(cmd, matches.iter(), &cx, jobs)?;
+        if failed > 0 { anyhow::bail!("{} of {} --exec commands failed", failed, matches.len()); }
+    } else if let Some(cmd) = &opts.exec_batch {
+        let failed = exec::run_batch(cmd, matches);
+        if failed > 0 { anyhow::bail!("{} --exec-batch runs failed", failed); }
+    } else if opts.raw0 {
+        let mut out = std::io::BufWriter::new(sink.writer());
+        for m in matches {
+            out.write_all(&output::os_bytes(&m.path))?;
+            out.write_all(b"\0")?;
+        }
+        out.flush()?;
+    } else {
+        let streamed_any = streamed_count.load(Ordering::Relaxed) > 0;
+        if let Some(path) = &opts.output {
+            // The box's rows, unstyled, then the copies and hard links
+            let mut out = std::io::BufWriter::new(sink.writer());
+            for m in matches {
+                writeln!(out, "{}", console::strip_ansi_codes(&line_of(&m)))?;
+                for l in attr_lines(&m.path, opts.with_xattrs, opts.with_quarantine) { writeln!(out, "{}", console::strip_ansi_codes(&l))?; }
+            }
+            for c in &result.copies { writeln!(out, "{} (copy)", output::display_path(&c.path, opts.quote))?; }
+            for h in &result.hardlinks { writeln!(out, "{} (hard link)", output::display_path(&h.path, opts.quote))?; }
+            out.flush()?;
+            println!("{} {}", style("Results written to").dim(), style(path.display()).bold());
+        } else if !streaming_allowed || !streamed_any {
+            let lines: Vec<String> = if matches.is_empty() {
+                vec![format!("{}{}", icons.prefix(Icon::Warning), style("No matches found.").yellow())]
+            } else {
+                matches.iter()
+                    .flat_map(|m| {
+                        let attrs = attr_lines(&m.path, opts.with_xattrs, opts.with_quarantine);
+                        std::iter::once(line_of(&m)).chain(attrs)
+                    })
+                    .collect()
+            };
+            print_box(&lines, glyphs);
+        }
+        // If we streamed any results, add a blank line after them before stats
+        if streaming_allowed && streamed_any {
+            println!();
+        }
+
+        if opts.scan.copies && opts.output.is_none() {
+            let lines: Vec<String> = if result.copies.is_empty() {
+                vec![format!("{}{}", icons.prefix(Icon::Warning), style("No content copies found.").yellow())]
+            } else {
+                result.copies.iter()
+                    .map(|c| format!("{}{} {}", icons.prefix(Icon::Copy), style(output::display_path(&c.path, opts.quote)).white().bold(), style("(copy)").dim()))
+                    .collect()
+            };
+            if !(streaming_allowed && streamed_any) { println!(); }
+            print_box(&lines, glyphs);
+            if streaming_allowed && streamed_any { println!(); }
+        }
+        if opts.scan.hardlinks.is_some() && opts.output.is_none() {
+            let lines: Vec<String> = if result.hardlinks.is_empty() {
+                vec![format!("{}{}", icons.prefix(Icon::Warning), style("No hard links found.").yellow())]
+            } else {
+                result.hardlinks.iter()
+                    .map(|h| format!("{}{} {}", icons.prefix(Icon::Hardlink), style(output::display_path(&h.path, opts.quote)).white().bold(), style("(hard link)").dim()))
+                    .collect()
+            };
+            if !(streaming_allowed && streamed_any) { println!(); }
+            print_box(&lines, glyphs);
+            if streaming_allowed && streamed_any { println!(); }
+        }
+
+        // Stats below results
+        let elapsed = overall_start.elapsed();
+        let secs = elapsed.as_secs_f64();
+        if !(streaming_allowed && streamed_any) { println!(); }
+
+        let folders_s = result.dirs.to_formatted_string(&Locale::en);
+        let files_s = result.files.to_formatted_string(&Locale::en);
+        let syms_s = total.to_formatted_string(&Locale::en);
+        let matches_s = (matches.len()).to_formatted_string(&Locale::en);
+        let walk_rate_s = (result.walk_rate().round() as usize).to_formatted_string(&Locale::en);
+        let resolve_rate_s = (result.resolve_rate().round() as usize).to_formatted_string(&Locale::en);
+
+        println!("{} {}", style("Folders traversed:").dim(), style(folders_s).bold().cyan());
+        println!("{} {}", style("Files traversed:").dim(), style(files_s).bold().cyan());
+        println!("{} {}", style("Symlinks scanned:").dim(), style(syms_s).bold().cyan());
+        println!("{}{} {}", icons.prefix(Icon::Link), style("Matches:").dim(), style(matches_s).bold().green());
+        if opts.scan.copies {
+            let copies_s = result.copies.len().to_formatted_string(&Locale::en);
+            println!("{}{} {}", icons.prefix(Icon::Copy), style("Content copies:").dim(), style(copies_s).bold().green());
+        }
+        if opts.scan.hardlinks.is_some() {
+            let links_s = result.hardlinks.len().to_formatted_string(&Locale::en);
+            println!("{}{} {}", icons.prefix(Icon::Hardlink), style("Hard links:").dim(), style(links_s).bold().green());
+        }
+        if opts.scan.retries > 0 {
+            println!("{} {}", style("Retries:").dim(), style(result.retries.to_formatted_string(&Locale::en)).bold().yellow());
+        }
+        println!("{} {:.2}s", style("Elapsed:").dim(), secs);
+        println!(
+            "{} {} {} {}",
+            style("Walk rate:").dim(),
+            style(walk_rate_s).bold().magenta(),
+            style("entries/s").dim(),
+            style(format!("({:.2}s)", result.walk_time.as_secs_f64())).dim(),
+        );
+        println!(
+            "{} {} {} {}",
+            style("Resolve rate:").dim(),
+            style(resolve_rate_s).bold().magenta(),
+            style("symlinks/s").dim(),
+            style(format!("({:.2}s)", result.resolve_time.as_secs_f64())).dim(),
+        );
+
+        if let Some(r) = reclaim.as_ref().filter(|_| !delete_dry_run) { print_reclaim(r); }
+        if let Some(r) = &mount_refs { print_mount_refs(r); }
+
+        if multi {
+            println!();
+            print_target_table(&targets, &result, glyphs);
+        }
+        if !result.roots.is_empty() {
+            println!();
+            print_root_table(&result.roots, glyphs);
+        }
+        if result.aborted {
+            let why = match result.cancelled {
+                Some(why) => format!("Scan {}: results above are partial.", why),
+                None => format!("Aborted after {} errors: results above are partial.", result.errors.len()),
+            };
+            println!();
+            println!("{}{}", icons.prefix(Icon::Warning), style(why).red().bold());
+        }
+        if result.limited {
+            println!();
+            println!(
+                "{}{}",
+                icons.prefix(Icon::Warning),
+                style(format!("Stopped at {} matches (--max-count): the counts cover only part of the tree.", matches.len())).yellow(),
+            );
+        }
+        if let (Some(n), false) = (opts.scan.max_dir_entries, result.skipped_dirs.is_empty()) {
+            println!();
+            println!(
+                "{}{}",
+                icons.prefix(Icon::Warning),
+                style(format!("Skipped {} directories with more than {} entries (--max-dir-entries, listed on stderr): links inside them are not counted.", result.skipped_dirs.len(), n)).yellow(),
+            );
+        }
+        if let Some(action) = opts.action() {
+            let describe = |m: &Match| match std::fs::read_link(&m.path) {
+                Ok(text) => format!(" -> {}", output::display_path(&text, opts.quote)),
+                Err(_) => String::new(),
+            };
+            let mut journal = opts.journal.as_deref().map(undo::Journal::open).transpose()?;
+            let report = actions::run(&action, matches, opts.yes, opts.dry_run, journal.as_mut(), describe)?;
+            print_action_report(&action, &report, opts.dry_run, icons, glyphs, opts.quote);
+            if let Some(r) = reclaim.as_ref().filter(|_| delete_dry_run) { print_reclaim(r); }
+            if !report.failed.is_empty() {
+                anyhow::bail!("could not {} {} of the matched symlinks", action.verb(), report.failed.len());
+            }
+        }
+    }
+
+    // A partial result never replaces the file; dropping the sink removes the temporary one
+    if !result.aborted { sink.commit().with_context(|| format!("write {}", opts.output.as_deref().unwrap_or(Path::new("-")).display()))?; }
+
+    if let Some(mode) = opts.notify {
+        let broken = matches.iter().filter(|m| m.broken).count();
+        let body = format!(
+            "{} matches ({} broken) among {} symlinks in {:.1}s",
+            matches.len().to_formatted_string(&Locale::en),
+            broken.to_formatted_string(&Locale::en),
+            total.to_formatted_string(&Locale::en),
+            overall_start.elapsed().as_secs_f64(),
+        );
+        notify::send(mode, "find-symlinks: scan finished", &body);
+    }
+    if opts.profile_ignores {
+        print_ignore_profile(&scan::profile_ignores(&opts.scan.walk));
+    }
+    if result.aborted { return Err(aborted_error(&result)); }
+    if let Some(w) = watcher { return watch::run(w, &opts, &targets, &result.matches); }
+
+    Ok(())
+}
+
+/// Exit error for a scan stopped by `--max-errors` (naming the last error seen) or cancelled.
+pub(crate) fn aborted_error(result: &scan::ScanResult) -> anyhow::Error {
+    if let Some(why) = result.cancelled { return anyhow::anyhow!("scan {}; results are partial", why); }
+    // Walker messages already lead with the path
+    let last = result.errors.last().map(|e| match &e.path {
+        Some(p) if !e.message.contains(&*p.to_string_lossy()) => format!("{}: {}", p.display(), e.message),
+        _ => e.message.clone(),
+    });
+    anyhow::anyhow!("scan aborted after {} errors (--max-errors); last: {}", result.errors.len(), last.unwrap_or_default())
+}
+
+/// `--reclaim` stats lines.
+fn print_reclaim(r: &reclaim::Reclaim) {
+    println!(
+        "{} {} {}",
+        style("Reclaimable (links):").dim(),
+        style(reclaim::human_bytes(r.link_bytes)).bold().green(),
+        style(format!("in {} links, {} in {} broken", r.links, reclaim::human_bytes(r.broken_bytes), r.broken)).dim(),
+    );
+    let Some(orphans) = &r.orphans else { return };
+    println!(
+        "{} {} {}",
+        style("Reclaimable (orphaned targets):").dim(),
+        style(reclaim::human_bytes(r.orphan_bytes())).bold().green(),
+        style(format!("in {} targets", orphans.len())).dim(),
+    );
+    for o in orphans {
+        println!("  {} {}", style(&o.target).white(), style(format!("({})", reclaim::human_bytes(o.bytes))).dim());
+    }
+}
+
+/// `--check mounts-config` lines: one per reference, or a note that there are none.
+fn print_mount_refs(refs: &[mounts_config::MountRef]) {
+    println!();
+    if refs.is_empty() {
+        println!("{}", style("Mount configuration: no references to the target").dim());
+        return;
+    }
+    println!("{}", style("Mount configuration:").dim());
+    for r in refs {
+        let via = r.via_symlink.as_ref().map(|v| format!(" (via symlink to {})", v)).unwrap_or_default();
+        println!(
+            "  {} {} {}{} {}",
+            style(&r.file).cyan(),
+            style(r.role).dim(),
+            style(&r.path).white().bold(),
+            style(via).yellow(),
+            style(format!("{} {}", r.relation, r.target)).dim(),
+        );
+    }
+}
+
+/// `--changed-since` report: `+` added, `-` removed, `~` retargeted matches, then counts.
+fn print_diff(diff: &Diff, targets: &[scan::Target], json: bool, encoding: output::PathEncoding, quote: Option<output::Quote>, secs: f64) -> Result<()> {
+    if json {
+        let paths = |v: &[snapshot::SnapMatch]| -> Vec<serde_json::Value> {
+            v.iter().map(|m| serde_json::to_value(JsonPath { path: &m.path, encoding }).unwrap_or_default()).collect()
+        };
+        let retargeted: Vec<serde_json::Value> = diff.retargeted.iter()
+            .map(|(old, new)| serde_json::json!({
+                "path": JsonPath { path: &new.path, encoding },
+                "from": old.link_text.as_ref().map(|t| t.to_string_lossy()),
+                "to": new.link_text.as_ref().map(|t| t.to_string_lossy()),
+            }))
+            .collect();
+        let doc = serde_json::json!({
+            "added": paths(&diff.added),
+            "removed": paths(&diff.removed),
+            "retargeted": retargeted,
+            "dirs_checked": diff.dirs_checked,
+            "dirs_rescanned": diff.dirs_rescanned,
+        });
+        println!("{}", serde_json::to_string_pretty(&doc)?);
+        return Ok(());
+    }
+    let text = |t: &Option<PathBuf>| t.as_ref().map_or_else(|| "?".to_string(), |t| t.display().to_string());
+    for m in &diff.added {
+        println!("{} {} {}", style("+").green().bold(), style(output::display_path(&m.path, quote)).white().bold(), style(format!("-> {}", targets[m.target].given)).dim());
+    }
+    for m in &diff.removed {
+        println!("{} {}", style("-").red().bold(), style(output::display_path(&m.path, quote)).white().bold());
+    }
+    for (old, new) in &diff.retargeted {
+        println!("{} {} {}", style("~").yellow().bold(), style(output::display_path(&new.path, quote)).white().bold(), style(format!("{} -> {}", text(&old.link_text), text(&new.link_text))).dim());
+    }
+    if !(diff.added.is_empty() && diff.removed.is_empty() && diff.retargeted.is_empty()) { println!(); }
+    println!("{} {}", style("Added:").dim(), style(diff.added.len().to_formatted_string(&Locale::en)).bold().green());
+    println!("{} {}", style("Removed:").dim(), style(diff.removed.len().to_formatted_string(&Locale::en)).bold().red());
+    println!("{} {}", style("Retargeted:").dim(), style(diff.retargeted.len().to_formatted_string(&Locale::en)).bold().yellow());
+    println!(
+        "{} {} {}",
+        style("Directories rescanned:").dim(),
+        style(diff.dirs_rescanned.to_formatted_string(&Locale::en)).bold().cyan(),
+        style(format!("of {}", diff.dirs_checked.to_formatted_string(&Locale::en))).dim(),
+    );
+    println!("{} {:.2}s", style("Elapsed:").dim(), secs);
+    Ok(())
+}
+
+/// `--profile-ignores` table on stderr, so it never mixes with JSON or NUL output.
+fn print_ignore_profile(rows: &[scan::IgnoreProfile]) {
+    eprintln!();
+    eprintln!("{}", style("Ignore-rule profile (count-only walks, warm cache):").dim());
+    let label_w = rows.iter().map(|r| r.label.len()).max().unwrap_or(0);
+    for r in rows {
+        let us = if r.entries > 0 { r.time.as_secs_f64() * 1e6 / r.entries as f64 } else { 0.0 };
+        eprintln!(
+            "  {:<label_w$}  {:>12} {}  {:>8.3}s  {}",
+            r.label,
+            style(r.entries.to_formatted_string(&Locale::en)).bold().cyan(),
+            style("entries").dim(),
+            r.time.as_secs_f64(),
+            style(format!("({:.2} us/entry)", us)).dim(),
+        );
+    }
+}
+
+/// `--stats-json`: counts, phase timings, and rates for dashboards.
+fn write_stats_json(path: &Path, result: &scan::ScanResult, reclaim: Option<&reclaim::Reclaim>, elapsed_secs: f64) -> Result<()> {
+    let mut doc = serde_json::json!({
+        "folders": result.dirs,
+        "files": result.files,
+        "symlinks": result.symlinks,
+        "matches": result.matches.len(),
+        "copies": result.copies.len(),
+        "hardlinks": result.hardlinks.len(),
+        "errors": result.errors.len(),
+        "aborted": result.aborted,
+        "cancelled": result.cancelled,
+        "limited": result.limited,
+        "skipped_dirs": result.skipped_dirs.len(),
+        "retries": result.retries,
+        "elapsed_secs": elapsed_secs,
+        "walk_secs": result.walk_time.as_secs_f64(),
+        "resolve_secs": result.resolve_time.as_secs_f64(),
+        "walk_entries_per_sec": result.walk_rate(),
+        "resolve_symlinks_per_sec": result.resolve_rate(),
+    });
+    if let Some(r) = reclaim { doc["reclaim"] = serde_json::to_value(r)?; }
+    if !result.roots.is_empty() {
+        doc["roots"] = result.roots.iter()
+            .map(|r| serde_json::json!({
+                "root": r.root.to_string_lossy(),
+                "folders": r.dirs,
+                "files": r.files,
+                "symlinks": r.symlinks,
+                "matches": r.matches,
+            }))
+            .collect();
+    }
+    let text = serde_json::to_string_pretty(&doc)?;
+    if path.as_os_str() == "-" {
+        eprintln!("{}", text);
+    } else {
+        std::fs::write(path, text + "\n").with_context(|| format!("write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn match_line(m: &Match, icons: Icons, quote: Option<output::Quote>) -> String {
+    let path = output::display_path(&m.path, quote);
+    if m.broken {
+        format!("{}{} {}", icons.prefix(Icon::Broken), style(path).white().bold(), style("(broken)").red())
+    } else {
+        format!("{}{}", icons.prefix(Icon::Link), style(path).white().bold())
+    }
+}
+
+/// `--broken` details of a dangling match: its link text and why it does not resolve.
+fn broken_detail(p: &Path, from_manifest: bool) -> (Option<PathBuf>, Option<String>) {
+    if from_manifest { return (None, Some("not in manifest".into())); }
+    (std::fs::read_link(p).ok(), scan::broken_reason(p))
+}
+
+/// Schema 2 `metadata`: the link's own `lstat`, or null if it is gone.
+fn link_metadata(p: &Path) -> serde_json::Value {
+    let Ok(meta) = std::fs::symlink_metadata(p) else { return serde_json::Value::Null };
+    let mtime = meta.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).map(|d| d.as_secs());
+    #[allow(unused_mut)]
+    let mut v = serde_json::json!({ "size": meta.len(), "mtime": mtime });
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        v["mode"] = format!("{:o}", meta.mode() & 0o7777).into();
+        v["uid"] = meta.uid().into();
+        v["gid"] = meta.gid().into();
+        v["owner"] = users::user_name(meta.uid()).into();
+        v["group"] = users::group_name(meta.gid()).into();
+        v["inode"] = meta.ino().into();
+    }
+    v
+}
+
+/// One `--json` element. Schema 2: an object with the link text, resolution, matched target, depth, and metadata.
+/// Schema 1: the path, or an object with its root and depth, link details with `--broken`/`--all`,
+/// the physical path under an aliased root, and the `--via` chain and attributes on request. `roots` is empty for a manifest.
+fn json_item(m: &Match, t: &scan::Target, opts: &FindOpts, roots: &[PathBuf], aliases: &scan::RootAliases) -> serde_json::Result<serde_json::Value> {
+    let from_manifest = opts.from_manifest.is_some();
+    let schema2 = opts.json_schema >= 2;
+    let path = serde_json::to_value(JsonPath { path: &m.path, encoding: opts.path_encoding })?;
+    let object = schema2 || opts.with_xattrs || opts.with_quarantine || opts.via || opts.scan.broken || opts.scan.all || !aliases.is_empty() || roots.len() > 1;
+    if !object && !path.is_object() { return Ok(path); }
+    // Encoded paths are already objects; plain strings become `{"path": ...}`
+    let mut item = if path.is_object() { path } else { serde_json::json!({ "path": path }) };
+    if let Some((root, depth)) = scan::locate(roots, &m.path) {
+        item["root"] = serde_json::to_value(JsonPath { path: root, encoding: opts.path_encoding })?;
+        item["depth"] = depth.into();
+    } else if schema2 {
+        item["depth"] = scan::depth(&m.path).into();
+    }
+    if !aliases.is_empty() {
+        item["physical"] = match aliases.physical(&m.path) {
+            Some(p) => serde_json::to_value(JsonPath { path: &p, encoding: opts.path_encoding })?,
+            None => serde_json::Value::Null,
+        };
+    }
+    if schema2 || opts.scan.broken || opts.scan.all {
+        let (text, reason) = if m.broken { broken_detail(&m.path, from_manifest) } else { (std::fs::read_link(&m.path).ok(), None) };
+        item["broken"] = m.broken.into();
+        item["link_text"] = text.map(|t| t.to_string_lossy().into_owned()).into();
+        if schema2 || opts.scan.all {
+            // Manifest links resolve through the listing, not this filesystem
+            let resolved = if m.broken { None } else if from_manifest { Some(scan::filter_target(&m.path, t)) } else { scan::link_destination(&opts.scan, &m.path) };
+            item["resolved"] = resolved.map(|r| r.to_string_lossy().into_owned()).into();
+        }
+        item["reason"] = reason.into();
+    }
+    if schema2 {
+        let dest = scan::filter_target(&m.path, t);
+        item["target"] = if dest.as_os_str().is_empty() { serde_json::Value::Null } else { serde_json::to_value(JsonPath { path: &dest, encoding: opts.path_encoding })? };
+        item["matched_target"] = t.given.clone().into();
+        item["metadata"] = if from_manifest { serde_json::Value::Null } else { link_metadata(&m.path) };
+    }
+    if opts.via {
+        let chain = scan::link_chain(&m.path);
+        let via: Vec<JsonPath> = chain.iter().map(|p| JsonPath { path: p, encoding: opts.path_encoding }).collect();
+        item["via"] = serde_json::to_value(via)?;
+    }
+    if opts.with_xattrs {
+        item["xattrs"] = serde_json::to_value(xattr::read(&m.path, false))?;
+        item["target_xattrs"] = serde_json::to_value(xattr::read(&m.path, true))?;
+    }
+    if opts.with_quarantine {
+        item["quarantine"] = serde_json::to_value(xattr::quarantine(&m.path, false))?;
+        item["target_quarantine"] = serde_json::to_value(xattr::quarantine(&m.path, true))?;
+    }
+    Ok(item)
+}
+
+/// One `--json-lines` object: the link, where it points, and the target it matched as given.
+pub(crate) fn json_line(m: &Match, t: &scan::Target, encoding: output::PathEncoding) -> serde_json::Result<serde_json::Value> {
+    let dest = scan::filter_target(&m.path, t);
+    let dest = if dest.as_os_str().is_empty() { serde_json::Value::Null } else { serde_json::to_value(JsonPath { path: &dest, encoding })? };
+    Ok(serde_json::json!({
+        "path": JsonPath { path: &m.path, encoding },
+        "target": dest,
+        "matched_target": t.given,
+        "broken": m.broken,
+    }))
+}
+
+/// A `--broken` match: path, link text, and the failure reason.
+fn broken_line(m: &Match, icons: Icons, quote: Option<output::Quote>, from_manifest: bool) -> String {
+    let (text, reason) = broken_detail(&m.path, from_manifest);
+    let text = text.map(|t| format!(" {} {}", style("->").dim(), style(t.display()).dim())).unwrap_or_default();
+    format!(
+        "{}{}{} {}",
+        icons.prefix(Icon::Broken),
+        style(output::display_path(&m.path, quote)).white().bold(),
+        text,
+        style(format!("({})", reason.as_deref().unwrap_or("broken"))).red(),
+    )
+}
+
+/// An `--all` inventory line for a live link: path, link text, and where it resolves.
+fn inventory_line(m: &Match, opts: &ScanOpts, icons: Icons, quote: Option<output::Quote>) -> String {
+    let text = std::fs::read_link(&m.path).map(|t| t.display().to_string()).unwrap_or_default();
+    let resolved = scan::link_destination(opts, &m.path).map(|r| r.display().to_string()).unwrap_or_default();
+    format!(
+        "{}{} {} {} {} {}",
+        icons.prefix(Icon::Link),
+        style(output::display_path(&m.path, quote)).white().bold(),
+        style("->").dim(),
+        style(text).dim(),
+        style("=>").dim(),
+        style(resolved).cyan(),
+    )
+}
+
+/// `--with-xattrs` / `--with-quarantine` lines under a match: the link's own attributes, then its target's.
+fn attr_lines(p: &Path, xattrs: bool, quarantine: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (of, follow) in [("link", false), ("target", true)] {
+        if xattrs {
+            for (k, v) in xattr::read(p, follow) {
+                lines.push(format!("    {} {}={}", style(format!("{}:", of)).dim(), style(k).cyan(), v));
+            }
+        }
+        if let Some(q) = quarantine.then(|| xattr::quarantine(p, follow)).flatten() {
+            let desc = if q.approved { style(q.describe()).yellow() } else { style(q.describe()).red() };
+            lines.push(format!("    {} {} {}", style(format!("{}:", of)).dim(), style("quarantined").bold(), desc));
+        }
+    }
+    lines
+}
+
+/// Boxed target -> match/broken counts, one row per target.
+fn print_target_table(targets: &[scan::Target], result: &scan::ScanResult, glyphs: &term::Glyphs) {
+    let counts = result.per_target(targets.len());
+    let with_copies = !result.copies.is_empty();
+    let with_hardlinks = !result.hardlinks.is_empty();
+    let name_w = targets.iter().map(|t| measure_text_width(&t.given)).max().unwrap_or(0).max("Target".len());
+    let mut header = format!("{:<name_w$}  {:>8}  {:>8}", "Target", "Matches", "Broken");
+    if with_copies { header.push_str(&format!("  {:>8}", "Copies")); }
+    if with_hardlinks { header.push_str(&format!("  {:>10}", "Hard links")); }
+    let mut lines = vec![style(header).dim().to_string()];
+    for (t, c) in targets.iter().zip(counts) {
+        let n_s = format!("{:>8}", c.matches.to_formatted_string(&Locale::en));
+        let b_s = format!("{:>8}", c.broken.to_formatted_string(&Locale::en));
+        let mut line = format!(
+            "{:<name_w$}  {}  {}",
+            t.given,
+            if c.matches > 0 { style(n_s).bold().green() } else { style(n_s).dim() },
+            if c.broken > 0 { style(b_s).bold().red() } else { style(b_s).dim() },
+        );
+        if with_copies { line.push_str(&format!("  {:>8}", c.copies.to_formatted_string(&Locale::en))); }
+        if with_hardlinks { line.push_str(&format!("  {:>10}", c.hardlinks.to_formatted_string(&Locale::en))); }
+        lines.push(line);
+    }
+    print_box(&lines, glyphs);
+}
+
+/// One `--count-by-target` row.
+#[derive(serde::Serialize)]
+struct TargetCount {
+    target: String,
+    links: usize,
+    broken: usize,
+}
+
+/// Inbound links per target, busiest first; `--broken`/`--all` links count toward their destination.
+fn target_counts(targets: &[scan::Target], result: &scan::ScanResult, opts: &ScanOpts, from_manifest: bool) -> Vec<TargetCount> {
+    let mut counts: HashMap<String, (usize, usize)> = targets.iter()
+        .filter(|t| !t.is_catch_all())
+        .map(|t| (t.given.clone(), (0, 0)))
+        .collect();
+    for m in &result.matches {
+        let t = &targets[m.target];
+        // Destinations are looked up on the live filesystem; a manifest's links count toward the catch-all itself
+        let dest = (t.is_catch_all() && !from_manifest).then(|| scan::points_to(opts, &m.path)).flatten();
+        let key = dest.map_or_else(|| t.given.clone(), |d| d.display().to_string());
+        let c = counts.entry(key).or_default();
+        c.0 += 1;
+        if m.broken { c.1 += 1; }
+    }
+    let mut rows: Vec<TargetCount> = counts.into_iter()
+        .map(|(target, (links, broken))| TargetCount { target, links, broken })
+        .collect();
+    rows.sort_by(|a, b| b.links.cmp(&a.links).then_with(|| a.target.cmp(&b.target)));
+    rows
+}
+
+/// Boxed `--count-by-target` table.
+fn print_target_counts(rows: &[TargetCount], glyphs: &term::Glyphs) {
+    let name_w = rows.iter().map(|r| measure_text_width(&r.target)).max().unwrap_or(0).max("Target".len());
+    let with_broken = rows.iter().any(|r| r.broken > 0);
+    let mut header = format!("{:<name_w$}  {:>8}", "Target", "Links");
+    if with_broken { header.push_str(&format!("  {:>8}", "Broken")); }
+    let mut lines = vec![style(header).dim().to_string()];
+    for r in rows {
+        let n_s = format!("{:>8}", r.links.to_formatted_string(&Locale::en));
+        let mut line = format!("{:<name_w$}  {}", r.target, if r.links > 0 { style(n_s).bold().green() } else { style(n_s).dim() });
+        if with_broken {
+            let b_s = format!("{:>8}", r.broken.to_formatted_string(&Locale::en));
+            line.push_str(&format!("  {}", if r.broken > 0 { style(b_s).bold().red() } else { style(b_s).dim() }));
+        }
+        lines.push(line);
+    }
+    print_box(&lines, glyphs);
+}
+
+/// Counts per `--root`, when there are several.
+fn print_root_table(roots: &[scan::RootStats], glyphs: &term::Glyphs) {
+    let names: Vec<String> = roots.iter().map(|r| r.root.display().to_string()).collect();
+    let name_w = names.iter().map(|n| measure_text_width(n)).max().unwrap_or(0).max("Root".len());
+    let header = format!("{:<name_w$}  {:>10}  {:>10}  {:>10}  {:>8}", "Root", "Folders", "Files", "Symlinks", "Matches");
+    let mut lines = vec![style(header).dim().to_string()];
+    for (name, r) in names.iter().zip(roots) {
+        let m_s = format!("{:>8}", r.matches.to_formatted_string(&Locale::en));
+        lines.push(format!(
+            "{:<name_w$}  {:>10}  {:>10}  {:>10}  {}",
+            name,
+            r.dirs.to_formatted_string(&Locale::en),
+            r.files.to_formatted_string(&Locale::en),
+            r.symlinks.to_formatted_string(&Locale::en),
+            if r.matches > 0 { style(m_s).bold().green() } else { style(m_s).dim() },
+        ));
+    }
+    print_box(&lines, glyphs);
+}
+
+fn run_bench(opts: &BenchOpts) -> Result<()> {
+    let targets = scan::resolve_targets(&opts.scan)?;
+    let mut times = Vec::with_capacity(opts.runs);
+    for i in 0..opts.warmup + opts.runs.max(1) {
+        let start = Instant::now();
+        let r = scan::scan(&opts.scan, &targets, &hooks::Quiet, &|_| {})?;
+        if r.aborted { return Err(aborted_error(&r)); }
+        let secs = start.elapsed().as_secs_f64();
+        if i < opts.warmup { continue; }
+        println!(
+            "{} {:.3}s {}",
+            style(format!("Run {}:", i - opts.warmup + 1)).dim(),
+            secs,
+            style(format!(
+                "({} dirs, {} files, {} symlinks, {} matches)",
+                r.dirs.to_formatted_string(&Locale::en),
+                r.files.to_formatted_string(&Locale::en),
+                r.symlinks.to_formatted_string(&Locale::en),
+                r.matches.len().to_formatted_string(&Locale::en),
+            )).dim(),
+        );
+        times.push(secs);
+    }
+    times.sort_by(f64::total_cmp);
+    let mean = times.iter().sum::<f64>() / times.len() as f64;
+    println!();
+    println!("{} {:.3}s", style("Min:").dim(), times[0]);
+    println!("{} {:.3}s", style("Median:").dim(), times[times.len() / 2]);
+    println!("{} {:.3}s", style("Mean:").dim(), mean);
+    println!("{} {:.3}s", style("Max:").dim(), times[times.len() - 1]);
+    Ok(())
+}
+
+/// `--delete`, `--retarget`, and the other changes: the links changed (or, with `--dry-run`, that would be), failures, and counts.
+fn print_action_report(action: &actions::Action, r: &actions::Report, dry_run: bool, icons: Icons, glyphs: &term::Glyphs, quote: Option<output::Quote>) {
+    println!();
+    if !r.done.is_empty() {
+        let lines: Vec<String> = r.done.iter()
+            .map(|(p, text)| match text {
+                Some(t) => format!("{} -> {}", output::display_path(p, quote), output::display_path(t, quote)),
+                None => output::display_path(p, quote).into_owned(),
+            })
+            .collect();
+        print_box(&lines, glyphs);
+    }
+    for (p, e) in &r.failed {
+        println!("{}{} {}", icons.prefix(Icon::Warning), style(format!("Could not {} {}:", action.verb(), output::display_path(p, quote))).red(), e);
+    }
+    let n = r.done.len().to_formatted_string(&Locale::en);
+    let head = if dry_run { format!("Would {} {} symlinks (dry run)", action.verb(), n) } else { format!("{} {} symlinks", action.past(), n) };
+    let mut tail = Vec::new();
+    if r.unchanged > 0 { tail.push(format!("{} unchanged", r.unchanged)); }
+    if r.skipped > 0 { tail.push(format!("{} skipped", r.skipped)); }
+    if !r.failed.is_empty() { tail.push(format!("{} failed", r.failed.len())); }
+    let tail = if tail.is_empty() { String::new() } else { format!(" {}", style(format!("({})", tail.join(", "))).dim()) };
+    println!("{}{}", style(head).bold().green(), tail);
+}
+
+fn print_box(lines: &[String], g: &term::Glyphs) {
+    let pad = 1usize;
+    let content_width = lines.iter().map(|s| measure_text_width(s)).max().unwrap_or(0);
+    let width = content_width + pad * 2;
+    println!("{}", style(format!("{}{}{}", g.top_left, g.horizontal.repeat(width), g.top_right)).cyan());
+    for line in lines {
+        let w = measure_text_width(line);
+        let right = width.saturating_sub(w + pad);
+        print!("{}{}{}", style(g.vertical).cyan(), " ".repeat(pad), line);
+        println!("{}{}", " ".repeat(right), style(g.vertical).cyan());
+    }
+    println!("{}", style(format!("{}{}{}", g.bottom_left, g.horizontal.repeat(width), g.bottom_right)).cyan());
+}
@@ -0,0 +1,36 @@
+//! `--lock`: advisory single-instance lock for scheduled runs.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Exit status when another instance holds the lock (EX_TEMPFAIL).
+pub const EXIT_LOCKED: i32 = 75;
+
+/// Take an exclusive advisory lock on `path`, held until the returned file is dropped.
+///
+/// Returns `Ok(None)` when another process holds it and `wait` is false.
+pub fn acquire(path: &Path, wait: bool) -> Result<Option<File>> {
+    let mut f = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("open lock file {}", path.display()))?;
+    if wait {
+        f.lock().with_context(|| format!("lock {}", path.display()))?;
+    } else {
+        match f.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e).with_context(|| format!("lock {}", path.display())),
+        }
+    }
+    // Record the holder for whoever finds the lock taken
+    f.set_len(0)?;
+    writeln!(f, "{}", std::process::id())?;
+    Ok(Some(f))
+}
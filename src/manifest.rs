@@ -0,0 +1,326 @@
+//! `--from-manifest`: match against a recorded listing (mtree or `find -ls`) instead of the live filesystem.
+//!
+//! Manifest paths are taken as absolute with the listing's root at `/` (`./usr/bin/x` is `/usr/bin/x`),
+//! and links are resolved lexically against the other entries, so a backup can be audited without
+//! restoring it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+use crate::cli::ScanOpts;
+use crate::hooks::{Hooks, Phase};
+use crate::scan::{self, LinkRecord, Match, ScanError, ScanResult, Target};
+
+/// Symlink hops before a chain is treated as a loop (the kernel's limit).
+const MAX_HOPS: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Dir,
+    File,
+    Link(PathBuf),
+    Other,
+}
+
+/// One recorded entry: the path as written, and what it was.
+struct Entry {
+    shown: PathBuf,
+    kind: Kind,
+}
+
+#[derive(Default)]
+struct Manifest {
+    /// Keyed by the absolute, normalized path
+    entries: HashMap<PathBuf, Entry>,
+    errors: Vec<ScanError>,
+}
+
+/// Absolute, normalized form of a manifest path (`.` is the root).
+fn normalize(p: &Path) -> PathBuf {
+    let mut out = PathBuf::from("/");
+    for c in p.components() {
+        match c {
+            Component::ParentDir => { out.pop(); }
+            Component::Normal(n) => out.push(n),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Undo backslash escapes: `\ooo` octal bytes (mtree, `find -ls`), `\\`, and `\<char>`.
+fn unescape(s: &str) -> PathBuf {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'\\' && i + 1 < b.len() {
+            let oct = &b[i + 1..b.len().min(i + 4)];
+            if oct.len() == 3 && oct.iter().all(|c| (b'0'..=b'7').contains(c)) {
+                out.push(oct.iter().fold(0u8, |n, c| n.wrapping_mul(8) + (c - b'0')));
+                i += 4;
+            } else {
+                out.push(match b[i + 1] { b's' => b' ', b't' => b'\t', b'n' => b'\n', c => c });
+                i += 2;
+            }
+        } else {
+            out.push(b[i]);
+            i += 1;
+        }
+    }
+    bytes_to_path(out)
+}
+
+#[cfg(unix)]
+fn bytes_to_path(b: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(b))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(b: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&b).into_owned())
+}
+
+/// `find -ls`: inode, blocks, mode, links, owner, group, size (or `major, minor`), 3 date fields, path.
+fn looks_like_find_ls(line: &str) -> bool {
+    let mut it = line.split_whitespace();
+    let inode = it.next().unwrap_or("");
+    let mode = it.nth(1).unwrap_or("");
+    !inode.is_empty() && inode.bytes().all(|c| c.is_ascii_digit())
+        && mode.len() >= 10 && "-dlcbps".contains(&mode[..1])
+}
+
+/// Split off `n` whitespace-separated fields and return the rest of the line.
+fn skip_fields(line: &str, n: usize) -> Option<&str> {
+    let mut rest = line.trim_start();
+    for _ in 0..n {
+        let end = rest.find(char::is_whitespace)?;
+        rest = rest[end..].trim_start();
+    }
+    Some(rest)
+}
+
+impl Manifest {
+    fn add(&mut self, shown: PathBuf, kind: Kind, progress: &dyn Hooks) {
+        progress.walked(&shown, kind == Kind::Dir, matches!(kind, Kind::Link(_)));
+        self.entries.insert(normalize(&shown), Entry { shown, kind });
+    }
+
+    fn error(&mut self, line: usize, message: &str) {
+        self.errors.push(ScanError { path: None, message: format!("manifest line {}: {}", line, message) });
+    }
+
+    fn parse_find_ls(&mut self, lineno: usize, line: &str, progress: &dyn Hooks) {
+        let mode = line.split_whitespace().nth(2).unwrap_or("");
+        // Device nodes print "major, minor" in place of the size
+        let fields = if mode.starts_with(['b', 'c']) { 11 } else { 10 };
+        let Some(rest) = skip_fields(line, fields).filter(|r| !r.is_empty()) else {
+            return self.error(lineno, "truncated find -ls line");
+        };
+        let kind = match &mode[..1] {
+            "d" => Kind::Dir,
+            "-" => Kind::File,
+            "l" => match rest.split_once(" -> ") {
+                Some((path, text)) => return self.add(unescape(path), Kind::Link(unescape(text)), progress),
+                None => return self.error(lineno, "symlink without ` -> target`"),
+            },
+            _ => Kind::Other,
+        };
+        self.add(unescape(rest), kind, progress);
+    }
+
+    /// One mtree entry; `cwd` tracks the directory of hierarchical (non-path) specs.
+    fn parse_mtree(&mut self, lineno: usize, line: &str, defaults: &mut HashMap<String, String>, cwd: &mut PathBuf, progress: &dyn Hooks) {
+        let mut words = line.split_whitespace();
+        let Some(first) = words.next() else { return };
+        let keywords = |words: std::str::SplitWhitespace| -> HashMap<String, String> {
+            let mut kw = defaults.clone();
+            for w in words {
+                let (k, v) = w.split_once('=').unwrap_or((w, ""));
+                kw.insert(k.to_string(), v.to_string());
+            }
+            kw
+        };
+        match first {
+            "/set" => { *defaults = keywords(words); return; }
+            "/unset" => {
+                for k in words { if k == "all" { defaults.clear() } else { defaults.remove(k); } }
+                return;
+            }
+            ".." => { cwd.pop(); return; }
+            _ => {}
+        }
+        let kw = keywords(words);
+        let name = unescape(first);
+        let full = first.contains('/');
+        let shown = if full || first == "." { name } else { cwd.join(name) };
+        let kind = match kw.get("type").map(String::as_str) {
+            Some("dir") => Kind::Dir,
+            Some("file") => Kind::File,
+            Some("link") => match kw.get("link") {
+                Some(text) => Kind::Link(unescape(text)),
+                None => return self.error(lineno, "type=link without link="),
+            },
+            Some(_) => Kind::Other,
+            None => return self.error(lineno, "entry without type="),
+        };
+        // A directory named without a path opens a level of a hierarchical spec
+        if !full && kind == Kind::Dir && shown != Path::new(".") { *cwd = shown.clone(); }
+        self.add(shown, kind, progress);
+    }
+
+    fn load(path: &Path, progress: &dyn Hooks) -> Result<Manifest> {
+        let text = fs::read(path).with_context(|| format!("read manifest {}", path.display()))?;
+        let text = String::from_utf8_lossy(&text);
+        let mut m = Manifest::default();
+        let mut defaults = HashMap::new();
+        let mut cwd = PathBuf::from(".");
+        let mut find_ls = None;
+        let mut pending = String::new();
+        for (i, raw) in text.lines().enumerate() {
+            // mtree continues long lines with a trailing backslash
+            if let Some(head) = raw.strip_suffix('\\').filter(|_| find_ls != Some(true)) {
+                pending.push_str(head);
+                pending.push(' ');
+                continue;
+            }
+            pending.push_str(raw);
+            let line = std::mem::take(&mut pending);
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') { continue; }
+            let is_find_ls = *find_ls.get_or_insert_with(|| looks_like_find_ls(trimmed));
+            if is_find_ls { m.parse_find_ls(i + 1, trimmed, progress); }
+            else { m.parse_mtree(i + 1, trimmed, &mut defaults, &mut cwd, progress); }
+        }
+        Ok(m)
+    }
+
+    /// Resolve every link along `p` through the manifest; `None` on a loop.
+    fn resolve(&self, p: &Path, hops: usize) -> Option<PathBuf> {
+        let mut out = PathBuf::from("/");
+        for c in p.components() {
+            match c {
+                Component::ParentDir => { out.pop(); }
+                Component::Normal(n) => {
+                    out.push(n);
+                    if let Some(Entry { kind: Kind::Link(text), .. }) = self.entries.get(&out) {
+                        if hops >= MAX_HOPS { return None; }
+                        let dest = out.parent().unwrap_or(Path::new("/")).join(text);
+                        out = self.resolve(&dest, hops + 1)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(out)
+    }
+
+    fn exists(&self, p: &Path) -> bool {
+        p == Path::new("/") || self.entries.contains_key(p)
+    }
+}
+
+/// Targets for a manifest scan: paths inside the listing, resolved through it rather than the filesystem.
+fn manifest_targets(m: &Manifest, opts: &ScanOpts) -> Result<Vec<Target>> {
+    let mut out: Vec<Target> = Vec::new();
+    for given in &opts.targets {
+        let lexical = normalize(Path::new(given));
+        let resolved = m.resolve(&lexical, 0).filter(|r| m.exists(r));
+        if resolved.is_none() {
+            eprintln!("warning: target {} is not in the manifest; matching broken links only", given);
+        }
+        if out.iter().any(|t| t.lexical() == lexical) { continue; }
+        out.push(Target::recorded(given.clone(), resolved, lexical).under(opts.under));
+    }
+    out.extend(scan::pattern_targets(opts)?);
+    Ok(out)
+}
+
+/// Parse `path` and match its recorded symlinks against `opts.targets`.
+pub fn scan(
+    path: &Path,
+    opts: &ScanOpts,
+    progress: &dyn Hooks,
+    on_match: &(dyn Fn(&Match) + Sync),
+) -> Result<(Vec<Target>, ScanResult)> {
+    if opts.filter.is_some() { anyhow::bail!("--where is not supported with --from-manifest"); }
+    if opts.copies { anyhow::bail!("--copies is not supported with --from-manifest"); }
+    if opts.hardlinks.is_some() { anyhow::bail!("--hardlinks is not supported with --from-manifest"); }
+    if opts.wsl_links { anyhow::bail!("--wsl-links is not supported with --from-manifest"); }
+    if !opts.exclude_targets.is_empty() { anyhow::bail!("--exclude-target is not supported with --from-manifest"); }
+    if !opts.perms.is_empty() { anyhow::bail!("--perm is not supported with --from-manifest"); }
+
+    let cancel = opts.walk.cancel.armed(opts.walk.timeout);
+    progress.phase(Phase::Walking);
+    let walk_start = Instant::now();
+    let m = Manifest::load(path, progress)?;
+    let walk_time = walk_start.elapsed();
+    let targets = manifest_targets(&m, opts)?;
+
+    let mut links: Vec<(&PathBuf, &Entry, &PathBuf)> = m.entries.iter()
+        .filter_map(|(abs, e)| match &e.kind { Kind::Link(text) => Some((abs, e, text)), _ => None })
+        .collect();
+    links.sort_by(|a, b| a.1.shown.cmp(&b.1.shown));
+    progress.phase(Phase::Resolving { total: links.len() });
+    let resolve_start = Instant::now();
+    let mut matches = Vec::new();
+    let mut records = Vec::new();
+    for (abs, e, text) in &links {
+        if cancel.is_cancelled() { break; }
+        let resolved = m.resolve(abs, 0).filter(|r| m.exists(r));
+        if opts.record_links {
+            records.push(LinkRecord {
+                path: e.shown.clone(),
+                link_text: Some(text.to_path_buf()),
+                error: resolved.is_none().then(|| "not in manifest".to_string()),
+                resolved: resolved.clone(),
+            });
+        }
+        let hit = match &resolved {
+            Some(r) => scan::innermost(&targets, |t| t.covers(r)).map(|t| (t, false)),
+            None => scan::match_link_text(abs, text, &targets).map(|t| (t, true)),
+        };
+        let hit = hit.or_else(|| {
+            // Where the link points: through the manifest, or lexically if it dangles
+            let dest = resolved.clone().unwrap_or_else(|| normalize(&abs.parent().unwrap_or(Path::new("/")).join(text)));
+            scan::match_link_pattern(text, &targets).or_else(|| scan::match_dest_regex(&dest, &targets)).map(|t| (t, resolved.is_none()))
+        });
+        let hit = hit.or_else(|| scan::match_catch_all(&targets, resolved.is_none()).map(|t| (t, resolved.is_none())));
+        if let Some((target, broken)) = hit {
+            let m = Match { path: e.shown.clone(), target, broken };
+            progress.matched(&m);
+            on_match(&m);
+            matches.push(m);
+        }
+        progress.resolved();
+    }
+    let resolve_time = resolve_start.elapsed();
+    progress.phase(Phase::Finished);
+
+    let count = |k: fn(&Kind) -> bool| m.entries.values().filter(|e| k(&e.kind)).count();
+    let result = ScanResult {
+        dirs: count(|k| *k == Kind::Dir),
+        files: count(|k| *k == Kind::File),
+        symlinks: links.len(),
+        matches: matches.into(),
+        copies: Vec::new(),
+        hardlinks: Vec::new(),
+        links: records,
+        errors: m.errors,
+        dir_mtimes: Vec::new(),
+        walk_time,
+        resolve_time,
+        aborted: cancel.reason().is_some(),
+        cancelled: cancel.reason(),
+        limited: false,
+        roots: Vec::new(),
+        skipped_dirs: Vec::new(),
+        retries: 0,
+    };
+    Ok((targets, result))
+}
@@ -0,0 +1,60 @@
+//! `merge`: combine `--json` reports from several machines into one document keyed by host.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+
+use crate::cli::MergeOpts;
+
+/// Host name for a report: its `"host"` field, else the file stem (`web1.json` -> `web1`).
+fn host_of(path: &std::path::Path, doc: &Value) -> String {
+    if let Some(h) = doc.get("host").and_then(Value::as_str) { return h.to_string(); }
+    path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string())
+}
+
+/// Normalize either report shape (bare match array, or `{matches, copies?, targets?}`) to an object.
+fn envelope(doc: Value, path: &std::path::Path) -> Result<Map<String, Value>> {
+    match doc {
+        Value::Array(matches) => Ok(Map::from_iter([("matches".to_string(), Value::Array(matches))])),
+        Value::Object(mut m) if m.get("matches").is_some_and(Value::is_array) => {
+            m.remove("host");
+            Ok(m)
+        }
+        _ => anyhow::bail!("{}: not a find-symlinks --json report", path.display()),
+    }
+}
+
+pub fn run(opts: MergeOpts) -> Result<()> {
+    let mut hosts = Map::new();
+    let mut sources: Vec<(String, PathBuf)> = Vec::new();
+    let mut total_matches = 0usize;
+    let mut total_targets = Map::new();
+    for path in &opts.reports {
+        let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        let doc: Value = serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+        let host = host_of(path, &doc);
+        if let Some((_, prev)) = sources.iter().find(|(h, _)| *h == host) {
+            anyhow::bail!("host {:?} appears in both {} and {}", host, prev.display(), path.display());
+        }
+        let env = envelope(doc, path)?;
+        total_matches += env["matches"].as_array().map_or(0, Vec::len);
+        if let Some(Value::Object(targets)) = env.get("targets") {
+            for (given, counts) in targets {
+                let sum = total_targets.entry(given.clone()).or_insert_with(|| json!({}));
+                let Value::Object(counts) = counts else { continue };
+                for (k, n) in counts {
+                    let prev = sum[k.as_str()].as_u64().unwrap_or(0);
+                    sum[k.as_str()] = (prev + n.as_u64().unwrap_or(0)).into();
+                }
+            }
+        }
+        hosts.insert(host.clone(), Value::Object(env));
+        sources.push((host, path.clone()));
+    }
+    let mut totals = json!({ "hosts": hosts.len(), "matches": total_matches });
+    if !total_targets.is_empty() { totals["targets"] = total_targets.into(); }
+    let doc = json!({ "hosts": hosts, "totals": totals });
+    println!("{}", serde_json::to_string_pretty(&doc)?);
+    Ok(())
+}
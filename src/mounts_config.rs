@@ -0,0 +1,123 @@
+//! `--check mounts-config`: mount configuration that refers to a target, besides symlinks.
+//!
+//! `/etc/fstab` entries and systemd `.mount`/`.automount` units are checked for a
+//! source or mount point that is the target, contains it, or lies inside it. Paths
+//! are compared lexically and, when they exist, through their symlinks, so a mount
+//! point reached through a symlinked directory is reported too.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::scan::{self, Target};
+
+/// Where systemd looks for units, highest priority first.
+const UNIT_DIRS: &[&str] = &["/etc/systemd/system", "/run/systemd/system", "/usr/local/lib/systemd/system", "/usr/lib/systemd/system", "/lib/systemd/system"];
+
+#[derive(Serialize)]
+pub struct MountRef {
+    /// `/etc/fstab:12` or the unit file
+    pub file: String,
+    /// `bind source`, `mount source`, or `mount point`
+    pub role: &'static str,
+    pub path: String,
+    /// Set when `path` reaches the target only through a symlink: where it really is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via_symlink: Option<String>,
+    /// `is target`, `contains target`, or `inside target`
+    pub relation: &'static str,
+    pub target: String,
+}
+
+/// One mount as configured: source, mount point, options.
+struct Entry {
+    file: String,
+    what: String,
+    at: String,
+    options: String,
+}
+
+/// fstab escapes spaces and tabs as `\040` and `\011`.
+fn unescape(s: &str) -> String {
+    s.replace("\\040", " ").replace("\\011", "\t").replace("\\134", "\\")
+}
+
+fn fstab() -> Vec<Entry> {
+    let Ok(text) = fs::read_to_string("/etc/fstab") else { return Vec::new() };
+    text.lines().enumerate()
+        .filter_map(|(i, l)| {
+            let l = l.trim();
+            if l.is_empty() || l.starts_with('#') { return None; }
+            let mut f = l.split_whitespace();
+            let (what, at) = (f.next()?, f.next()?);
+            let options = f.nth(1).unwrap_or("");
+            Some(Entry { file: format!("/etc/fstab:{}", i + 1), what: unescape(what), at: unescape(at), options: options.to_string() })
+        })
+        .collect()
+}
+
+/// `What=`, `Where=`, `Options=` of every mount and automount unit; a unit in an earlier directory masks later ones.
+fn units() -> Vec<Entry> {
+    let mut seen = std::collections::HashSet::new();
+    let mut out = Vec::new();
+    for dir in UNIT_DIRS {
+        let Ok(rd) = fs::read_dir(dir) else { continue };
+        let mut files: Vec<PathBuf> = rd.flatten().map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "mount" || e == "automount"))
+            .collect();
+        files.sort();
+        for p in files {
+            if !seen.insert(p.file_name().map(|n| n.to_os_string())) { continue; }
+            let Ok(text) = fs::read_to_string(&p) else { continue };
+            let mut e = Entry { file: p.display().to_string(), what: String::new(), at: String::new(), options: String::new() };
+            for l in text.lines().map(str::trim) {
+                let Some((k, v)) = l.split_once('=') else { continue };
+                match k.trim() {
+                    "What" => e.what = v.trim().to_string(),
+                    "Where" => e.at = v.trim().to_string(),
+                    "Options" => e.options = v.trim().to_string(),
+                    _ => {}
+                }
+            }
+            out.push(e);
+        }
+    }
+    out
+}
+
+/// How `p` (absolute) relates to `target`.
+fn relation(p: &Path, target: &Path) -> Option<&'static str> {
+    if p == target { Some("is target") }
+    else if target.starts_with(p) && p != Path::new("/") { Some("contains target") }
+    else if p.starts_with(target) { Some("inside target") }
+    else { None }
+}
+
+/// References to `targets` (path targets only) in fstab and mount units.
+pub fn check(targets: &[Target]) -> Vec<MountRef> {
+    let mut out = Vec::new();
+    for e in fstab().into_iter().chain(units()) {
+        let bind = e.options.split(',').any(|o| o == "bind" || o == "rbind");
+        let paths = [(if bind { "bind source" } else { "mount source" }, &e.what), ("mount point", &e.at)];
+        for (role, raw) in paths {
+            // UUID=, LABEL=, server:/export, tmpfs, ...: not a local path
+            if !raw.starts_with('/') { continue; }
+            let lexical = scan::lexical_absolute(Path::new(raw));
+            let real = scan::realpath(Path::new(raw)).ok().filter(|r| *r != lexical);
+            for t in targets.iter().filter(|t| !t.lexical().as_os_str().is_empty()) {
+                let tpath = t.resolved.as_deref().unwrap_or(t.lexical());
+                let (relation, via) = match (relation(&lexical, t.lexical()).or_else(|| relation(&lexical, tpath)), &real) {
+                    (Some(r), _) => (r, None),
+                    (None, Some(real)) => match relation(real, tpath) {
+                        Some(r) => (r, Some(real.display().to_string())),
+                        None => continue,
+                    },
+                    (None, None) => continue,
+                };
+                out.push(MountRef { file: e.file.clone(), role, path: raw.clone(), via_symlink: via, relation, target: t.given.clone() });
+            }
+        }
+    }
+    out
+}
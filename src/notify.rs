@@ -0,0 +1,69 @@
+//! `--notify`: tell the user a scan finished, for runs left unattended in another window.
+
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum NotifyMode {
+    /// Desktop notification, plus a terminal bell when stderr is a terminal
+    Auto,
+    /// Desktop notification only
+    Desktop,
+    /// Terminal bell only
+    Bell,
+}
+
+/// Send `summary`/`body` as requested; a missing notifier is a warning, never an error.
+pub fn send(mode: NotifyMode, summary: &str, body: &str) {
+    if mode != NotifyMode::Bell {
+        if let Err(e) = desktop(summary, body) {
+            eprintln!("warning: --notify: desktop notification failed: {}", e);
+        }
+    }
+    if mode != NotifyMode::Desktop && std::io::stderr().is_terminal() {
+        let mut err = std::io::stderr();
+        let _ = err.write_all(b"\x07");
+        let _ = err.flush();
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn desktop(summary: &str, body: &str) -> std::io::Result<()> {
+    // AppleScript string literals escape only `"` and `\`
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let script = format!("display notification {} with title {}", quote(body), quote(summary));
+    run(Command::new("osascript").args(["-e", &script]))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn desktop(summary: &str, body: &str) -> std::io::Result<()> {
+    run(Command::new("notify-send").args(["--app-name=find-symlinks", summary, body]))
+}
+
+#[cfg(windows)]
+fn desktop(summary: &str, body: &str) -> std::io::Result<()> {
+    // A balloon tip from the tray: available on every supported Windows without extra modules
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; $n = New-Object System.Windows.Forms.NotifyIcon; \
+         $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+         $n.ShowBalloonTip(10000, {}, {}, 'Info'); Start-Sleep -Seconds 5; $n.Dispose()",
+        quote(summary), quote(body),
+    );
+    run(Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script]))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn desktop(_summary: &str, _body: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "no notifier on this platform"))
+}
+
+#[cfg(any(unix, windows))]
+fn run(cmd: &mut Command) -> std::io::Result<()> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status()
+        .map_err(|e| if e.kind() == std::io::ErrorKind::NotFound { std::io::Error::other(format!("{} not found", program)) } else { e })?;
+    if status.success() { Ok(()) } else { Err(std::io::Error::other(format!("{} exited with {}", program, status))) }
+}
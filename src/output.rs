@@ -0,0 +1,279 @@
+//! Structured (JSON, mtree) and shell-quoted rendering of result paths.
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use base64::Engine;
+use clap::ValueEnum;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// How paths that are not valid UTF-8 are written to JSON.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PathEncoding {
+    /// Plain strings; invalid sequences become U+FFFD (not round-trippable)
+    Lossy,
+    /// Objects with `path` plus a base64 `raw` field for non-UTF-8 paths
+    Base64,
+    /// Objects with `path` plus a byte-array `raw` field for non-UTF-8 paths
+    Bytes,
+}
+
+/// The platform bytes of a path: exact on Unix, UTF-8 (lossy) elsewhere.
+pub fn os_bytes(p: &Path) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Cow::Borrowed(p.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        match p.to_string_lossy() {
+            Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+            Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+        }
+    }
+}
+
+/// mtree (vis-style) form of a path: octal escapes for whitespace, `\`, `#`, `=`, and non-printable or non-ASCII bytes.
+pub fn mtree_escape(p: &Path) -> String {
+    let mut out = String::new();
+    for &b in os_bytes(p).iter() {
+        if b.is_ascii_graphic() && !matches!(b, b'\\' | b'#' | b'=') { out.push(b as char); }
+        else { out.push_str(&format!("\\{:03o}", b)); }
+    }
+    out
+}
+
+/// Shell syntax for `--quote`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Quote {
+    /// POSIX sh single quotes; `$'\xNN'` escapes for paths that are not valid UTF-8
+    Sh,
+    /// PowerShell single-quoted strings
+    Powershell,
+}
+
+/// A path as text, shell-quoted when `quote` is set and the path has anything but safe characters.
+pub fn display_path(p: &Path, quote: Option<Quote>) -> Cow<'_, str> {
+    let Some(quote) = quote else { return p.to_string_lossy() };
+    if quote == Quote::Sh && p.to_str().is_none() {
+        // Only ANSI-C quoting can spell arbitrary bytes (bash, zsh, ksh, POSIX.1-2024 sh)
+        let mut out = String::from("$'");
+        for &b in os_bytes(p).iter() {
+            if b.is_ascii_graphic() && !matches!(b, b'\\' | b'\'') { out.push(b as char); }
+            else { out.push_str(&format!("\\x{:02x}", b)); }
+        }
+        out.push('\'');
+        return Cow::Owned(out);
+    }
+    // PowerShell has no byte escapes: invalid sequences become U+FFFD
+    let s = p.to_string_lossy();
+    let safe = |c: char| c.is_ascii_alphanumeric() || match quote {
+        Quote::Sh => "_./-+,:@%=".contains(c),
+        Quote::Powershell => "_./\\:-".contains(c),
+    };
+    if !s.is_empty() && s.chars().all(safe) { return s; }
+    Cow::Owned(match quote {
+        Quote::Sh => format!("'{}'", s.replace('\'', "'\\''")),
+        Quote::Powershell => format!("'{}'", s.replace('\'', "''")),
+    })
+}
+
+/// Serializes a path according to a [`PathEncoding`].
+pub struct JsonPath<'a> {
+    pub path: &'a Path,
+    pub encoding: PathEncoding,
+}
+
+impl Serialize for JsonPath<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let lossy = self.path.to_string_lossy();
+        if self.encoding == PathEncoding::Lossy { return s.serialize_str(&lossy); }
+        let exact = self.path.to_str().is_some();
+        let mut m = s.serialize_map(Some(if exact { 1 } else { 2 }))?;
+        m.serialize_entry("path", &lossy)?;
+        if !exact {
+            let raw = os_bytes(self.path);
+            match self.encoding {
+                PathEncoding::Base64 => m.serialize_entry("raw", &base64::engine::general_purpose::STANDARD.encode(&raw))?,
+                _ => m.serialize_entry("raw", &raw[..])?,
+            }
+        }
+        m.end()
+    }
+}
+
+/// Where results are written: stdout, or `--output FILE` through a temporary file renamed over FILE by
+/// [`Sink::commit`]. A run that fails or is dropped before then leaves FILE as it was.
+pub struct Sink {
+    file: Option<PendingFile>,
+}
+
+struct PendingFile {
+    out: Mutex<BufWriter<File>>,
+    tmp: PathBuf,
+    dest: PathBuf,
+}
+
+/// A [`Write`] handle on a [`Sink`]; buffer it for many small writes.
+pub struct SinkWriter<'a>(&'a Sink);
+
+impl Sink {
+    pub fn new(path: Option<&Path>) -> io::Result<Sink> {
+        let Some(dest) = path else { return Ok(Sink { file: None }) };
+        let mut tmp = dest.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let out = Mutex::new(BufWriter::new(File::create(&tmp)?));
+        Ok(Sink { file: Some(PendingFile { out, tmp, dest: dest.to_path_buf() }) })
+    }
+
+    /// Write `bytes` in one piece, so concurrent writers do not interleave; stdout is flushed.
+    pub fn write_all(&self, bytes: &[u8]) -> io::Result<()> {
+        match &self.file {
+            Some(f) => f.out.lock().unwrap().write_all(bytes),
+            None => {
+                let mut out = io::stdout().lock();
+                out.write_all(bytes)?;
+                out.flush()
+            }
+        }
+    }
+
+    pub fn writer(&self) -> SinkWriter<'_> {
+        SinkWriter(self)
+    }
+
+    /// Flush, sync, and rename the file into place; nothing to do for stdout.
+    pub fn commit(mut self) -> io::Result<()> {
+        let Some(f) = self.file.take() else { return Ok(()) };
+        let file = f.out.into_inner().unwrap().into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&f.tmp, &f.dest)
+    }
+}
+
+impl Drop for Sink {
+    fn drop(&mut self) {
+        if let Some(f) = &self.file { let _ = fs::remove_file(&f.tmp); }
+    }
+}
+
+impl Write for SinkWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &self.0.file {
+            Some(f) => f.out.lock().unwrap().write(buf),
+            None => io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.0.file {
+            // The file is flushed once, by `commit`
+            Some(_) => Ok(()),
+            None => io::stdout().flush(),
+        }
+    }
+}
+
+/// `--stream-json`: a pretty-printed JSON array written element by element, so consumers see matches as they are confirmed.
+///
+/// With `key`, the array is the first member of an object whose other members [`JsonStream::finish`] writes.
+pub struct JsonStream<'a> {
+    sink: &'a Sink,
+    key: Option<&'static str>,
+    state: Mutex<StreamState>,
+}
+
+#[derive(Default)]
+struct StreamState {
+    items: usize,
+    /// The first write or serialization error; later elements are dropped
+    error: Option<io::Error>,
+}
+
+/// `v` pretty-printed, with every line after the first indented by `indent`.
+fn pretty(v: &serde_json::Value, indent: &str) -> String {
+    let text = serde_json::to_string_pretty(v).unwrap_or_default();
+    text.replace('\n', &format!("\n{}", indent))
+}
+
+impl<'a> JsonStream<'a> {
+    pub fn new(sink: &'a Sink, key: Option<&'static str>) -> JsonStream<'a> {
+        JsonStream { sink, key, state: Mutex::new(StreamState::default()) }
+    }
+
+    fn indent(&self) -> &'static str {
+        if self.key.is_some() { "    " } else { "  " }
+    }
+
+    /// Write one element (or record why it could not be built).
+    pub fn push(&self, item: serde_json::Result<serde_json::Value>) {
+        let mut st = self.state.lock().unwrap();
+        if st.error.is_some() { return; }
+        let item = match item {
+            Ok(v) => v,
+            Err(e) => { st.error = Some(e.into()); return; }
+        };
+        let open = match (st.items, self.key) {
+            (0, Some(k)) => format!("{{\n  {}: [\n", serde_json::Value::from(k)),
+            (0, None) => "[\n".to_string(),
+            _ => ",\n".to_string(),
+        };
+        let text = format!("{}{}{}", open, self.indent(), pretty(&item, self.indent()));
+        match self.sink.write_all(text.as_bytes()) {
+            Ok(()) => st.items += 1,
+            Err(e) => st.error = Some(e),
+        }
+    }
+
+    /// Close the array, then write the object's remaining members (`rest`, ignored without a key).
+    pub fn finish(self, rest: serde_json::Map<String, serde_json::Value>) -> io::Result<()> {
+        let st = self.state.into_inner().unwrap();
+        if let Some(e) = st.error { return Err(e); }
+        let mut text = match (st.items, self.key) {
+            (0, Some(k)) => format!("{{\n  {}: []", serde_json::Value::from(k)),
+            (0, None) => "[]".to_string(),
+            (_, Some(_)) => "\n  ]".to_string(),
+            (_, None) => "\n]".to_string(),
+        };
+        if self.key.is_some() {
+            for (k, v) in &rest {
+                text.push_str(&format!(",\n  {}: {}", serde_json::Value::from(k.as_str()), pretty(v, "  ")));
+            }
+            text.push_str("\n}");
+        }
+        text.push('\n');
+        self.sink.write_all(text.as_bytes())
+    }
+}
+
+/// NDJSON: one compact object per line, written and flushed as each match is confirmed.
+pub struct JsonLines<'a> {
+    sink: &'a Sink,
+    /// The first write or serialization error; later lines are dropped
+    error: Mutex<Option<io::Error>>,
+}
+
+impl<'a> JsonLines<'a> {
+    pub fn new(sink: &'a Sink) -> JsonLines<'a> {
+        JsonLines { sink, error: Mutex::new(None) }
+    }
+
+    pub fn push(&self, item: serde_json::Result<serde_json::Value>) {
+        let mut error = self.error.lock().unwrap();
+        if error.is_some() { return; }
+        let line = match item {
+            Ok(v) => format!("{}\n", v),
+            Err(e) => { *error = Some(e.into()); return; }
+        };
+        if let Err(e) = self.sink.write_all(line.as_bytes()) { *error = Some(e); }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.error.into_inner().unwrap().map_or(Ok(()), Err)
+    }
+}
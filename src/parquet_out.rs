@@ -0,0 +1,96 @@
+//! `--format parquet`: matches with metadata columns as a Parquet file (feature `parquet`).
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow_array::{ArrayRef, BooleanArray, Int64Array, RecordBatch, StringArray, UInt32Array, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::scan::{self, ScanResult, Target};
+
+/// Write one row per match to `out`; `root` and `depth` are relative to the `--root` each match was found under.
+pub fn write<W: Write + Send>(out: W, roots: &[PathBuf], targets: &[Target], result: &ScanResult) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("path", DataType::Utf8, false),
+        Field::new("target", DataType::Utf8, false),
+        Field::new("resolved", DataType::Utf8, true),
+        Field::new("link_text", DataType::Utf8, true),
+        Field::new("broken", DataType::Boolean, false),
+        Field::new("root", DataType::Utf8, true),
+        Field::new("depth", DataType::UInt64, false),
+        Field::new("size", DataType::UInt64, true),
+        Field::new("mtime", DataType::Int64, true),
+        Field::new("uid", DataType::UInt32, true),
+        Field::new("gid", DataType::UInt32, true),
+        Field::new("mode", DataType::UInt32, true),
+    ]));
+
+    let mut path = Vec::new();
+    let mut target = Vec::new();
+    let mut resolved = Vec::new();
+    let mut link_text = Vec::new();
+    let mut broken = Vec::new();
+    let mut root = Vec::new();
+    let mut depths = Vec::new();
+    let mut size = Vec::new();
+    let mut mtime = Vec::new();
+    let mut uid = Vec::new();
+    let mut gid = Vec::new();
+    let mut mode = Vec::new();
+    for m in &result.matches {
+        let t = &targets[m.target];
+        let meta = fs::symlink_metadata(&m.path).ok();
+        path.push(m.path.to_string_lossy().into_owned());
+        target.push(t.given.clone());
+        resolved.push(t.resolved.as_ref().map(|p| p.to_string_lossy().into_owned()));
+        link_text.push(fs::read_link(&m.path).ok().map(|p| p.to_string_lossy().into_owned()));
+        broken.push(m.broken);
+        let found = scan::locate(roots, &m.path);
+        root.push(found.map(|(r, _)| r.to_string_lossy().into_owned()));
+        depths.push(found.map_or_else(|| scan::depth(&m.path), |(_, d)| d) as u64);
+        size.push(meta.as_ref().map(|m| m.len()));
+        mtime.push(meta.as_ref().and_then(|m| m.modified().ok()).and_then(|t| {
+            t.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs() as i64)
+        }));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            uid.push(meta.as_ref().map(|m| m.uid()));
+            gid.push(meta.as_ref().map(|m| m.gid()));
+            mode.push(meta.as_ref().map(|m| m.mode()));
+        }
+        #[cfg(not(unix))]
+        {
+            uid.push(None);
+            gid.push(None);
+            mode.push(None);
+        }
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(path)),
+        Arc::new(StringArray::from(target)),
+        Arc::new(StringArray::from(resolved)),
+        Arc::new(StringArray::from(link_text)),
+        Arc::new(BooleanArray::from(broken)),
+        Arc::new(StringArray::from(root)),
+        Arc::new(UInt64Array::from(depths)),
+        Arc::new(UInt64Array::from(size)),
+        Arc::new(Int64Array::from(mtime)),
+        Arc::new(UInt32Array::from(uid)),
+        Arc::new(UInt32Array::from(gid)),
+        Arc::new(UInt32Array::from(mode)),
+    ];
+    let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+    let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let mut writer = ArrowWriter::try_new(out, schema, Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
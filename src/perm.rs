@@ -0,0 +1,99 @@
+//! `--perm MODE`: permission tests as in GNU find's `-perm`.
+//!
+//! `MODE` is octal (`644`, `4755`) or symbolic (`u+w,o=r`, applied to an empty mode). A bare mode
+//! must match exactly; `-MODE` needs all of its bits set and `/MODE` any of them.
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Test {
+    Exact,
+    All,
+    Any,
+}
+
+/// One parsed `--perm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Perm {
+    test: Test,
+    bits: u32,
+}
+
+/// Which inode `--perm` looks at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PermOf {
+    /// What the link resolves to; a dangling link has no mode and never passes
+    Target,
+    /// The symlink itself (always 777 on Linux; meaningful on macOS and the BSDs)
+    Link,
+}
+
+const MASK: u32 = 0o7777;
+
+impl Perm {
+    /// Parse a mode; usable as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Perm, String> {
+        let (test, mode) = match s.as_bytes().first() {
+            Some(b'-') => (Test::All, &s[1..]),
+            Some(b'/') => (Test::Any, &s[1..]),
+            _ => (Test::Exact, s),
+        };
+        if mode.is_empty() { return Err("empty mode".into()); }
+        let bits = if mode.bytes().all(|b| b.is_ascii_digit()) {
+            u32::from_str_radix(mode, 8).ok().filter(|b| *b <= MASK).ok_or_else(|| format!("invalid octal mode {}", mode))?
+        } else {
+            symbolic(mode)?
+        };
+        Ok(Perm { test, bits })
+    }
+
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & MASK;
+        match self.test {
+            Test::Exact => mode == self.bits,
+            Test::All => mode & self.bits == self.bits,
+            // `/000` matches everything, as in find
+            Test::Any => self.bits == 0 || mode & self.bits != 0,
+        }
+    }
+}
+
+/// `u+w,g=rx,o-r`: clauses applied in order to mode 0.
+fn symbolic(s: &str) -> Result<u32, String> {
+    let mut mode = 0;
+    for clause in s.split(',') {
+        let op_at = clause.find(['+', '-', '=']).ok_or_else(|| format!("invalid mode clause {:?}: no +, -, or =", clause))?;
+        let (who, rest) = clause.split_at(op_at);
+        let mut who_bits = 0;
+        for c in who.chars() {
+            who_bits |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o0007,
+                'a' => 0o6777,
+                _ => return Err(format!("invalid mode clause {:?}: unknown class {:?}", clause, c)),
+            };
+        }
+        if who_bits == 0 { who_bits = 0o6777; }
+        let (op, perms) = rest.split_at(1);
+        let mut perm_bits = 0;
+        for c in perms.chars() {
+            perm_bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => return Err(format!("invalid mode clause {:?}: unknown permission {:?}", clause, c)),
+            };
+        }
+        // The sticky bit belongs to no class
+        let bits = perm_bits & (who_bits | if perms.contains('t') { 0o1000 } else { 0 });
+        match op {
+            "+" => mode |= bits,
+            "-" => mode &= !bits,
+            _ => mode = (mode & !who_bits) | bits,
+        }
+    }
+    Ok(mode)
+}
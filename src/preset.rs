@@ -0,0 +1,318 @@
+//! `--preset`: scan well-known directory lists instead of the working directory.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use console::style;
+use serde::Serialize;
+
+use crate::cli::ScanOpts;
+use crate::output::{JsonPath, PathEncoding};
+use crate::scan::{self, Target};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Every directory in $PATH, in order, with which entry of each command wins
+    Path,
+    /// Shared-library directories (ld.so.conf, ld.so.cache, system defaults): soname link chains to a library
+    Ldso,
+    /// Time Machine backups on mounted disks and local APFS snapshots: when links to the target appeared or changed
+    Timemachine,
+}
+
+/// A symlink in a $PATH directory that resolves to a target.
+#[derive(Serialize)]
+struct PathHit<'a> {
+    command: String,
+    path: JsonPath<'a>,
+    /// 1-based position of the directory in $PATH
+    path_index: usize,
+    target: &'a str,
+    broken: bool,
+    /// Whether this is what the command name runs (the first executable entry in $PATH)
+    wins: bool,
+    shadowed_by: Option<JsonPath<'a>>,
+}
+
+#[cfg(unix)]
+fn is_executable(p: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(p).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(p: &Path) -> bool {
+    fs::metadata(p).is_ok_and(|m| m.is_file())
+}
+
+/// $PATH entries in order, skipping empty, missing, and repeated directories.
+fn path_dirs() -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    std::env::var_os("PATH")
+        .map(|p| std::env::split_paths(&p).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|d| !d.as_os_str().is_empty() && d.is_dir())
+        .filter(|d| seen.insert(fs::canonicalize(d).unwrap_or_else(|_| d.clone())))
+        .collect()
+}
+
+pub fn run(preset: Preset, opts: &ScanOpts, targets: &[Target], json: bool, encoding: PathEncoding) -> Result<()> {
+    match preset {
+        Preset::Path => run_path(opts, targets, json, encoding),
+        Preset::Ldso => run_ldso(opts, targets, json),
+        Preset::Timemachine => crate::timemachine::run(opts, targets, json, encoding),
+    }
+}
+
+fn run_path(opts: &ScanOpts, targets: &[Target], json: bool, encoding: PathEncoding) -> Result<()> {
+    let dirs = path_dirs();
+    let has_patterns = scan::has_patterns(targets);
+    let ids = scan::target_ids(targets);
+    // (dir index, link path, target, broken), in $PATH order then by name
+    let mut found = Vec::new();
+    for (i, d) in dirs.iter().enumerate() {
+        let Ok(rd) = fs::read_dir(d) else { continue };
+        let mut links: Vec<PathBuf> = rd.flatten()
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_symlink()))
+            .map(|e| e.path())
+            .collect();
+        links.sort();
+        for p in links {
+            if let Some((t, broken)) = scan::match_symlink(opts, &p, targets, &ids, has_patterns) { found.push((i, p, t, broken)); }
+        }
+    }
+    // The entry a command name runs: the first executable with that name in $PATH
+    let winner = |name: &std::ffi::OsStr| dirs.iter().map(|d| d.join(name)).find(|p| is_executable(p));
+    let winners: Vec<Option<PathBuf>> = found.iter().map(|(_, p, _, _)| winner(p.file_name().unwrap_or_default())).collect();
+    let hits: Vec<PathHit> = found.iter().zip(&winners)
+        .map(|((i, p, t, broken), w)| PathHit {
+            command: p.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            path: JsonPath { path: p, encoding },
+            path_index: i + 1,
+            target: &targets[*t].given,
+            broken: *broken,
+            wins: w.as_deref() == Some(p.as_path()),
+            shadowed_by: w.as_deref().filter(|w| *w != p.as_path()).map(|w| JsonPath { path: w, encoding }),
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&hits)?);
+        return Ok(());
+    }
+    if hits.is_empty() {
+        println!("{}", style(format!("No symlinks in $PATH ({} directories) resolve to the target.", dirs.len())).yellow());
+        return Ok(());
+    }
+    let cmd_w = hits.iter().map(|h| h.command.len()).max().unwrap_or(0);
+    let path_w = hits.iter().map(|h| h.path.path.display().to_string().len()).max().unwrap_or(0);
+    for (h, w) in hits.iter().zip(&winners) {
+        let status = match (h.broken, h.wins, w) {
+            (true, _, _) => style("broken".to_string()).red(),
+            (_, true, _) => style("runs".to_string()).green().bold(),
+            (_, _, Some(w)) => style(format!("shadowed by {}", w.display())).dim(),
+            _ => style("not executable".to_string()).yellow(),
+        };
+        println!(
+            "{} {:<cmd_w$}  {}  {}",
+            style(format!("{:>2}.", h.path_index)).dim(),
+            h.command,
+            style(format!("{:<path_w$}", h.path.path.display())).white().bold(),
+            status,
+        );
+    }
+    let breaking = hits.iter().filter(|h| h.wins).count();
+    println!();
+    println!("{} {}", style("Commands that break if the target goes away:").dim(), style(breaking).bold().red());
+    Ok(())
+}
+
+/// Directories searched by the dynamic linker even without configuration.
+const LDSO_DEFAULT_DIRS: &[&str] = &["/lib", "/usr/lib", "/lib64", "/usr/lib64"];
+
+/// `soname => path` pairs from `/etc/ld.so.cache` (glibc "new" format, which is all glibc 2.32+ writes).
+fn ldso_cache(path: &Path) -> Vec<(String, PathBuf)> {
+    const MAGIC: &[u8] = b"glibc-ld.so.cache1.1";
+    let Ok(data) = fs::read(path) else { return Vec::new() };
+    // An old-format cache may come first; the new one starts at its magic
+    let Some(base) = data.windows(MAGIC.len()).position(|w| w == MAGIC) else { return Vec::new() };
+    let buf = &data[base..];
+    let u32_at = |off: usize| buf.get(off..off + 4).map(|b| u32::from_ne_bytes(b.try_into().unwrap()) as usize);
+    let cstr_at = |off: usize| {
+        let tail = buf.get(off..)?;
+        let end = tail.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&tail[..end]).into_owned())
+    };
+    let Some(n) = u32_at(MAGIC.len()) else { return Vec::new() };
+    // Header: magic, nlibs, len_strings, flags + padding, extension offset, 3 unused words
+    let (header, entry) = (MAGIC.len() + 28, 24);
+    (0..n)
+        .filter_map(|i| {
+            let e = header + i * entry;
+            Some((cstr_at(u32_at(e + 4)?)?, PathBuf::from(cstr_at(u32_at(e + 8)?)?)))
+        })
+        .collect()
+}
+
+/// Directories named in an ld.so.conf file, following `include` globs.
+fn ldso_conf_dirs(path: &Path, depth: usize, out: &mut Vec<PathBuf>) {
+    let Ok(text) = fs::read_to_string(path) else { return };
+    for line in text.lines().map(|l| l.split('#').next().unwrap_or("").trim()).filter(|l| !l.is_empty()) {
+        if let Some(pattern) = line.strip_prefix("include").map(str::trim) {
+            if depth > 8 { continue; }
+            let pattern = if Path::new(pattern).is_absolute() { PathBuf::from(pattern) } else { path.parent().unwrap_or(Path::new("/")).join(pattern) };
+            let (Some(dir), Some(name)) = (pattern.parent(), pattern.file_name()) else { continue };
+            let Ok(glob) = globset::Glob::new(&name.to_string_lossy()) else { continue };
+            let glob = glob.compile_matcher();
+            let Ok(rd) = fs::read_dir(dir) else { continue };
+            let mut files: Vec<PathBuf> = rd.flatten().map(|e| e.path()).filter(|p| p.file_name().is_some_and(|n| glob.is_match(n))).collect();
+            files.sort();
+            for f in files { ldso_conf_dirs(&f, depth + 1, out); }
+        } else {
+            out.push(PathBuf::from(line));
+        }
+    }
+}
+
+/// How a soname chain ends.
+#[derive(Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ChainStatus {
+    /// Reaches the target
+    Ok,
+    /// A hop names something that does not exist
+    Broken,
+    /// Reaches a different file (another version, usually)
+    Elsewhere,
+}
+
+#[derive(Serialize)]
+struct Chain {
+    path: PathBuf,
+    /// Link text of each hop, in order
+    hops: Vec<PathBuf>,
+    status: ChainStatus,
+    /// 1-based hop whose destination is missing
+    broken_at: Option<usize>,
+    /// Final file when the chain resolves
+    resolved: Option<PathBuf>,
+    /// What ld.so.cache says for this file name: `cached` (this path), `stale` (another path), or `absent`
+    cache: Option<&'static str>,
+}
+
+/// Follow `p` one hop at a time.
+fn follow_chain(p: &Path, target: &Path) -> (Vec<PathBuf>, ChainStatus, Option<usize>, Option<PathBuf>) {
+    let mut hops = Vec::new();
+    let mut cur = p.to_path_buf();
+    while hops.len() < 40 {
+        let Ok(text) = fs::read_link(&cur) else { break };
+        cur = cur.parent().unwrap_or(Path::new("/")).join(&text);
+        hops.push(text);
+        if fs::symlink_metadata(&cur).is_err() {
+            let at = hops.len();
+            return (hops, ChainStatus::Broken, Some(at), None);
+        }
+    }
+    match fs::canonicalize(&cur) {
+        Ok(r) if r == target => (hops, ChainStatus::Ok, None, Some(r)),
+        Ok(r) => (hops, ChainStatus::Elsewhere, None, Some(r)),
+        Err(_) => { let at = hops.len(); (hops, ChainStatus::Broken, Some(at), None) }
+    }
+}
+
+fn run_ldso(opts: &ScanOpts, targets: &[Target], json: bool) -> Result<()> {
+    let [target] = targets else { anyhow::bail!("--preset ldso takes a single library path as target") };
+    let Some(lib) = target.resolved.as_deref() else { anyhow::bail!("--preset ldso needs an existing library, {} was not found", target.given) };
+    let name = lib.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    // `libfoo.so.1.2.3` -> `libfoo.so`: every link in the family starts with it
+    let stem = name.find(".so").map_or(name.as_str(), |i| &name[..i + 3]).to_string();
+
+    let cache = ldso_cache(Path::new("/etc/ld.so.cache"));
+    let mut dir_list = Vec::new();
+    ldso_conf_dirs(Path::new("/etc/ld.so.conf"), 0, &mut dir_list);
+    dir_list.extend(cache.iter().filter_map(|(_, p)| p.parent().map(Path::to_path_buf)));
+    dir_list.extend(LDSO_DEFAULT_DIRS.iter().map(PathBuf::from));
+    dir_list.extend(lib.parent().map(Path::to_path_buf));
+    let mut seen = HashSet::new();
+    let dirs: Vec<PathBuf> = dir_list.into_iter()
+        .filter(|d| d.is_dir() && seen.insert(fs::canonicalize(d).unwrap_or_else(|_| d.clone())))
+        .collect();
+
+    let has_patterns = scan::has_patterns(targets);
+    let ids = scan::target_ids(targets);
+    let mut chains = Vec::new();
+    for d in &dirs {
+        let Ok(rd) = fs::read_dir(d) else { continue };
+        let mut links: Vec<PathBuf> = rd.flatten()
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_symlink()))
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name().is_some_and(|n| n.to_string_lossy().starts_with(&stem))
+                    || scan::match_symlink(opts, p, targets, &ids, has_patterns).is_some()
+            })
+            .collect();
+        links.sort();
+        for p in links {
+            let (hops, status, broken_at, resolved) = follow_chain(&p, lib);
+            let file_name = p.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let cached: Vec<&PathBuf> = cache.iter().filter(|(k, _)| *k == file_name).map(|(_, v)| v).collect();
+            // Development links (`libfoo.so`) are not sonames and never cached
+            let cache_state = if file_name == stem { None }
+                else if cached.iter().any(|v| **v == p) { Some("cached") }
+                else if cached.is_empty() { Some("absent") }
+                else { Some("stale") };
+            chains.push(Chain { path: p, hops, status, broken_at, resolved, cache: cache_state });
+        }
+    }
+    // Cache entries for the family that no longer lead to the target
+    let stale_cache: Vec<(&String, &PathBuf)> = cache.iter()
+        .filter(|(k, _)| k.starts_with(&stem))
+        .filter(|(_, v)| fs::canonicalize(v).ok().as_deref() != Some(lib))
+        .map(|(k, v)| (k, v))
+        .collect();
+
+    if json {
+        let doc = serde_json::json!({
+            "target": lib,
+            "directories": dirs,
+            "chains": chains,
+            "stale_cache": stale_cache.iter().map(|(k, v)| serde_json::json!({ "soname": k, "path": v })).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&doc)?);
+        return Ok(());
+    }
+    if chains.is_empty() {
+        println!("{}", style(format!("No {}* links in {} library directories.", stem, dirs.len())).yellow());
+    }
+    for c in &chains {
+        let mut line = style(c.path.display()).white().bold().to_string();
+        for (i, h) in c.hops.iter().enumerate() {
+            let hop = if c.broken_at == Some(i + 1) { style(h.display()).red().bold() } else { style(h.display()).cyan() };
+            line.push_str(&format!(" {} {}", style("->").dim(), hop));
+        }
+        let status = match c.status {
+            ChainStatus::Ok => style("ok".to_string()).green(),
+            ChainStatus::Broken => style(format!("broken at hop {}", c.broken_at.unwrap_or(0))).red().bold(),
+            ChainStatus::Elsewhere => style(format!("resolves to {}", c.resolved.as_deref().unwrap_or(Path::new("?")).display())).yellow(),
+        };
+        let cache = match c.cache {
+            Some("stale") => style(" [ld.so.cache points elsewhere]".to_string()).yellow(),
+            Some("absent") if c.status == ChainStatus::Ok => style(" [not in ld.so.cache; run ldconfig]".to_string()).yellow(),
+            Some("cached") => style(" [cached]".to_string()).dim(),
+            _ => style(String::new()),
+        };
+        println!("{}  {}{}", line, status, cache);
+    }
+    for (k, v) in &stale_cache {
+        println!("{} {} => {} {}", style("ld.so.cache:").dim(), k, v.display(), style("(does not lead to the target)").yellow());
+    }
+    let broken = chains.iter().filter(|c| c.status == ChainStatus::Broken).count();
+    println!();
+    println!("{} {}", style("Chains:").dim(), style(chains.len()).bold().cyan());
+    println!("{} {}", style("Broken mid-way:").dim(), if broken > 0 { style(broken).bold().red() } else { style(broken).dim() });
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex, OnceLock, atomic::{AtomicBool, AtomicUsize, Ordering}};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use num_format::{Locale, ToFormattedString};
+
+use crate::config::ProgressConfig;
+use crate::hooks::{Hooks, Phase};
+use crate::scan::Match;
+use crate::term::Glyphs;
+
+/// How scan progress is rendered on stderr.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    /// Animated bars on a terminal, plain lines on dumb terminals, nothing otherwise
+    Auto,
+    /// Animated spinner and progress bar (indicatif)
+    Bar,
+    /// One throttled status line every interval; no cursor movement or ANSI rewriting
+    Plain,
+    /// Like `bar`, after a counting pass so the walk shows a true percentage
+    Exact,
+    /// No progress output
+    None,
+}
+
+impl ProgressMode {
+    /// Collapse `Auto` into a concrete mode for the current stderr.
+    pub fn resolve(self) -> ProgressMode {
+        match self {
+            ProgressMode::Auto => {
+                if !std::io::stderr().is_terminal() { ProgressMode::None }
+                else if std::env::var("TERM").is_ok_and(|t| t == "dumb") { ProgressMode::Plain }
+                else { ProgressMode::Bar }
+            }
+            m => m,
+        }
+    }
+}
+
+/// Counters shared between the scan and the plain reporter thread.
+#[derive(Default)]
+struct Counters {
+    resolving: AtomicBool,
+    walked: AtomicUsize,
+    symlinks: AtomicUsize,
+    resolved: AtomicUsize,
+    total: AtomicUsize,
+    matched: AtomicUsize,
+    /// Expected walk entries from `--estimate` or `--progress exact` (0: unknown)
+    estimate: AtomicUsize,
+    /// `estimate` was counted, not predicted
+    exact: AtomicBool,
+}
+
+/// The CLI's scan progress: indicatif bars or plain status lines on stderr, driven by `Hooks`.
+pub struct Progress {
+    counters: Arc<Counters>,
+    mp: Option<MultiProgress>,
+    walk_pb: Option<ProgressBar>,
+    /// Set once, before the resolve starts, so workers tick it without a lock
+    resolve_pb: OnceLock<ProgressBar>,
+    resolve_style: Option<ProgressStyle>,
+    estimate_style: Option<ProgressStyle>,
+    stop: Arc<AtomicBool>,
+    reporter: Mutex<Option<JoinHandle<()>>>,
+    ellipsis: &'static str,
+}
+
+impl Progress {
+    pub fn new(mode: ProgressMode, interval: Duration, cfg: &ProgressConfig, glyphs: &Glyphs) -> Result<Progress> {
+        let mode = mode.resolve();
+        let counters = Arc::new(Counters::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut mp = None;
+        let mut walk_pb = None;
+        let mut resolve_style = None;
+        let mut estimate_style = None;
+        let mut reporter = None;
+        match mode {
+            ProgressMode::Bar | ProgressMode::Exact => {
+                let mut walk_style = ProgressStyle::with_template(&cfg.walk_template)
+                    .context("invalid [progress] walk_template")?;
+                if let Some(frames) = glyphs.spinner { walk_style = walk_style.tick_chars(frames); }
+                resolve_style = Some(
+                    ProgressStyle::with_template(&cfg.resolve_template)
+                        .context("invalid [progress] resolve_template")?
+                        .progress_chars(&cfg.progress_chars),
+                );
+                estimate_style = Some(
+                    ProgressStyle::with_template(&cfg.estimate_template)
+                        .context("invalid [progress] estimate_template")?
+                        .progress_chars(&cfg.progress_chars),
+                );
+                let m = MultiProgress::new();
+                let pb = m.add(ProgressBar::new_spinner());
+                pb.set_style(walk_style);
+                pb.set_message(format!("Walking filesystem{}", glyphs.ellipsis));
+                pb.enable_steady_tick(Duration::from_millis(cfg.tick_ms.max(1)));
+                walk_pb = Some(pb);
+                mp = Some(m);
+            }
+            ProgressMode::Plain => {
+                let counters = Arc::clone(&counters);
+                let stop = Arc::clone(&stop);
+                reporter = Some(std::thread::spawn(move || plain_reporter(&counters, &stop, interval)));
+            }
+            _ => {}
+        }
+        Ok(Progress {
+            counters, mp, walk_pb, resolve_pb: OnceLock::new(), resolve_style, estimate_style, stop,
+            reporter: Mutex::new(reporter), ellipsis: glyphs.ellipsis,
+        })
+    }
+
+    /// Switch from the walk phase to resolving `total` symlinks.
+    fn begin_resolve(&self, total: usize) {
+        if let Some(pb) = &self.walk_pb { pb.finish_and_clear(); }
+        self.counters.total.store(total, Ordering::Relaxed);
+        self.counters.resolving.store(true, Ordering::Relaxed);
+        if let (Some(mp), Some(style)) = (&self.mp, &self.resolve_style) {
+            let pb = mp.add(ProgressBar::new(total as u64));
+            pb.set_style(style.clone());
+            pb.set_message("Checking symlinks");
+            let _ = self.resolve_pb.set(pb);
+        }
+    }
+
+    /// Tear down bars and stop the plain reporter.
+    fn finish(&self) {
+        if let Some(pb) = &self.walk_pb { pb.finish_and_clear(); }
+        if let Some(pb) = self.resolve_pb.get() { pb.finish_and_clear(); }
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.reporter.lock().unwrap().take() {
+            h.thread().unpark();
+            let _ = h.join();
+        }
+    }
+
+    /// Print a result line without corrupting an active progress bar.
+    pub fn println(&self, line: &str) {
+        match (self.resolve_pb.get(), &self.mp) {
+            (Some(pb), _) => pb.println(line),
+            // `--max-count` matches arrive during the walk
+            (None, Some(mp)) => { let _ = mp.println(line); }
+            (None, None) => println!("{}", line),
+        }
+    }
+
+    /// Run `f` (a write to stdout) with the bars hidden; they are redrawn below its output.
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match &self.mp {
+            Some(m) => m.suspend(f),
+            None => f(),
+        }
+    }
+}
+
+impl Hooks for Progress {
+    fn phase(&self, phase: Phase) {
+        match phase {
+            Phase::Counting => {
+                if let Some(pb) = &self.walk_pb { pb.set_message(format!("Counting entries{}", self.ellipsis)); }
+            }
+            Phase::Walking => {
+                if let Some(pb) = &self.walk_pb { pb.set_message(format!("Walking filesystem{}", self.ellipsis)); }
+            }
+            Phase::Resolving { total } => self.begin_resolve(total),
+            Phase::Finished => self.finish(),
+        }
+    }
+
+    /// Turn the walk spinner into a bar out of `entries` entries: predicted, or counted when `exact`.
+    fn estimate(&self, entries: u64, exact: bool) {
+        self.counters.estimate.store(entries as usize, Ordering::Relaxed);
+        self.counters.exact.store(exact, Ordering::Relaxed);
+        let style = if exact { &self.resolve_style } else { &self.estimate_style };
+        if let (Some(pb), Some(style)) = (&self.walk_pb, style) {
+            pb.set_length(entries.max(1));
+            pb.set_style(style.clone());
+        }
+    }
+
+    /// Directories occasionally update the bar's `{prefix}`.
+    fn walked(&self, path: &Path, is_dir: bool, is_symlink: bool) {
+        let n = self.counters.walked.fetch_add(1, Ordering::Relaxed);
+        if is_symlink { self.counters.symlinks.fetch_add(1, Ordering::Relaxed); }
+        if let Some(pb) = &self.walk_pb {
+            pb.inc(1);
+            if is_dir && n.is_multiple_of(64) {
+                pb.set_prefix(path.display().to_string());
+                // Underestimated: keep the bar short of full until the walk ends
+                if pb.length().is_some_and(|len| n as u64 >= len) { pb.set_length(n as u64 + n as u64 / 10); }
+            }
+        }
+    }
+
+    fn resolved(&self) {
+        self.counters.resolved.fetch_add(1, Ordering::Relaxed);
+        if let Some(pb) = self.resolve_pb.get() { pb.inc(1); }
+    }
+
+    /// Counted for plain status lines; the match itself is printed by the caller.
+    fn matched(&self, _m: &Match) {
+        self.counters.matched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        Progress::suspend(self, f)
+    }
+}
+
+fn plain_reporter(c: &Counters, stop: &AtomicBool, interval: Duration) {
+    let start = Instant::now();
+    let mut next = start + interval;
+    while !stop.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now < next { std::thread::park_timeout(next - now); continue; }
+        next += interval;
+        let secs = start.elapsed().as_secs();
+        let line = if c.resolving.load(Ordering::Relaxed) {
+            format!(
+                "find-symlinks: checking symlinks {}/{}, {} matches ({}s)",
+                c.resolved.load(Ordering::Relaxed).to_formatted_string(&Locale::en),
+                c.total.load(Ordering::Relaxed).to_formatted_string(&Locale::en),
+                c.matched.load(Ordering::Relaxed).to_formatted_string(&Locale::en),
+                secs,
+            )
+        } else {
+            let walked = c.walked.load(Ordering::Relaxed);
+            let estimate = c.estimate.load(Ordering::Relaxed);
+            let of = if estimate > 0 {
+                let approx = if c.exact.load(Ordering::Relaxed) { "" } else { "~" };
+                format!(" of {}{} ({}%)", approx, estimate.to_formatted_string(&Locale::en), (walked * 100 / estimate).min(99))
+            } else {
+                String::new()
+            };
+            format!(
+                "find-symlinks: walking, {}{} entries, {} symlinks ({}s)",
+                walked.to_formatted_string(&Locale::en),
+                of,
+                c.symlinks.load(Ordering::Relaxed).to_formatted_string(&Locale::en),
+                secs,
+            )
+        };
+        let mut err = std::io::stderr().lock();
+        let _ = writeln!(err, "{}", line);
+    }
+}
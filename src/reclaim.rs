@@ -0,0 +1,113 @@
+//! `--reclaim`: disk space a cleanup of the reported links (and, optionally, their orphaned targets) would free.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::scan::{ScanResult, Target};
+
+#[derive(Serialize, Default)]
+pub struct Reclaim {
+    pub links: usize,
+    /// Allocated size of the matched links themselves (0 for short links stored in the inode)
+    pub link_bytes: u64,
+    pub broken: usize,
+    pub broken_bytes: u64,
+    /// Only with `--reclaim-orphans`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orphans: Option<Vec<Orphan>>,
+}
+
+/// A target nothing but the reported links refers to.
+#[derive(Serialize)]
+pub struct Orphan {
+    pub target: String,
+    pub bytes: u64,
+}
+
+impl Reclaim {
+    pub fn orphan_bytes(&self) -> u64 {
+        self.orphans.iter().flatten().map(|o| o.bytes).sum()
+    }
+}
+
+/// Bytes allocated on disk (`st_blocks`), or the apparent size where that is unavailable.
+fn allocated(m: &fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    { use std::os::unix::fs::MetadataExt; m.blocks() * 512 }
+    #[cfg(not(unix))]
+    { m.len() }
+}
+
+#[cfg(unix)]
+fn inode(m: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_m: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// A regular file with more than one hard link.
+#[cfg(unix)]
+fn other_names(m: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    m.is_file() && m.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn other_names(_m: &fs::Metadata) -> bool {
+    false
+}
+
+/// Like `du -s`: every file under `p` once (hard links counted once), links not followed.
+fn tree_bytes(p: &Path, seen: &mut HashSet<(u64, u64)>) -> u64 {
+    let Ok(m) = fs::symlink_metadata(p) else { return 0 };
+    if inode(&m).is_some_and(|i| !seen.insert(i)) { return 0; }
+    let mut n = allocated(&m);
+    if m.is_dir() {
+        for e in fs::read_dir(p).into_iter().flatten().flatten() { n += tree_bytes(&e.path(), seen); }
+    }
+    n
+}
+
+/// Tally the matches; with `orphans`, also size each target no unreported link (into it or below it)
+/// refers to. `result.links` must be recorded for that.
+pub fn estimate(targets: &[Target], result: &ScanResult, orphans: bool) -> Reclaim {
+    let mut r = Reclaim::default();
+    for m in &result.matches {
+        let bytes = fs::symlink_metadata(&m.path).map_or(0, |md| allocated(&md));
+        r.links += 1;
+        r.link_bytes += bytes;
+        if m.broken { r.broken += 1; r.broken_bytes += bytes; }
+    }
+    if !orphans { return r; }
+
+    let reported: HashSet<&Path> = result.matches.paths().collect();
+    let mut seen = HashSet::new();
+    r.orphans = Some(targets.iter()
+        .filter_map(|t| {
+            let dest = t.resolved.as_deref()?;
+            let referenced = result.links.iter()
+                .any(|l| !reported.contains(l.path.as_path()) && l.resolved.as_deref().is_some_and(|r| r.starts_with(dest)));
+            if referenced { return None; }
+            // A file with other hard links keeps its data after this name is removed
+            if fs::symlink_metadata(dest).ok().is_none_or(|m| other_names(&m)) { return None; }
+            Some(Orphan { target: t.given.clone(), bytes: tree_bytes(dest, &mut seen) })
+        })
+        .collect());
+    r
+}
+
+/// `1536` -> `1.5 KiB`.
+pub fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut v = n as f64;
+    let mut unit = 0;
+    while v >= 1024.0 && unit + 1 < UNITS.len() { v /= 1024.0; unit += 1; }
+    if unit == 0 { format!("{} B", n) } else { format!("{:.1} {}", v, UNITS[unit]) }
+}
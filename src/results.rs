@@ -0,0 +1,237 @@
+//! The matches of a scan, on the heap or (`--mmap-results`) in a memory-mapped temporary file.
+//!
+//! Mapped, each match is a record appended to the file (path length, path bytes, target index,
+//! broken flag) and only its offset stays on the heap. The kernel writes the pages back and drops
+//! them under memory pressure, so an inventory of tens of millions of links is not held in RAM.
+
+use std::borrow::Cow;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use memmap2::MmapMut;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::scan::Match;
+
+/// First size of a mapped file; it doubles whenever a record would not fit.
+const INITIAL_SIZE: u64 = 64 << 20;
+
+#[derive(Debug)]
+pub struct Matches(Store);
+
+#[derive(Debug)]
+enum Store {
+    Heap(Vec<Match>),
+    Mapped(Mapped),
+}
+
+#[derive(Debug)]
+struct Mapped {
+    file: File,
+    map: MmapMut,
+    /// Bytes of records written so far
+    end: usize,
+    /// Where each record starts, in list order
+    offsets: Vec<usize>,
+}
+
+impl Default for Matches {
+    fn default() -> Matches {
+        Matches(Store::Heap(Vec::new()))
+    }
+}
+
+impl From<Vec<Match>> for Matches {
+    fn from(v: Vec<Match>) -> Matches {
+        Matches(Store::Heap(v))
+    }
+}
+
+impl Matches {
+    /// An empty list backed by a new file in `dir`, unlinked as soon as it is open (Unix only, where
+    /// an open file outlives its name).
+    pub fn mapped(dir: &Path) -> Result<Matches> {
+        static SEQ: AtomicUsize = AtomicUsize::new(0);
+        let path = dir.join(format!(".find-symlinks-results-{}-{}", std::process::id(), SEQ.fetch_add(1, Ordering::Relaxed)));
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)
+            .with_context(|| format!("create results file {}", path.display()))?;
+        let _ = std::fs::remove_file(&path);
+        reserve(&file, INITIAL_SIZE).with_context(|| format!("size results file {}", path.display()))?;
+        // SAFETY: the file was just created by this process and, on Unix, no longer has a name;
+        // nothing else maps or truncates it while the mapping lives.
+        let map = unsafe { MmapMut::map_mut(&file) }.with_context(|| format!("map results file {}", path.display()))?;
+        Ok(Matches(Store::Mapped(Mapped { file, map, end: 0, offsets: Vec::new() })))
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Store::Heap(v) => v.len(),
+            Store::Mapped(m) => m.offsets.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append a match; an error if a mapped file cannot grow (the disk is full).
+    pub fn push(&mut self, m: Match) -> io::Result<()> {
+        match &mut self.0 {
+            Store::Heap(v) => { v.push(m); Ok(()) }
+            Store::Mapped(mapped) => mapped.push(&m),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { matches: self, next: 0 }
+    }
+
+    fn get(&self, i: usize) -> Cow<'_, Match> {
+        match &self.0 {
+            Store::Heap(v) => Cow::Borrowed(&v[i]),
+            Store::Mapped(m) => Cow::Owned(m.decode(m.offsets[i])),
+        }
+    }
+
+    pub fn sort_by_path(&mut self) {
+        match &mut self.0 {
+            Store::Heap(v) => v.sort_by(|a, b| a.path.cmp(&b.path)),
+            Store::Mapped(Mapped { map, offsets, .. }) => offsets.sort_by(|&a, &b| path_at(map, a).cmp(path_at(map, b))),
+        }
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&Match) -> bool) {
+        match &mut self.0 {
+            Store::Heap(v) => v.retain(keep),
+            Store::Mapped(m) => {
+                let Mapped { map, offsets, .. } = m;
+                offsets.retain(|&o| keep(&decode(map, o)));
+            }
+        }
+    }
+
+    /// Each match's path, borrowed from the list or the mapping, in list order.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        let (heap, mapped) = match &self.0 {
+            Store::Heap(v) => (Some(v.iter().map(|m| m.path.as_path())), None),
+            Store::Mapped(m) => (None, Some(m.offsets.iter().map(|&o| path_at(&m.map, o)))),
+        };
+        heap.into_iter().flatten().chain(mapped.into_iter().flatten())
+    }
+}
+
+impl Mapped {
+    fn push(&mut self, m: &Match) -> io::Result<()> {
+        let path = crate::output::os_bytes(&m.path);
+        let len = u32::try_from(path.len()).map_err(|_| io::Error::other("path too long"))?;
+        let size = 4 + path.len() + 8 + 1;
+        if self.end + size > self.map.len() {
+            let want = ((self.end + size) as u64).max(self.map.len() as u64 * 2);
+            self.map.flush_async()?;
+            reserve(&self.file, want)?;
+            // SAFETY: as in `Matches::mapped`; the old mapping is replaced here and not used again.
+            self.map = unsafe { MmapMut::map_mut(&self.file) }?;
+        }
+        let rec = &mut self.map[self.end..self.end + size];
+        rec[..4].copy_from_slice(&len.to_le_bytes());
+        rec[4..4 + path.len()].copy_from_slice(&path);
+        rec[4 + path.len()..size - 1].copy_from_slice(&(m.target as u64).to_le_bytes());
+        rec[size - 1] = m.broken as u8;
+        self.offsets.push(self.end);
+        self.end += size;
+        Ok(())
+    }
+
+    fn decode(&self, at: usize) -> Match {
+        decode(&self.map, at)
+    }
+}
+
+/// Grow `file` to `size` bytes with its blocks allocated, so a full disk is an error here rather
+/// than a SIGBUS when the mapping is written.
+fn reserve(file: &File, size: u64) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        let size = libc::off_t::try_from(size).map_err(|_| io::Error::other("results file too large"))?;
+        // SAFETY: plain call on an open descriptor; returns an error number, not -1/errno
+        let rc = unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, size) };
+        match rc {
+            0 => return Ok(()),
+            // Filesystems without fallocate (some network ones): size it sparsely instead
+            libc::EOPNOTSUPP | libc::EINVAL => {}
+            e => return Err(io::Error::from_raw_os_error(e)),
+        }
+    }
+    file.set_len(size)
+}
+
+fn path_bytes(map: &[u8], at: usize) -> &[u8] {
+    let len = u32::from_le_bytes(map[at..at + 4].try_into().unwrap()) as usize;
+    &map[at + 4..at + 4 + len]
+}
+
+#[cfg(unix)]
+fn path_at(map: &[u8], at: usize) -> &Path {
+    use std::os::unix::ffi::OsStrExt;
+    Path::new(std::ffi::OsStr::from_bytes(path_bytes(map, at)))
+}
+
+/// `os_bytes` wrote UTF-8 here, so this never falls back.
+#[cfg(not(unix))]
+fn path_at(map: &[u8], at: usize) -> &Path {
+    Path::new(std::str::from_utf8(path_bytes(map, at)).unwrap_or_default())
+}
+
+fn decode(map: &[u8], at: usize) -> Match {
+    let path = path_at(map, at).to_path_buf();
+    let rest = at + 4 + path_bytes(map, at).len();
+    let target = u64::from_le_bytes(map[rest..rest + 8].try_into().unwrap()) as usize;
+    Match { path, target, broken: map[rest + 8] != 0 }
+}
+
+pub struct Iter<'a> {
+    matches: &'a Matches,
+    next: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Cow<'a, Match>;
+
+    fn next(&mut self) -> Option<Cow<'a, Match>> {
+        if self.next >= self.matches.len() { return None; }
+        self.next += 1;
+        Some(self.matches.get(self.next - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.matches.len() - self.next;
+        (left, Some(left))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl<'a> IntoIterator for &'a Matches {
+    type Item = Cow<'a, Match>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl Serialize for Matches {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for Matches {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Matches, D::Error> {
+        Vec::<Match>::deserialize(d).map(Matches::from)
+    }
+}
@@ -0,0 +1,1280 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use regex_automata::meta::Regex;
+use ignore::{overrides::OverrideBuilder, WalkBuilder, WalkState};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::Boundaries;
+use crate::cancel::Reason;
+use crate::cli::{HardlinkMode, LinkTextMode, ScanOpts, WalkOpts};
+use crate::filter::Candidate;
+use crate::firmlink;
+use crate::hooks::{Hooks, Phase};
+use crate::perm::PermOf;
+use crate::results::Matches;
+use crate::timeout;
+use crate::wsl;
+
+/// Default heavy directory skip list (can be re-enabled with --include-heavy)
+pub(crate) const HEAVY_DIRS: &[&str] = &[
+    "node_modules",
+    ".cache",
+    "target",
+    "build",
+    "dist",
+    "out",
+    ".git",
+    ".venv",
+    "venv",
+];
+
+/// How long a mount point the walk reaches gets to answer before it is skipped (unless `--op-timeout` is set)
+const MOUNT_PROBE: Duration = Duration::from_secs(2);
+
+/// A path the scan matches symlinks against.
+#[derive(Debug)]
+pub struct Target {
+    /// The argument as given
+    pub given: String,
+    /// Canonical path, or `None` when the target no longer exists
+    pub resolved: Option<PathBuf>,
+    /// Absolute, lexically normalized form of `given` (for dangling links)
+    lexical: PathBuf,
+    meta: Option<fs::Metadata>,
+    /// For `--lname`/`--ilname`/`--link-text`: match links by their text instead of what they resolve to
+    link_pattern: Option<LinkPattern>,
+    /// For `--target-regex`: match links by where they point, as a whole-path regex
+    dest_regex: Option<Regex>,
+    /// For `--broken` and `--all`: take the links no other target claims
+    catch_all: Option<CatchAll>,
+    /// `--under`: also match links to anything inside the target
+    pub(crate) under: bool,
+}
+
+/// How a link-text target compares the raw `readlink` text.
+#[derive(Debug)]
+enum LinkPattern {
+    Glob(GlobMatcher),
+    /// `--link-text`: the same bytes
+    Exact(PathBuf),
+    /// `--link-text-mode prefix`: text starting with these bytes
+    Prefix(PathBuf),
+}
+
+impl LinkPattern {
+    fn is_match(&self, text: &Path) -> bool {
+        match self {
+            LinkPattern::Glob(g) => g.is_match(text),
+            LinkPattern::Exact(p) => p.as_os_str() == text.as_os_str(),
+            LinkPattern::Prefix(p) => crate::output::os_bytes(text).starts_with(&crate::output::os_bytes(p)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CatchAll {
+    /// `--broken`: every link that does not resolve
+    Broken,
+    /// `--all`: every link
+    All,
+}
+
+/// A symlink that resolves (or, if `broken`, points by name) to `targets[target]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Match {
+    pub path: PathBuf,
+    pub target: usize,
+    pub broken: bool,
+}
+
+/// A regular file whose content is identical to `targets[target]` (not a link to it).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentCopy {
+    pub path: PathBuf,
+    pub target: usize,
+}
+
+/// A regular file that is a hard link to `targets[target]` (same device and inode, another name).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardLink {
+    pub path: PathBuf,
+    pub target: usize,
+}
+
+/// A walk error (permission denied, loop, vanished entry, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+/// One symlink seen by the walk and how it resolved (recorded on request, for exports).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkRecord {
+    pub path: PathBuf,
+    pub link_text: Option<PathBuf>,
+    pub resolved: Option<PathBuf>,
+    pub error: Option<String>,
+}
+
+impl Target {
+    /// A target that was not looked up on this filesystem (e.g. one resolved through a manifest).
+    pub(crate) fn recorded(given: String, resolved: Option<PathBuf>, lexical: PathBuf) -> Target {
+        Target { given, resolved, lexical, meta: None, link_pattern: None, dest_regex: None, catch_all: None, under: false }
+    }
+
+    /// With `--under`, match links to anything inside the target as well.
+    pub(crate) fn under(mut self, under: bool) -> Target {
+        self.under = under;
+        self
+    }
+
+    /// `--broken`/`--all`: a target standing for whatever the links point at.
+    pub(crate) fn is_catch_all(&self) -> bool {
+        self.catch_all.is_some()
+    }
+
+    pub(crate) fn lexical(&self) -> &Path {
+        &self.lexical
+    }
+
+    /// Whether canonical path `r` is the target (or, with `--under`, inside it).
+    pub(crate) fn covers(&self, r: &Path) -> bool {
+        self.resolved.as_deref().is_some_and(|t| if self.under { r.starts_with(t) } else { r == t })
+    }
+
+    /// Whether lexical `dest` (where a dangling link points) names the target, or with `--under` a path inside it.
+    pub(crate) fn names(&self, dest: &Path) -> bool {
+        let is = |t: &Path| !t.as_os_str().is_empty() && if self.under { dest.starts_with(t) } else { dest == t };
+        is(&self.lexical) || self.resolved.as_deref().is_some_and(is)
+    }
+}
+
+fn error_path(e: &ignore::Error) -> Option<PathBuf> {
+    match e {
+        ignore::Error::WithPath { path, .. } => Some(path.clone()),
+        ignore::Error::Loop { child, .. } => Some(child.clone()),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => error_path(err),
+        _ => None,
+    }
+}
+
+/// Counts and matches from one walk + resolve pass.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub dirs: usize,
+    pub files: usize,
+    pub symlinks: usize,
+    /// Matching symlinks, sorted by path
+    pub matches: Matches,
+    /// Content copies of the targets (with `--copies`), sorted by path
+    pub copies: Vec<ContentCopy>,
+    /// Hard links to file targets (with `--hardlinks`), sorted by path
+    pub hardlinks: Vec<HardLink>,
+    /// Every symlink and its resolution (only with `ScanOpts::record_links`), sorted by path
+    pub links: Vec<LinkRecord>,
+    pub errors: Vec<ScanError>,
+    /// Every directory walked and its mtime (only with `ScanOpts::record_dirs`)
+    pub dir_mtimes: Vec<(PathBuf, SystemTime)>,
+    /// Wall time of the directory walk
+    pub walk_time: Duration,
+    /// Wall time spent resolving the collected symlinks
+    pub resolve_time: Duration,
+    /// Stopped early by `--max-errors` or cancelled: the counts and matches cover only part of the tree
+    pub aborted: bool,
+    /// Why the scan was cancelled, if it was (`--timeout`, Ctrl-C)
+    #[serde(default)]
+    pub cancelled: Option<Reason>,
+    /// Stopped at `--max-count` matches: the counts cover only the part of the tree walked
+    pub limited: bool,
+    /// Counts per scan root, in `--root` order (only with more than one root)
+    pub roots: Vec<RootStats>,
+    /// Directories not descended into for `--max-dir-entries`, sorted by path
+    pub skipped_dirs: Vec<PathBuf>,
+    /// Stats tried again after a transient error (`--retries`)
+    pub retries: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootStats {
+    pub root: PathBuf,
+    pub dirs: usize,
+    pub files: usize,
+    pub symlinks: usize,
+    pub matches: usize,
+}
+
+/// Per-target tallies for the multi-target summary.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TargetCounts {
+    pub matches: usize,
+    pub broken: usize,
+    pub copies: usize,
+    pub hardlinks: usize,
+}
+
+fn per_sec(n: usize, d: Duration) -> f64 {
+    let secs = d.as_secs_f64();
+    if secs > 0.0 { n as f64 / secs } else { n as f64 }
+}
+
+impl ScanResult {
+    /// Entries (dirs, files, symlinks) walked per second; bound by directory I/O.
+    pub fn walk_rate(&self) -> f64 {
+        per_sec(self.dirs + self.files + self.symlinks, self.walk_time)
+    }
+
+    /// Symlinks resolved per second; bound by `stat`/`realpath` calls.
+    pub fn resolve_rate(&self) -> f64 {
+        per_sec(self.symlinks, self.resolve_time)
+    }
+
+    /// Counts per target index.
+    pub fn per_target(&self, targets: usize) -> Vec<TargetCounts> {
+        let mut counts = vec![TargetCounts::default(); targets];
+        for m in &self.matches {
+            counts[m.target].matches += 1;
+            if m.broken { counts[m.target].broken += 1; }
+        }
+        for c in &self.copies { counts[c.target].copies += 1; }
+        for h in &self.hardlinks { counts[h.target].hardlinks += 1; }
+        counts
+    }
+}
+
+pub fn realpath(path: &Path) -> Result<PathBuf> {
+    // Resolve symlinks and normalize
+    let rp = fs::canonicalize(path).with_context(|| format!("realpath of {}", path.display()))?;
+    Ok(firmlink::fold(rp))
+}
+
+/// Roots reached through symlinked components (`--root /var/run` is `/run`), with their physical form.
+///
+/// The walk does not follow links, so only a path under such a root has an alias.
+pub struct RootAliases(Vec<(PathBuf, PathBuf)>);
+
+impl RootAliases {
+    pub fn new(roots: &[PathBuf]) -> RootAliases {
+        RootAliases(roots.iter()
+            .filter_map(|r| {
+                let real = realpath(r).ok()?;
+                (lexical_absolute(r) != real).then(|| (r.clone(), real))
+            })
+            .collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The physical path of walked path `p`, if it differs from `p`.
+    pub fn physical(&self, p: &Path) -> Option<PathBuf> {
+        self.0.iter().find_map(|(root, real)| Some(real.join(p.strip_prefix(root).ok()?)))
+    }
+}
+
+/// Device and inode: what every name of one file shares.
+#[cfg(unix)]
+fn identity(m: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_m: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Identity of the link itself: what two routes to the same link share.
+fn link_identity(p: &Path) -> Option<(u64, u64)> {
+    identity(&fs::symlink_metadata(p).ok()?)
+}
+
+/// Device and inode of each target that exists, to the first target with them: a live link is
+/// matched with one lookup however many targets there are.
+pub(crate) type TargetIds = HashMap<(u64, u64), usize>;
+
+pub(crate) fn target_ids(targets: &[Target]) -> TargetIds {
+    let mut ids = HashMap::new();
+    for (i, t) in targets.iter().enumerate() {
+        if let Some(id) = t.meta.as_ref().and_then(identity) { ids.entry(id).or_insert(i); }
+    }
+    ids
+}
+
+/// `--hardlinks`: device and inode of file targets that have other names, to the target index.
+fn hardlink_ids(opts: &ScanOpts, targets: &[Target]) -> TargetIds {
+    if opts.hardlinks.is_none() { return HashMap::new(); }
+    let mut ids = HashMap::new();
+    for (i, t) in targets.iter().enumerate() {
+        let Some(m) = t.meta.as_ref().filter(|m| m.is_file() && nlink(m) > 1) else { continue };
+        if let Some(id) = identity(m) { ids.entry(id).or_insert(i); }
+    }
+    ids
+}
+
+#[cfg(unix)]
+fn nlink(m: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    m.nlink()
+}
+
+#[cfg(not(unix))]
+fn nlink(_m: &fs::Metadata) -> u64 {
+    1
+}
+
+/// Which of the configured filtering rules a walker applies (all of them for a real scan).
+#[derive(Clone, Copy)]
+struct Rules {
+    heavy: bool,
+    user: bool,
+    gitignore: bool,
+}
+
+impl Rules {
+    const ALL: Rules = Rules { heavy: true, user: true, gitignore: true };
+}
+
+/// A walk visitor, as `WalkParallel::run` takes them.
+type Visitor<'s> = Box<dyn FnMut(Result<ignore::DirEntry, ignore::Error>) -> WalkState + Send + 's>;
+
+/// Walk the roots with every rule applied. Several roots (often on different disks) each get a
+/// walker of their own, run side by side so their I/O overlaps; `--threads` is split between them.
+fn walk_roots<'s>(opts: &WalkOpts, roots: &[PathBuf], boundaries: Option<Arc<Boundaries>>, visit: &(dyn Fn() -> Visitor<'s> + Sync)) {
+    let roots: Vec<PathBuf> = roots.iter().filter(|r| responds(r, opts.op_timeout, "readdir")).cloned().collect();
+    let roots = roots.as_slice();
+    if roots.is_empty() { return; }
+    if roots.len() < 2 {
+        return build_walker_with(opts, Rules::ALL, roots, boundaries).build_parallel().run(visit);
+    }
+    let threads = opts.threads.map(|n| n.div_ceil(roots.len()));
+    std::thread::scope(|s| {
+        for root in roots {
+            let boundaries = boundaries.clone();
+            s.spawn(move || {
+                let mut wb = build_walker_with(opts, Rules::ALL, std::slice::from_ref(root), boundaries);
+                if let Some(n) = threads { wb.threads(n); }
+                wb.build_parallel().run(visit);
+            });
+        }
+    });
+}
+
+/// Whether directory `p` can be opened and read within `limit`; one that cannot is noted as a timed-out `op`.
+fn responds(p: &Path, limit: Option<Duration>, op: &str) -> bool {
+    let Some(limit) = limit else { return true };
+    let q = p.to_path_buf();
+    let ok = timeout::call(Some(limit), move || { let _ = fs::read_dir(q).map(|mut d| d.next()); }).is_some();
+    if !ok { timeout::abandoned(p, op, limit); }
+    ok
+}
+
+/// The mount points strictly inside `roots`, as the walk will reach them (lexical, under each root as given).
+fn mount_points(roots: &[PathBuf]) -> HashSet<PathBuf> {
+    let mounts = crate::boundary::mounts();
+    let mut out = HashSet::new();
+    for r in roots {
+        let Ok(real) = realpath(r) else { continue };
+        let walked = lexical_absolute(r);
+        for m in mounts.keys() {
+            if let Ok(rest) = m.strip_prefix(&real) {
+                if !rest.as_os_str().is_empty() { out.insert(walked.join(rest)); }
+            }
+        }
+    }
+    out
+}
+
+fn boundaries(opts: &WalkOpts) -> Option<Arc<Boundaries>> {
+    Boundaries::new(opts, &opts.roots).map(Arc::new)
+}
+
+/// A walker over the subtree at `root` (a path below the scan root, or a tree of its own), with every rule applied.
+pub(crate) fn build_subtree_walker(opts: &WalkOpts, root: &Path) -> WalkBuilder {
+    let roots = [root.to_path_buf()];
+    build_walker_with(opts, Rules::ALL, &roots, Boundaries::new(opts, &roots).map(Arc::new))
+}
+
+/// `boundaries` replaces the walker's own `st_dev` check for `--one-filesystem`.
+fn build_walker_with(opts: &WalkOpts, rules: Rules, roots: &[PathBuf], boundaries: Option<Arc<Boundaries>>) -> WalkBuilder {
+    let gitignore = rules.gitignore && opts.respect_gitignore;
+    let mut wb = WalkBuilder::new(&roots[0]);
+    for r in &roots[1..] { wb.add(r); }
+    wb.follow_links(false)
+        .hidden(opts.hidden) // include hidden by default
+        .git_ignore(gitignore)
+        .git_global(gitignore && !opts.no_global_gitignore)
+        .git_exclude(gitignore && !opts.no_git_exclude)
+        .require_git(false)
+        .same_file_system(opts.one_filesystem && boundaries.is_none());
+    if let Some(n) = opts.threads { wb.threads(n); }
+    if rules.user {
+        for f in &opts.ignore_files { let _ = wb.add_ignore(f); }
+    }
+
+    let skip_heavy = rules.heavy && !opts.include_heavy;
+    let skip_submodules = rules.user && opts.skip_submodules;
+    // macOS: the data volume's copy of a firmlinked directory the walk also reaches through `/`
+    let aliases = firmlink::aliases(&roots.iter().filter_map(|r| realpath(r).ok()).collect::<Vec<_>>());
+    let op_timeout = opts.op_timeout;
+    // A stale automount or dead network mount hangs whoever lists it; each one the walk reaches is probed first
+    let mounts = if op_timeout.is_none() { mount_points(roots) } else { HashSet::new() };
+    if skip_heavy || skip_submodules || boundaries.is_some() || !aliases.is_empty() || op_timeout.is_some() || !mounts.is_empty() {
+        wb.filter_entry(move |e| {
+            if let Some(ft) = e.file_type() {
+                if ft.is_dir() {
+                    // Opened first, so a dead mount cannot hang the walker thread that would list it
+                    if !responds(e.path(), op_timeout, "readdir") { return false; }
+                    if !mounts.is_empty() && mounts.contains(&lexical_absolute(e.path())) && !responds(e.path(), Some(MOUNT_PROBE), "mount probe") { return false; }
+                    let name = e.file_name().to_string_lossy();
+                    if skip_heavy && HEAVY_DIRS.contains(&name.as_ref()) { return false; }
+                    // A submodule (or nested clone) has its own `.git` file or directory
+                    if skip_submodules && e.depth() > 0 && e.path().join(".git").symlink_metadata().is_ok() { return false; }
+                    if !aliases.is_empty() && e.depth() > 0 && aliases.contains(&lexical_absolute(e.path())) { return false; }
+                    if let (Some(b), true, Ok(m)) = (&boundaries, e.depth() > 0, e.metadata()) {
+                        if b.stops_at(e.path(), &m) { return false; }
+                    }
+                }
+            }
+            true
+        });
+    }
+
+    // User-specified ignore globs
+    if rules.user && !opts.ignores.is_empty() {
+        // Globs with a `/` are anchored at the first root
+        let mut ob = OverrideBuilder::new(&opts.roots[0]);
+        for g in &opts.ignores {
+            // In override matcher, a pattern starting with '!' is an ignore glob
+            // (whitelist otherwise). We want ignores here.
+            let pat = if g.starts_with('!') { g.clone() } else { format!("!{}", g) };
+            let _ = ob.add(&pat);
+        }
+        if let Ok(ov) = ob.build() { wb.overrides(ov); }
+    }
+    if let Some(d) = opts.max_depth { wb.max_depth(Some(d)); }
+    wb
+}
+
+/// One row of `--profile-ignores`: a walk with some of the rules enabled.
+#[derive(Debug)]
+pub struct IgnoreProfile {
+    pub label: &'static str,
+    pub entries: usize,
+    pub time: Duration,
+}
+
+/// Time count-only walks with the ignore rules enabled one layer at a time.
+///
+/// The first walk (no rules) is run twice and only the second is kept, so the
+/// page cache is warm for every row.
+pub fn profile_ignores(opts: &WalkOpts) -> Vec<IgnoreProfile> {
+    let timed = |label, rules| {
+        let entries = AtomicUsize::new(0);
+        let start = Instant::now();
+        build_walker_with(opts, rules, &opts.roots, None).build_parallel().run(|| {
+            let entries = &entries;
+            Box::new(move |_| {
+                entries.fetch_add(1, Ordering::Relaxed);
+                WalkState::Continue
+            })
+        });
+        IgnoreProfile { label, entries: entries.into_inner(), time: start.elapsed() }
+    };
+    let none = Rules { heavy: false, user: false, gitignore: false };
+    timed("no rules", none);
+    let mut rows = vec![timed("no rules", none)];
+    let mut rules = none;
+    if !opts.include_heavy {
+        rules.heavy = true;
+        rows.push(timed("+ heavy dirs", rules));
+    }
+    if !opts.ignores.is_empty() || !opts.ignore_files.is_empty() || opts.skip_submodules {
+        rules.user = true;
+        rows.push(timed("+ --ignore/--ignore-file/--skip-submodules", rules));
+    }
+    if opts.respect_gitignore {
+        rules.gitignore = true;
+        rows.push(timed("+ gitignore", rules));
+    }
+    rows
+}
+
+/// Make `p` absolute against the working directory and drop `.`/`..` without touching the filesystem.
+pub fn lexical_absolute(p: &Path) -> PathBuf {
+    let joined = if p.is_absolute() { p.to_path_buf() } else { std::env::current_dir().unwrap_or_default().join(p) };
+    let mut out = PathBuf::new();
+    for c in joined.components() {
+        match c {
+            Component::ParentDir => { out.pop(); }
+            Component::CurDir => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Depth of a walked path below the scan root (`./a/b` is 2).
+pub fn depth(p: &Path) -> usize {
+    p.components().filter(|c| matches!(c, Component::Normal(_))).count()
+}
+
+/// The `--root` (as given) walked path `p` was found under, and its depth below it (`/etc/a/b` under `/etc` is 2).
+pub fn locate<'a>(roots: &'a [PathBuf], p: &Path) -> Option<(&'a Path, usize)> {
+    roots.iter()
+        .filter_map(|r| Some((r.as_path(), depth(p.strip_prefix(r).ok()?))))
+        .max_by_key(|(r, _)| r.components().count())
+}
+
+/// Resolve the target arguments to the canonical paths matches are compared against.
+///
+/// A single target must exist. With several, a missing target is kept (with a
+/// warning) so dangling links that still name it are reported as broken.
+pub fn resolve_targets(opts: &ScanOpts) -> Result<Vec<Target>> {
+    let mut out: Vec<Target> = Vec::with_capacity(opts.targets.len());
+    // Seen so far, for skipping duplicates without comparing against every earlier target
+    let (mut seen_resolved, mut seen_lexical, mut seen_unresolved) = (HashSet::new(), HashSet::new(), HashSet::new());
+    for given in &opts.targets {
+        let path = target_path(opts, given);
+        let resolved = match realpath(&path) {
+            Ok(rp) => Some(rp),
+            Err(e) if opts.targets.len() == 1 => return Err(e).with_context(|| "Failed to resolve target"),
+            Err(_) => {
+                eprintln!("warning: cannot resolve target {}; matching broken links only", given);
+                None
+            }
+        };
+        let lexical = firmlink::fold_lexical(lexical_absolute(&path));
+        let dup = match &resolved {
+            Some(r) => seen_resolved.contains(r) || seen_unresolved.contains(&lexical),
+            None => seen_lexical.contains(&lexical),
+        };
+        if dup { continue; }
+        match &resolved {
+            Some(r) => { seen_resolved.insert(r.clone()); }
+            None => { seen_unresolved.insert(lexical.clone()); }
+        }
+        seen_lexical.insert(lexical.clone());
+        let meta = resolved.as_deref().and_then(|p| fs::metadata(p).ok());
+        out.push(Target { given: given.clone(), resolved, lexical, meta, link_pattern: None, dest_regex: None, catch_all: None, under: opts.under });
+    }
+    out.extend(pattern_targets(opts)?);
+    Ok(out)
+}
+
+/// A target argument as a path: Windows forms (`C:\Users\me`) on the WSL drive mounts, with `--wsl-links` or under WSL.
+fn target_path(opts: &ScanOpts, given: &str) -> PathBuf {
+    let wsl = (opts.wsl_links || wsl::detected()).then(|| wsl::to_linux(Path::new(given), opts.wsl_unc)).flatten();
+    wsl.unwrap_or_else(|| PathBuf::from(given))
+}
+
+/// `--lname`/`--ilname` patterns as targets, matched against link text like GNU find (`*` also matches `/`),
+/// and `--link-text` (exact, prefix, or glob), `--target-regex` on where links point, then `--broken` and `--all`, which take the (dangling, or any)
+/// links no other target claims.
+pub(crate) fn pattern_targets(opts: &ScanOpts) -> Result<Vec<Target>> {
+    let glob = |pat: &str, icase: bool, flag: &str| -> Result<LinkPattern> {
+        let g = GlobBuilder::new(pat).case_insensitive(icase).backslash_escape(true).build()
+            .with_context(|| format!("invalid {} pattern {:?}", flag, pat))?;
+        Ok(LinkPattern::Glob(g.compile_matcher()))
+    };
+    let link_text = opts.link_text.iter().map(|p| {
+        let pattern = match opts.link_text_mode {
+            LinkTextMode::Exact => LinkPattern::Exact(p.into()),
+            LinkTextMode::Prefix => LinkPattern::Prefix(p.into()),
+            LinkTextMode::Glob => glob(p, false, "--link-text")?,
+        };
+        Ok((format!("--link-text {}", p), pattern))
+    });
+    let patterns = opts.lname.iter().map(|p| Ok((format!("-lname {}", p), glob(p, false, "-lname")?)))
+        .chain(opts.ilname.iter().map(|p| Ok((format!("-ilname {}", p), glob(p, true, "-ilname")?))))
+        .chain(link_text);
+    let regexes = opts.target_regex.iter().map(|re| {
+        let r = Regex::new(&format!("^(?:{})$", re)).with_context(|| format!("invalid --target-regex {:?}", re))?;
+        Ok(Target {
+            given: format!("--target-regex {}", re),
+            resolved: None,
+            lexical: PathBuf::new(),
+            meta: None,
+            link_pattern: None,
+            dest_regex: Some(r),
+            catch_all: None,
+            under: false,
+        })
+    });
+    let catch_all = [(opts.broken, CatchAll::Broken, "--broken"), (opts.all, CatchAll::All, "--all")]
+        .into_iter()
+        .filter(|(on, _, _)| *on)
+        .map(|(_, c, flag)| Ok(Target {
+            given: flag.into(),
+            resolved: None,
+            lexical: PathBuf::new(),
+            meta: None,
+            link_pattern: None,
+            dest_regex: None,
+            catch_all: Some(c),
+            under: false,
+        }));
+    patterns
+        .map(|p: Result<(String, LinkPattern)>| {
+            let (given, pattern) = p?;
+            Ok(Target {
+                given,
+                resolved: None,
+                lexical: PathBuf::new(),
+                meta: None,
+                link_pattern: Some(pattern),
+                dest_regex: None,
+                catch_all: None,
+                under: false,
+            })
+        })
+        .chain(regexes)
+        .chain(catch_all)
+        .collect()
+}
+
+pub(crate) fn has_patterns(targets: &[Target]) -> bool {
+    targets.iter().any(|t| t.link_pattern.is_some() || t.dest_regex.is_some())
+}
+
+/// Index of the first `--lname`/`--ilname`/`--link-text` target whose pattern matches link text `text`.
+pub(crate) fn match_link_pattern(text: &Path, targets: &[Target]) -> Option<usize> {
+    targets.iter().position(|t| t.link_pattern.as_ref().is_some_and(|g| g.is_match(text)))
+}
+
+/// Index of the first `--target-regex` target matching `dest`, where a link points.
+pub(crate) fn match_dest_regex(dest: &Path, targets: &[Target]) -> Option<usize> {
+    let bytes = crate::output::os_bytes(dest);
+    targets.iter().position(|t| t.dest_regex.as_ref().is_some_and(|r| r.is_match(bytes.as_ref())))
+}
+
+/// Index of the `--broken` (for a link that does not resolve) or `--all` target, for a link that matched nothing else.
+pub(crate) fn match_catch_all(targets: &[Target], broken: bool) -> Option<usize> {
+    let find = |c| targets.iter().position(|t| t.catch_all == Some(c));
+    if broken { find(CatchAll::Broken).or_else(|| find(CatchAll::All)) } else { find(CatchAll::All) }
+}
+
+/// Why the link at `p` does not resolve (`missing`, `loop`, `permission denied`, `not a directory`, or the OS error); `None` if it does.
+pub fn broken_reason(p: &Path) -> Option<String> {
+    let e = fs::metadata(p).err()?;
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(libc::ELOOP) { return Some("loop".into()); }
+    let reason = match e.kind() {
+        io::ErrorKind::NotFound => "missing",
+        io::ErrorKind::PermissionDenied => "permission denied",
+        io::ErrorKind::NotADirectory => "not a directory",
+        _ => return Some(e.to_string()),
+    };
+    Some(reason.into())
+}
+
+/// Where a matched link points, as the `target` seen by `--where` and `--post-filter`.
+pub(crate) fn filter_target(p: &Path, t: &Target) -> PathBuf {
+    if t.link_pattern.is_none() && t.dest_regex.is_none() && t.catch_all.is_none() && !t.under { return t.resolved.clone().unwrap_or_else(|| t.lexical.clone()); }
+    fs::read_link(p).map(|l| lexical_absolute(&p.parent().unwrap_or(Path::new("")).join(l))).unwrap_or_default()
+}
+
+/// Index of the target a live symlink resolves to.
+fn match_live(p: &Path, meta: &fs::Metadata, targets: &[Target], ids: &TargetIds, real_dir: Option<&Path>) -> Option<usize> {
+    // Fast path on Unix: look up device+inode without allocating full realpath
+    if let Some(&hit) = identity(meta).and_then(|id| ids.get(&id)) { return Some(hit); }
+    let resolved = match real_dir {
+        Some(dir) => realpath_in(dir, p).ok()?,
+        None => realpath(p).ok()?,
+    };
+    innermost(targets, |t| t.covers(&resolved))
+}
+
+/// Index of the target satisfying `hit`; with `--under`, nested targets claim a link for the deepest one.
+pub(crate) fn innermost(targets: &[Target], hit: impl Fn(&Target) -> bool) -> Option<usize> {
+    targets.iter().enumerate()
+        .filter(|(_, t)| hit(t))
+        .min_by_key(|(_, t)| std::cmp::Reverse(t.resolved.as_deref().unwrap_or(&t.lexical).components().count()))
+        .map(|(i, _)| i)
+}
+
+/// Index of the target a dangling symlink names, comparing its link text lexically.
+fn match_broken(p: &Path, targets: &[Target]) -> Option<usize> {
+    match_link_text(p, &fs::read_link(p).ok()?, targets)
+}
+
+/// Index of the target that link text `text` (of the link at `p`) names, without touching the filesystem.
+pub(crate) fn match_link_text(p: &Path, text: &Path, targets: &[Target]) -> Option<usize> {
+    match_dest(&p.parent().unwrap_or(Path::new("")).join(text), targets)
+}
+
+/// Index of the target `dest` (where a dangling link points) names, lexically.
+fn match_dest(dest: &Path, targets: &[Target]) -> Option<usize> {
+    let dest = firmlink::fold_lexical(lexical_absolute(dest));
+    innermost(targets, |t| t.names(&dest))
+}
+
+/// `--wsl-links`: where a dangling link with Windows text points on the drive mounts, and whether that exists.
+fn wsl_dest(opts: &ScanOpts, p: &Path) -> Option<(PathBuf, io::Result<fs::Metadata>)> {
+    let dest = wsl::to_linux(&fs::read_link(p).ok()?, opts.wsl_unc)?;
+    let meta = fs::metadata(&dest);
+    Some((dest, meta))
+}
+
+/// Where the link at `p` resolves to, through the WSL drive mounts with `--wsl-links`.
+pub fn link_destination(opts: &ScanOpts, p: &Path) -> Option<PathBuf> {
+    realpath(p).ok().or_else(|| {
+        if !opts.wsl_links { return None; }
+        realpath(&wsl_dest(opts, p)?.0).ok()
+    })
+}
+
+/// Where the link at `p` points: its resolution, or lexically (through the WSL drive mounts) if it dangles.
+pub fn points_to(opts: &ScanOpts, p: &Path) -> Option<PathBuf> {
+    link_destination(opts, p).or_else(|| {
+        let text = fs::read_link(p).ok()?;
+        let text = if opts.wsl_links { wsl::to_linux(&text, opts.wsl_unc).unwrap_or(text) } else { text };
+        Some(lexical_absolute(&p.parent().unwrap_or(Path::new("")).join(text)))
+    })
+}
+
+/// The symlinks that resolving the link at `p` passes through after `p` itself, in order (`--via`).
+///
+/// Like `namei`, every component is checked, so a hop into a symlinked directory lists that directory too.
+pub fn link_chain(p: &Path) -> Vec<PathBuf> {
+    let mut via = Vec::new();
+    let Ok(text) = fs::read_link(p) else { return via };
+    let Ok(parent) = fs::canonicalize(p.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."))) else { return via };
+    follow_chain(&parent.join(text), &mut via);
+    via
+}
+
+/// Resolve `p` component by component, recording each symlink met; stops following at a loop or the kernel's 40 hops.
+fn follow_chain(p: &Path, via: &mut Vec<PathBuf>) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in p.components() {
+        match c {
+            Component::ParentDir => { out.pop(); }
+            Component::CurDir => {}
+            Component::Normal(n) => {
+                out.push(n);
+                if via.len() >= 40 || via.contains(&out) { continue; }
+                if let Ok(text) = fs::read_link(&out) {
+                    via.push(out.clone());
+                    out = follow_chain(&out.parent().unwrap_or(Path::new("/")).join(text), via);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn hash_file(p: &Path) -> Option<blake3::Hash> {
+    let mut h = blake3::Hasher::new();
+    h.update_reader(fs::File::open(p).ok()?).ok()?;
+    Some(h.finalize())
+}
+
+/// Whether `meta` (of `p`) is the target file itself or a hard link to it.
+fn is_same_file(p: &Path, meta: &fs::Metadata, t: &Target) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let _ = p;
+        t.meta.as_ref().is_some_and(|tm| meta.dev() == tm.dev() && meta.ino() == tm.ino())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = meta;
+        realpath(p).ok().as_ref() == t.resolved.as_ref()
+    }
+}
+
+/// Hash same-sized candidates and keep those whose content equals a file target.
+fn find_copies(candidates: &[PathBuf], targets: &[Target]) -> Vec<ContentCopy> {
+    let hashed: Vec<(usize, u64, blake3::Hash)> = targets.iter().enumerate()
+        .filter_map(|(i, t)| {
+            let m = t.meta.as_ref().filter(|m| m.is_file() && m.len() > 0)?;
+            Some((i, m.len(), hash_file(t.resolved.as_deref()?)?))
+        })
+        .collect();
+    let mut copies: Vec<ContentCopy> = candidates.par_iter()
+        .filter_map(|p| {
+            let meta = fs::metadata(p).ok()?;
+            let sized: Vec<_> = hashed.iter()
+                .filter(|(i, len, _)| *len == meta.len() && !is_same_file(p, &meta, &targets[*i]))
+                .collect();
+            if sized.is_empty() { return None; }
+            let h = hash_file(p)?;
+            let (i, _, _) = sized.into_iter().find(|(_, _, th)| *th == h)?;
+            Some(ContentCopy { path: p.clone(), target: *i })
+        })
+        .collect();
+    copies.sort_by(|a, b| a.path.cmp(&b.path));
+    copies
+}
+
+/// Links in a directory holding at least this many are resolved in units sharing the directory
+const FARM_MIN: usize = 64;
+/// Links per link farm unit, so one huge directory is still spread over the resolve threads
+const FARM_UNIT: usize = 512;
+
+/// Split the collected links into link farm units (runs of up to `FARM_UNIT` links of one
+/// directory with at least `FARM_MIN`) and the rest, each on its own.
+fn farm_units(entries: &[PathBuf]) -> (Vec<Vec<&PathBuf>>, Vec<&PathBuf>) {
+    let mut by_dir: HashMap<&Path, Vec<&PathBuf>> = HashMap::new();
+    for p in entries { by_dir.entry(p.parent().unwrap_or(Path::new(""))).or_default().push(p); }
+    let (mut farms, mut singles) = (Vec::new(), Vec::new());
+    for links in by_dir.into_values() {
+        if links.len() < FARM_MIN { singles.extend(links); continue; }
+        farms.extend(links.chunks(FARM_UNIT).map(<[_]>::to_vec));
+    }
+    (farms, singles)
+}
+
+/// `realpath(p)` for the link `p` in `real_dir`, its directory already resolved: only the link
+/// text is walked, rather than every component of the link's path again.
+#[cfg(unix)]
+fn realpath_in(real_dir: &Path, p: &Path) -> io::Result<PathBuf> {
+    let mut hops = 0;
+    follow(real_dir.to_path_buf(), &fs::read_link(p)?, &mut hops).map(firmlink::fold)
+}
+
+#[cfg(not(unix))]
+fn realpath_in(_real_dir: &Path, p: &Path) -> io::Result<PathBuf> {
+    fs::canonicalize(p).map(firmlink::fold)
+}
+
+/// Resolve link text from the real directory `dir` as the kernel would: a symlink in it is
+/// followed before a `..` after it is applied.
+#[cfg(unix)]
+fn follow(mut dir: PathBuf, text: &Path, hops: &mut usize) -> io::Result<PathBuf> {
+    // Linux's limit on symlinks followed in one lookup
+    *hops += 1;
+    if *hops > 40 { return Err(io::Error::from_raw_os_error(libc::ELOOP)); }
+    // Whether `dir` so far is a directory; `.` and `..` after anything else are ENOTDIR
+    let mut is_dir = true;
+    for c in text.components() {
+        match c {
+            Component::RootDir => { dir = PathBuf::from("/"); is_dir = true; }
+            Component::CurDir | Component::ParentDir if !is_dir => return Err(io::Error::from_raw_os_error(libc::ENOTDIR)),
+            Component::CurDir => {}
+            Component::ParentDir => { dir.pop(); }
+            Component::Normal(name) => {
+                if !is_dir { return Err(io::Error::from_raw_os_error(libc::ENOTDIR)); }
+                dir.push(name);
+                let meta = fs::symlink_metadata(&dir)?;
+                if meta.is_symlink() {
+                    let text = fs::read_link(&dir)?;
+                    dir.pop();
+                    dir = follow(dir, &text, hops)?;
+                    is_dir = dir.is_dir();
+                } else {
+                    is_dir = meta.is_dir();
+                }
+            }
+            Component::Prefix(_) => {}
+        }
+    }
+    Ok(dir)
+}
+
+/// Target index (and whether the link is dangling) for the symlink at `p`, after `--where`.
+pub(crate) fn match_symlink(opts: &ScanOpts, p: &Path, targets: &[Target], ids: &TargetIds, has_patterns: bool) -> Option<(usize, bool)> {
+    match_symlink_meta(opts, p, &fs::metadata(p), targets, ids, has_patterns, None)
+}
+
+/// Whether resolving a link failed for a reason other than the link being dangling or looping
+/// (I/O errors, stale NFS handles, permissions): what `--max-errors` counts.
+fn is_resolve_fault(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(libc::ELOOP) { return false; }
+    !matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory)
+}
+
+/// `fs::metadata(p)` under `--op-timeout`, tried again with a doubling wait for `--retries` transient
+/// errors; `None` if it timed out.
+fn stat_retrying(opts: &ScanOpts, p: &Path, retries: &AtomicUsize) -> Option<io::Result<fs::Metadata>> {
+    let stat = || { let q = p.to_path_buf(); timeout::call(opts.walk.op_timeout, move || fs::metadata(q)) };
+    let mut meta = stat()?;
+    for n in 0..opts.retries {
+        if !meta.as_ref().is_err_and(is_transient) { break; }
+        std::thread::sleep(opts.retry_backoff.saturating_mul(1 << n.min(16)));
+        retries.fetch_add(1, Ordering::Relaxed);
+        meta = stat()?;
+    }
+    Some(meta)
+}
+
+/// An error a network filesystem may not return on the next try.
+fn is_transient(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    return matches!(e.raw_os_error(), Some(libc::EAGAIN | libc::EIO | libc::ESTALE));
+    #[cfg(not(unix))]
+    return e.kind() == io::ErrorKind::WouldBlock;
+}
+
+/// [`match_symlink`] with the link's `fs::metadata` already taken, and its directory resolved if
+/// that was shared with other links.
+fn match_symlink_meta(opts: &ScanOpts, p: &Path, meta: &io::Result<fs::Metadata>, targets: &[Target], ids: &TargetIds, has_patterns: bool, real_dir: Option<&Path>) -> Option<(usize, bool)> {
+    let wsl = if meta.is_err() && opts.wsl_links { wsl_dest(opts, p) } else { None };
+    let hit = match (meta, &wsl) {
+        (Ok(m), _) => match_live(p, m, targets, ids, real_dir).map(|t| (t, false)),
+        (Err(_), Some((dest, Ok(m)))) => match_live(dest, m, targets, ids, None).map(|t| (t, false)),
+        (Err(_), Some((dest, Err(_)))) => match_dest(dest, targets).map(|t| (t, true)),
+        (Err(_), None) => match_broken(p, targets).map(|t| (t, true)),
+    };
+    let broken = meta.is_err() && !wsl.as_ref().is_some_and(|(_, m)| m.is_ok());
+    let hit = hit.or_else(|| {
+        if !has_patterns { return None; }
+        match_link_pattern(&fs::read_link(p).ok()?, targets)
+            .or_else(|| match_dest_regex(&points_to(opts, p)?, targets))
+            .map(|t| (t, broken))
+    });
+    let hit = hit.or_else(|| match_catch_all(targets, broken).map(|t| (t, broken)));
+    let hit = hit.filter(|_| {
+        opts.exclude_targets.is_empty() || !points_to(opts, p).is_some_and(|d| opts.exclude_targets.iter().any(|x| d.starts_with(x)))
+    });
+    let hit = hit.filter(|_| opts.perms.is_empty() || perm_mode(opts, p, meta).is_some_and(|m| opts.perms.iter().all(|t| t.matches(m))));
+    hit.filter(|&(t, broken)| {
+        let Some(f) = &opts.filter else { return true };
+        f.matches(&Candidate::new(p, &filter_target(p, &targets[t]), broken))
+    })
+}
+
+/// The mode `--perm` tests: the target's (none if the link dangles) or, with `--perm-of link`, the link's own.
+#[cfg(unix)]
+fn perm_mode(opts: &ScanOpts, p: &Path, meta: &io::Result<fs::Metadata>) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    match opts.perm_of {
+        PermOf::Target => meta.as_ref().ok().map(|m| m.permissions().mode()),
+        PermOf::Link => fs::symlink_metadata(p).ok().map(|m| m.permissions().mode()),
+    }
+}
+
+#[cfg(not(unix))]
+fn perm_mode(_opts: &ScanOpts, _p: &Path, _meta: &io::Result<fs::Metadata>) -> Option<u32> {
+    None
+}
+
+/// Entries the walk will visit (the root included), for `--progress exact`; nothing is stat'ed.
+pub fn count_entries(opts: &ScanOpts) -> u64 {
+    let n = AtomicUsize::new(0);
+    walk_roots(&opts.walk, &opts.walk.roots, boundaries(&opts.walk), &|| {
+        Box::new(|res| {
+            let Ok(e) = res else { return WalkState::Continue };
+            n.fetch_add(1, Ordering::Relaxed);
+            if e.file_type().is_some_and(|t| t.is_dir()) && oversized(opts, &e) { return WalkState::Skip; }
+            WalkState::Continue
+        })
+    });
+    timeout::log(&timeout::take());
+    n.into_inner() as u64
+}
+
+/// Whether `--max-dir-entries` skips directory `e`: it has more than N entries. The roots are always walked.
+///
+/// Only the first N + 1 entries are read, so a huge directory costs little to reject.
+fn oversized(opts: &ScanOpts, e: &ignore::DirEntry) -> bool {
+    let Some(n) = opts.max_dir_entries else { return false };
+    e.depth() > 0 && fs::read_dir(e.path()).is_ok_and(|d| d.take(n + 1).count() > n)
+}
+
+/// The `--export-sqlite` row for the symlink at `p`.
+pub fn link_record(p: &Path) -> LinkRecord {
+    let (resolved, error) = match fs::canonicalize(p).map(firmlink::fold) {
+        Ok(rp) => (Some(rp), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    LinkRecord { path: p.to_path_buf(), link_text: fs::read_link(p).ok(), resolved, error }
+}
+
+/// Every symlink the walk visits, for `tune`'s timed runs and `index watch`.
+pub fn walk_symlinks(opts: &WalkOpts) -> Vec<PathBuf> {
+    let links = Mutex::new(Vec::new());
+    walk_roots(opts, &opts.roots, boundaries(opts), &|| {
+        Box::new(|res| {
+            if let Ok(e) = res {
+                if e.file_type().is_some_and(|t| t.is_symlink()) { links.lock().unwrap().push(e.into_path()); }
+            }
+            WalkState::Continue
+        })
+    });
+    timeout::log(&timeout::take());
+    links.into_inner().unwrap()
+}
+
+/// Walk the tree, then resolve every symlink in parallel against `targets`.
+///
+/// `on_match` is called from worker threads as soon as a match is confirmed; `progress` hears of
+/// every phase, entry, and link along the way.
+pub fn scan(
+    opts: &ScanOpts,
+    targets: &[Target],
+    progress: &dyn Hooks,
+    on_match: &(dyn Fn(&Match) + Sync),
+) -> Result<ScanResult> {
+    // Checked at every entry and link, alongside `--max-errors` and `--max-count`
+    let cancel = opts.walk.cancel.armed(opts.walk.timeout);
+    if opts.walk.backend == crate::cli::Backend::Cap {
+        if opts.max_count.is_some() { anyhow::bail!("--max-count is not available with --backend cap"); }
+        if !opts.priority_roots.is_empty() { anyhow::bail!("--priority-root is not available with --backend cap"); }
+        if opts.mmap_results.is_some() { anyhow::bail!("--mmap-results is not available with --backend cap"); }
+        #[cfg(feature = "cap-std")]
+        return crate::confined::scan(opts, targets, progress, on_match, &cancel);
+        #[cfg(not(feature = "cap-std"))]
+        anyhow::bail!("--backend cap needs a build with `--features cap-std`");
+    }
+    progress.phase(Phase::Walking);
+    // Collect symlink entries and count files/dirs traversed (parallel walk)
+    let file_count = Arc::new(AtomicUsize::new(0));
+    let dir_count = Arc::new(AtomicUsize::new(0));
+    let entries: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(Vec::new()));
+    // Symlinks resolved during the walk (with `--max-count`) rather than collected in `entries`
+    let symlink_count = AtomicUsize::new(0);
+    // Size pre-filter for --copies: only regular files as large as a file target get hashed
+    let copy_sizes: Arc<HashSet<u64>> = Arc::new(if opts.copies {
+        // Empty files are all "copies" of each other; skip them
+        targets.iter().filter_map(|t| t.meta.as_ref()).filter(|m| m.is_file() && m.len() > 0).map(|m| m.len()).collect()
+    } else {
+        HashSet::new()
+    });
+    let copy_candidates: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(Vec::new()));
+    // Only file targets with a link count above one can have other names
+    let hardlink_ids = Arc::new(hardlink_ids(opts, targets));
+    let hardlinks: Arc<Mutex<Vec<HardLink>>> = Arc::new(Mutex::new(Vec::new()));
+    let errors: Arc<Mutex<Vec<ScanError>>> = Arc::new(Mutex::new(Vec::new()));
+    let skipped_dirs = Mutex::new(Vec::<PathBuf>::new());
+    let retries = AtomicUsize::new(0);
+    let dir_mtimes: Arc<Mutex<Vec<(PathBuf, SystemTime)>>> = Arc::new(Mutex::new(Vec::new()));
+    // `--max-errors`: past the limit, the walk quits and the remaining links are skipped
+    let aborted = Arc::new(AtomicBool::new(false));
+    let record_error = |errors: &Mutex<Vec<ScanError>>, rec: ScanError| {
+        let Ok(mut v) = errors.lock() else { return };
+        v.push(rec);
+        if opts.walk.max_errors.is_some_and(|m| v.len() > m) { aborted.store(true, Ordering::Relaxed); }
+    };
+
+    // `--priority-root`s are walked first; one inside another root is skipped by that root's walk
+    let first = &opts.priority_roots;
+    let rest: Vec<PathBuf> = opts.walk.roots.iter().filter(|r| !first.contains(r)).cloned().collect();
+    let skip: HashSet<PathBuf> = first.iter()
+        .filter_map(|p| realpath(p).ok())
+        .flat_map(|p| rest.iter().filter_map(move |r| Some(r.join(p.strip_prefix(realpath(r).ok()?).ok()?))))
+        .collect();
+    // The innermost root decides which device a directory should be on
+    let boundaries = Boundaries::new(&opts.walk, &first.iter().chain(&rest).cloned().collect::<Vec<_>>()).map(Arc::new);
+    // Per-root dirs, files, symlinks; nested roots were dropped, so each entry has exactly one
+    let roots = &opts.walk.roots;
+    let per_root: Vec<[AtomicUsize; 3]> = if roots.len() > 1 { roots.iter().map(|_| Default::default()).collect() } else { Vec::new() };
+    let root_of = |p: &Path| roots.iter().position(|r| p.starts_with(r));
+
+    let matches_out = Mutex::new(match &opts.mmap_results {
+        Some(dir) => Matches::mapped(dir.as_deref().unwrap_or(&std::env::temp_dir()))?,
+        None => Matches::default(),
+    });
+    let links_out = Mutex::new(Vec::<LinkRecord>::new());
+    let seen = Mutex::new(HashSet::new());
+    // `--max-count`: set once that many matches are in, which stops the walk and the resolve
+    let limited = AtomicBool::new(false);
+    // Resolve links as the walk finds them: always for `--max-count`, and while walking the priority roots
+    let inline = AtomicBool::new(opts.max_count.is_some() || !first.is_empty());
+    let has_patterns = has_patterns(targets);
+    let ids = target_ids(targets);
+    let resolve = |p: &PathBuf, real_dir: Option<&Path>| {
+        if aborted.load(Ordering::Relaxed) || limited.load(Ordering::Relaxed) || cancel.is_cancelled() { return; }
+        let Some(meta) = stat_retrying(opts, p, &retries) else {
+            timeout::abandoned(p, "stat", opts.walk.op_timeout.unwrap_or_default());
+            return progress.resolved();
+        };
+        if let Err(e) = &meta {
+            // One still failing after its retries is reported rather than taken for a dangling link
+            if (opts.walk.max_errors.is_some() || (opts.retries > 0 && is_transient(e))) && is_resolve_fault(e) {
+                record_error(&errors, ScanError { path: Some(p.clone()), message: e.to_string() });
+            }
+        }
+        if opts.record_links {
+            let rec = link_record(p);
+            if let Ok(mut v) = links_out.lock() { v.push(rec); }
+        }
+        if opts.hardlinks == Some(HardlinkMode::Only) { return progress.resolved(); }
+        if let Some((target, broken)) = match_symlink_meta(opts, p, &meta, targets, &ids, has_patterns, real_dir) {
+            // The same link reached by a second route (a bind mount, a hard-linked symlink) counts once
+            if link_identity(p).is_none_or(|id| seen.lock().unwrap().insert(id)) {
+                let m = Match { path: p.clone(), target, broken };
+                // Admitted under the lock, so racing threads cannot go past the limit; the hooks run after it
+                let admitted = {
+                    let mut v = matches_out.lock().unwrap();
+                    let admitted = opts.max_count.is_none_or(|n| v.len() < n);
+                    if admitted {
+                        if let Err(e) = v.push(m.clone()) {
+                            // Out of room for the results file: what is in it is all there will be
+                            record_error(&errors, ScanError { path: None, message: format!("results file: {}", e) });
+                            aborted.store(true, Ordering::Relaxed);
+                        }
+                        if opts.max_count.is_some_and(|n| v.len() >= n) { limited.store(true, Ordering::Relaxed); }
+                    }
+                    admitted
+                };
+                if admitted {
+                    progress.matched(&m);
+                    on_match(&m);
+                }
+            }
+        }
+        progress.resolved();
+    };
+
+    let walk_start = Instant::now();
+    let visit = || -> Visitor {
+        let file_count = Arc::clone(&file_count);
+        let dir_count = Arc::clone(&dir_count);
+        let entries = Arc::clone(&entries);
+        let copy_sizes = Arc::clone(&copy_sizes);
+        let copy_candidates = Arc::clone(&copy_candidates);
+        let hardlink_ids = Arc::clone(&hardlink_ids);
+        let hardlinks = Arc::clone(&hardlinks);
+        let symlink_count = &symlink_count;
+        let errors = Arc::clone(&errors);
+        let dir_mtimes = Arc::clone(&dir_mtimes);
+        let aborted = Arc::clone(&aborted);
+        let (record_error, resolve, limited, inline, skip, skipped_dirs, cancel) = (&record_error, &resolve, &limited, &inline, &skip, &skipped_dirs, &cancel);
+        let (per_root, root_of) = (&per_root, &root_of);
+        Box::new(move |res| {
+            if aborted.load(Ordering::Relaxed) || limited.load(Ordering::Relaxed) || cancel.is_cancelled() { return WalkState::Quit; }
+            let e = match res {
+                Ok(e) => e,
+                Err(err) => {
+                    record_error(&errors, ScanError { path: error_path(&err), message: err.to_string() });
+                    return WalkState::Continue;
+                }
+            };
+            if e.depth() > 0 && !skip.is_empty() && skip.contains(e.path()) { return WalkState::Skip; }
+            if let Some(ft) = e.file_type() {
+                progress.walked(e.path(), ft.is_dir(), ft.is_symlink());
+                if !per_root.is_empty() {
+                    let kind = if ft.is_dir() { Some(0) } else if ft.is_file() { Some(1) } else if ft.is_symlink() { Some(2) } else { None };
+                    if let (Some(k), Some(r)) = (kind, root_of(e.path())) { per_root[r][k].fetch_add(1, Ordering::Relaxed); }
+                }
+                if ft.is_dir() {
+                    dir_count.fetch_add(1, Ordering::Relaxed);
+                    if opts.record_dirs {
+                        if let Ok(t) = e.metadata().and_then(|m| m.modified().map_err(Into::into)) {
+                            if let Ok(mut v) = dir_mtimes.lock() { v.push((e.path().to_path_buf(), t)); }
+                        }
+                    }
+                    // Counted and its mtime kept (the cache must notice it shrink), but not descended into
+                    if oversized(opts, &e) {
+                        if let Ok(mut v) = skipped_dirs.lock() { v.push(e.into_path()); }
+                        return WalkState::Skip;
+                    }
+                } else if ft.is_file() {
+                    file_count.fetch_add(1, Ordering::Relaxed);
+                    if !copy_sizes.is_empty() && e.metadata().is_ok_and(|m| copy_sizes.contains(&m.len())) {
+                        if let Ok(mut v) = copy_candidates.lock() { v.push(e.path().to_path_buf()); }
+                    }
+                    if !hardlink_ids.is_empty() {
+                        let id = e.metadata().ok().as_ref().and_then(identity);
+                        if let Some(&target) = id.and_then(|id| hardlink_ids.get(&id)) {
+                            if let Ok(mut v) = hardlinks.lock() { v.push(HardLink { path: e.path().to_path_buf(), target }); }
+                        }
+                    }
+                }
+                if ft.is_symlink() {
+                    // Resolved as found, so the walk can stop at `--max-count` and priority matches come first
+                    if inline.load(Ordering::Relaxed) {
+                        symlink_count.fetch_add(1, Ordering::Relaxed);
+                        resolve(&e.into_path(), None);
+                    } else if let Ok(mut v) = entries.lock() {
+                        v.push(e.into_path());
+                    }
+                }
+            }
+            WalkState::Continue
+        })
+    };
+    if !first.is_empty() {
+        walk_roots(&opts.walk, first, boundaries.clone(), &visit);
+        inline.store(opts.max_count.is_some(), Ordering::Relaxed);
+    }
+    walk_roots(&opts.walk, &rest, boundaries.clone(), &visit);
+
+    let walk_time = walk_start.elapsed();
+    if let Some(b) = boundaries.filter(|_| opts.walk.list_boundaries) {
+        let mut stops = std::mem::take(&mut *b.stops.lock().unwrap());
+        stops.sort_by(|a, b| a.path.cmp(&b.path));
+        if stops.is_empty() { eprintln!("boundary: none; the walk did not leave the root's filesystem"); }
+        for s in stops { eprintln!("boundary: {}  {}", s.path.display(), s.reason); }
+    }
+    // The walker's closures are done with it; take the list rather than copying it
+    let entries = std::mem::take(&mut *entries.lock().unwrap());
+    let total = entries.len() + symlink_count.load(Ordering::Relaxed);
+
+    // Determinate progress for resolving symlinks
+    progress.phase(Phase::Resolving { total: entries.len() });
+
+    // Parallel resolve
+    let resolve_start = Instant::now();
+    let (farms, singles) = farm_units(&entries);
+    singles.par_iter().with_min_len(opts.resolve_batch.unwrap_or(1)).for_each(|p| resolve(p, None));
+    farms.par_iter().for_each(|unit| {
+        let real_dir = unit[0].parent().and_then(|d| realpath(d).ok());
+        for p in unit { resolve(p, real_dir.as_deref()); }
+    });
+    let resolve_time = resolve_start.elapsed();
+    let abandoned = timeout::take();
+    progress.suspend(&mut || timeout::log(&abandoned));
+    for (p, message) in abandoned { record_error(&errors, ScanError { path: Some(p), message }); }
+
+    let copies = find_copies(&copy_candidates.lock().unwrap(), targets);
+    // The target's own name is not another link to it
+    let mut hardlinks: Vec<HardLink> = std::mem::take(&mut *hardlinks.lock().unwrap()).into_iter()
+        .filter(|h| realpath(&h.path).ok() != targets[h.target].resolved)
+        .collect();
+    hardlinks.sort_by(|a, b| a.path.cmp(&b.path));
+
+    progress.phase(Phase::Finished);
+
+    let mut matches = matches_out.into_inner().unwrap();
+    matches.sort_by_path();
+    let mut links = links_out.into_inner().unwrap();
+    links.sort_by(|a, b| a.path.cmp(&b.path));
+    let errors = std::mem::take(&mut *errors.lock().unwrap());
+    let dir_mtimes = std::mem::take(&mut *dir_mtimes.lock().unwrap());
+    let mut skipped_dirs = skipped_dirs.into_inner().unwrap();
+    skipped_dirs.sort();
+    let roots = per_root.iter().zip(roots)
+        .enumerate()
+        .map(|(i, ([d, f, s], root))| RootStats {
+            root: root.clone(),
+            dirs: d.load(Ordering::Relaxed),
+            files: f.load(Ordering::Relaxed),
+            symlinks: s.load(Ordering::Relaxed),
+            matches: matches.iter().filter(|m| root_of(&m.path) == Some(i)).count(),
+        })
+        .collect();
+    Ok(ScanResult {
+        dirs: dir_count.load(Ordering::Relaxed),
+        files: file_count.load(Ordering::Relaxed),
+        symlinks: total,
+        matches,
+        copies,
+        hardlinks,
+        links,
+        errors,
+        dir_mtimes,
+        walk_time,
+        resolve_time,
+        aborted: aborted.load(Ordering::Relaxed) || cancel.reason().is_some(),
+        cancelled: cancel.reason(),
+        limited: limited.load(Ordering::Relaxed),
+        roots,
+        skipped_dirs,
+        retries: retries.into_inner(),
+    })
+}
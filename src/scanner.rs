@@ -0,0 +1,53 @@
+//! The library's way into a scan: `find`'s walk and match, without its output or changes.
+//!
+//! A `Scanner` takes the same arguments as `find` (TARGETs, `--root`, `--under`, `--broken`, the
+//! ignore and walk options, ...) and runs `scan::scan` with them. Its `Cancel` stops a running scan
+//! from another thread, as Ctrl-C stops the command line: the scan returns within one filesystem
+//! call with what it found so far, `aborted` and `cancelled` set. `run_with_hooks` reports progress
+//! to a `Hooks`, as the CLI's progress bars get it.
+
+use std::ffi::OsString;
+
+use anyhow::Result;
+
+use crate::cancel::Cancel;
+use crate::cli::{self, ScanOpts};
+use crate::hooks::{Hooks, Quiet};
+use crate::scan::{self, Match, ScanResult, Target};
+
+/// A configured scan; run it as often as needed.
+#[derive(Debug)]
+pub struct Scanner {
+    opts: ScanOpts,
+}
+
+impl Scanner {
+    /// A scan configured by `find` arguments, without the program name or subcommand:
+    /// `Scanner::new(["--root", "/srv", "--under", "/opt/app"])`.
+    pub fn new<I, T>(args: I) -> Result<Scanner>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Ok(Scanner { opts: cli::parse_scan(args)? })
+    }
+
+    /// Stop the scan when `cancel` (or a clone of it) is cancelled.
+    pub fn cancel(mut self, cancel: Cancel) -> Scanner {
+        self.opts.walk.cancel = cancel;
+        self
+    }
+
+    /// Resolve the targets and walk the roots, calling `on_match` (from the worker threads) for each
+    /// match as it is confirmed. `Match::target` indexes the returned targets.
+    pub fn run(&self, on_match: impl Fn(&Match) + Sync) -> Result<(Vec<Target>, ScanResult)> {
+        self.run_with_hooks(&Quiet, on_match)
+    }
+
+    /// `run`, telling `hooks` about each phase, walked entry, resolved link, and match on the way.
+    pub fn run_with_hooks(&self, hooks: &dyn Hooks, on_match: impl Fn(&Match) + Sync) -> Result<(Vec<Target>, ScanResult)> {
+        let targets = scan::resolve_targets(&self.opts)?;
+        let result = scan::scan(&self.opts, &targets, hooks, &on_match)?;
+        Ok((targets, result))
+    }
+}
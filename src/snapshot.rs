@@ -0,0 +1,176 @@
+//! `--save-snapshot` / `--changed-since`: incremental scans driven by directory mtimes.
+//!
+//! A snapshot records every walked directory's mtime and the matches found. A
+//! later run re-lists only directories whose mtime changed (adding, removing, or
+//! replacing a link always touches its parent) plus directories that are new,
+//! and keeps the recorded matches everywhere else.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::ScanOpts;
+use crate::scan::{self, ScanResult, Target};
+
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    /// One key per target (canonical path, or the argument when unresolved; `/**` appended with `--under`); must match on reuse
+    targets: Vec<String>,
+    /// Directory -> mtime in nanoseconds since the epoch
+    dirs: BTreeMap<PathBuf, u64>,
+    matches: Vec<SnapMatch>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SnapMatch {
+    pub path: PathBuf,
+    pub target: usize,
+    pub broken: bool,
+    pub link_text: Option<PathBuf>,
+}
+
+/// Matches that appeared, disappeared, or now point elsewhere since the snapshot.
+pub struct Diff {
+    pub added: Vec<SnapMatch>,
+    pub removed: Vec<SnapMatch>,
+    /// (before, after)
+    pub retargeted: Vec<(SnapMatch, SnapMatch)>,
+    pub dirs_checked: usize,
+    pub dirs_rescanned: usize,
+}
+
+pub(crate) fn mtime_ns(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+}
+
+pub(crate) fn target_keys(targets: &[Target]) -> Vec<String> {
+    targets.iter()
+        .map(|t| {
+            let key = t.resolved.as_ref().map_or_else(|| t.given.clone(), |r| r.to_string_lossy().into_owned());
+            if t.under { key + "/**" } else { key }
+        })
+        .collect()
+}
+
+fn parent(p: &Path) -> &Path {
+    p.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+}
+
+impl Snapshot {
+    /// Snapshot a full scan (run with `record_dirs` and `record_links`).
+    pub fn from_scan(targets: &[Target], result: &ScanResult) -> Snapshot {
+        let link_text = |p: &Path| {
+            let i = result.links.binary_search_by(|l| l.path.as_path().cmp(p)).ok()?;
+            result.links[i].link_text.clone()
+        };
+        Snapshot {
+            version: VERSION,
+            targets: target_keys(targets),
+            dirs: result.dir_mtimes.iter().map(|(d, t)| (d.clone(), mtime_ns(*t))).collect(),
+            matches: result.matches.iter()
+                .map(|m| SnapMatch { path: m.path.clone(), target: m.target, broken: m.broken, link_text: link_text(&m.path) })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path, targets: &[Target]) -> Result<Snapshot> {
+        let text = fs::read(path).with_context(|| format!("read snapshot {}", path.display()))?;
+        let snap: Snapshot = serde_json::from_slice(&text).with_context(|| format!("parse snapshot {}", path.display()))?;
+        if snap.version != VERSION { anyhow::bail!("snapshot {} has unsupported version {}", path.display(), snap.version); }
+        if snap.targets != target_keys(targets) {
+            anyhow::bail!("snapshot {} was saved for different targets ({})", path.display(), snap.targets.join(", "));
+        }
+        Ok(snap)
+    }
+
+    /// Write to `path` via a temporary file, so an interrupted run keeps the old snapshot.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?).with_context(|| format!("write {}", path.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Bring the snapshot up to date, re-listing only changed and new directories.
+    pub fn update(&self, opts: &ScanOpts, targets: &[Target]) -> Result<(Snapshot, Diff)> {
+        let mut dirs = BTreeMap::new();
+        let mut rescan: HashSet<PathBuf> = HashSet::new();
+        for (d, &old) in &self.dirs {
+            // Gone (or replaced by a non-directory): its matches are dropped below
+            let Ok(m) = fs::symlink_metadata(d) else { continue };
+            if !m.is_dir() { continue; }
+            let now = m.modified().map_or(0, mtime_ns);
+            dirs.insert(d.clone(), now);
+            if now != old { rescan.insert(d.clone()); }
+        }
+
+        // List changed directories one level deep; directories not seen before are walked fully
+        let mut links = Vec::new();
+        let mut new_dirs = Vec::new();
+        for d in &rescan {
+            if opts.walk.max_depth.is_some_and(|m| scan::depth(d) >= m) { continue; }
+            let mut wb = scan::build_subtree_walker(&opts.walk, d);
+            wb.max_depth(Some(1));
+            for e in wb.build().flatten().filter(|e| e.depth() > 0) {
+                let Some(ft) = e.file_type() else { continue };
+                if ft.is_symlink() { links.push(e.into_path()); }
+                else if ft.is_dir() && !self.dirs.contains_key(e.path()) { new_dirs.push(e.into_path()); }
+            }
+        }
+        for d in new_dirs {
+            let mut wb = scan::build_subtree_walker(&opts.walk, &d);
+            if let Some(m) = opts.walk.max_depth { wb.max_depth(Some(m.saturating_sub(scan::depth(&d)))); }
+            for e in wb.build().flatten() {
+                let Some(ft) = e.file_type() else { continue };
+                if ft.is_symlink() { links.push(e.into_path()); }
+                else if ft.is_dir() {
+                    let t = e.metadata().ok().and_then(|m| m.modified().ok()).map_or(0, mtime_ns);
+                    dirs.insert(e.path().to_path_buf(), t);
+                    rescan.insert(e.into_path());
+                }
+            }
+        }
+
+        let has_patterns = scan::has_patterns(targets);
+        let ids = scan::target_ids(targets);
+        let fresh: Vec<SnapMatch> = links.par_iter()
+            .filter_map(|p| {
+                let (target, broken) = scan::match_symlink(opts, p, targets, &ids, has_patterns)?;
+                Some(SnapMatch { path: p.clone(), target, broken, link_text: fs::read_link(p).ok() })
+            })
+            .collect();
+        let mut matches: Vec<SnapMatch> = self.matches.iter()
+            .filter(|m| dirs.contains_key(parent(&m.path)) && !rescan.contains(parent(&m.path)))
+            .cloned()
+            .chain(fresh)
+            .collect();
+        matches.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let before: HashMap<&Path, &SnapMatch> = self.matches.iter().map(|m| (m.path.as_path(), m)).collect();
+        let after: HashMap<&Path, &SnapMatch> = matches.iter().map(|m| (m.path.as_path(), m)).collect();
+        let mut diff = Diff {
+            added: matches.iter().filter(|m| !before.contains_key(m.path.as_path())).cloned().collect(),
+            removed: self.matches.iter().filter(|m| !after.contains_key(m.path.as_path())).cloned().collect(),
+            retargeted: matches.iter()
+                .filter_map(|m| {
+                    let old = before.get(m.path.as_path())?;
+                    (old.target != m.target || old.link_text != m.link_text).then(|| ((*old).clone(), m.clone()))
+                })
+                .collect(),
+            dirs_checked: dirs.len(),
+            dirs_rescanned: rescan.len(),
+        };
+        diff.removed.sort_by(|a, b| a.path.cmp(&b.path));
+        let snap = Snapshot { version: VERSION, targets: self.targets.clone(), dirs, matches };
+        Ok((snap, diff))
+    }
+}
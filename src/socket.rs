@@ -0,0 +1,430 @@
+//! `index watch --socket`, `daemon`, and `client`: JSON queries against the live index over a Unix socket.
+//!
+//! Requests and responses are one JSON object per line. A connection may send any number of
+//! queries; after `subscribe` it only receives change events. Queries page by path: a response
+//! cut short by `limit` carries `next`, which the following request passes as `cursor`.
+//! `delete` and `relink` change links under the watched roots, for peers the `[socket]` config allows.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{ClientOpts, ClientRequest};
+use crate::daemon::MemIndex;
+use crate::scan;
+
+/// `<data dir>/find-symlinks/index.sock`
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("find-symlinks").join("index.sock"))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Request {
+    #[serde(flatten)]
+    pub query: Query,
+    /// At most this many links per response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Only links after this path: the previous response's `next`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Query {
+    Target {
+        path: PathBuf,
+        #[serde(default)]
+        under: bool,
+    },
+    Prefix { path: PathBuf },
+    Glob { pattern: String },
+    Subscribe,
+    /// Remove the symlink at `path`
+    Delete { path: PathBuf },
+    /// Point the symlink at `path` to `to` (its new link text), replacing it atomically
+    Relink { path: PathBuf, to: PathBuf },
+}
+
+impl Query {
+    /// Whether the request changes the filesystem, and so needs a `write_uids` peer.
+    fn mutates(&self) -> bool {
+        matches!(self, Query::Delete { .. } | Query::Relink { .. })
+    }
+
+    /// The request's `op`, as the client spelled it.
+    fn op(&self) -> String {
+        serde_json::to_value(self).ok().and_then(|v| v["op"].as_str().map(String::from)).unwrap_or_default()
+    }
+}
+
+/// Where queries are answered from.
+pub enum Source {
+    /// The index database of `index watch`, opened per query so a rebuild is picked up
+    Db(PathBuf),
+    /// The in-memory index of `daemon`
+    Memory(Arc<RwLock<MemIndex>>),
+}
+
+/// One `symlinks` row.
+#[derive(Serialize)]
+struct Link {
+    path: String,
+    link_text: Option<String>,
+    resolved: Option<String>,
+    error: Option<String>,
+}
+
+#[cfg(unix)]
+pub use unix::Server;
+
+#[cfg(unix)]
+mod unix {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::{Context, Result};
+    use globset::GlobBuilder;
+    use rusqlite::{named_params, Connection, OpenFlags};
+    use serde_json::json;
+
+    use super::{Link, Query, Request, Source};
+    use crate::config::SocketConfig;
+    use crate::daemon::MemIndex;
+    use crate::scan;
+
+    /// How long a subscriber may leave an event unread before it is dropped.
+    const SUBSCRIBER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+    /// What every connection's thread shares.
+    struct Shared {
+        source: Source,
+        /// The watched roots, canonical, as resolved link parents are compared with them
+        roots: Vec<PathBuf>,
+        roles: SocketConfig,
+        /// The watcher's effective UID, always allowed
+        own_uid: u32,
+        /// Each with a write timeout of `SUBSCRIBER_TIMEOUT`, so one that stops reading cannot stall the watcher
+        subscribers: Mutex<Vec<UnixStream>>,
+    }
+
+    /// The connecting process's UID, from the kernel rather than anything the client says.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn peer_uid(s: &UnixStream) -> Option<u32> {
+        use std::os::fd::AsRawFd;
+        let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: `cred` and `len` are valid for writes and sized for SO_PEERCRED
+        let rc = unsafe { libc::getsockopt(s.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, (&raw mut cred).cast(), &mut len) };
+        (rc == 0).then_some(cred.uid)
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly"))]
+    fn peer_uid(s: &UnixStream) -> Option<u32> {
+        use std::os::fd::AsRawFd;
+        let (mut uid, mut gid) = (0, 0);
+        // SAFETY: both out-pointers are valid for writes
+        let rc = unsafe { libc::getpeereid(s.as_raw_fd(), &mut uid, &mut gid) };
+        (rc == 0).then_some(uid)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly")))]
+    fn peer_uid(_s: &UnixStream) -> Option<u32> {
+        None
+    }
+
+    impl Shared {
+        /// Whether a peer may send `q`; an unknown UID may only query, and only when queries are open to all.
+        fn allows(&self, uid: Option<u32>, q: &Query) -> bool {
+            match uid {
+                Some(u) if u == self.own_uid => true,
+                Some(u) if q.mutates() => self.roles.write_uids.contains(&u),
+                Some(u) => self.roles.query_uids.as_ref().is_none_or(|q| q.contains(&u)),
+                None => !q.mutates() && self.roles.query_uids.is_none(),
+            }
+        }
+
+        /// `delete` and `relink`, limited to symlinks under the watched roots.
+        fn mutate(&self, q: &Query) -> Result<serde_json::Value> {
+            let (Query::Delete { path } | Query::Relink { path, .. }) = q else { anyhow::bail!("not a change request") };
+            if !path.is_absolute() { anyhow::bail!("path must be absolute: {}", path.display()); }
+            // Judged by where the link's directory really is, so a symlinked directory on the way
+            // cannot lead outside the roots; the link itself is then named through that directory
+            let path = scan::lexical_absolute(path);
+            let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else { anyhow::bail!("{} is not a symlink", path.display()) };
+            let dir = scan::realpath(dir).with_context(|| format!("resolve {}", dir.display()))?;
+            if !self.roots.iter().any(|r| dir.starts_with(r)) { anyhow::bail!("{} is not under a watched root", path.display()); }
+            let path = dir.join(name);
+            if !std::fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink()) { anyhow::bail!("{} is not a symlink", path.display()); }
+            match q {
+                Query::Relink { to, .. } => {
+                    crate::actions::relink(&path, to).with_context(|| format!("replace {}", path.display()))?;
+                    Ok(json!({ "ok": true, "relinked": path.to_string_lossy(), "to": to.to_string_lossy() }))
+                }
+                _ => {
+                    std::fs::remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+                    Ok(json!({ "ok": true, "deleted": path.to_string_lossy() }))
+                }
+            }
+        }
+    }
+
+    /// Relative paths would be taken from the watcher's working directory, not the client's.
+    fn absolute(p: &Path) -> Result<()> {
+        if !p.is_absolute() { anyhow::bail!("path must be absolute: {}", p.display()); }
+        Ok(())
+    }
+
+    /// `key/`, without doubling the slash of `/`.
+    fn below(key: &str) -> String {
+        if key.ends_with('/') { key.to_string() } else { format!("{}/", key) }
+    }
+
+    /// The key a `target` query looks up: the index stores canonical resolutions.
+    fn target_key(path: &Path) -> String {
+        scan::realpath(path).unwrap_or_else(|_| scan::lexical_absolute(path)).to_string_lossy().into_owned()
+    }
+
+    /// Cut `links` (read one past the limit) to a page, with the cursor for the next one if there is more.
+    fn page(mut links: Vec<Link>, limit: Option<usize>) -> (Vec<Link>, Option<String>) {
+        let next = match limit {
+            Some(n) if links.len() > n => {
+                links.truncate(n);
+                links.last().map(|l| l.path.clone())
+            }
+            _ => None,
+        };
+        (links, next)
+    }
+
+    fn glob(pattern: &str) -> Result<globset::GlobMatcher> {
+        Ok(GlobBuilder::new(pattern).literal_separator(true).build().context("invalid glob")?.compile_matcher())
+    }
+
+    /// One page of rows matching a query, sorted by path, and the cursor for the next page if there is one.
+    fn query(source: &Source, req: &Request) -> Result<(Vec<Link>, Option<String>)> {
+        match source {
+            Source::Db(db) => query_db(db, req),
+            Source::Memory(index) => query_memory(&*index.read().map_err(|_| anyhow::anyhow!("index lock poisoned"))?, req),
+        }
+    }
+
+    /// `query` against the index database, opened per query so a rebuild is picked up.
+    fn query_db(db: &Path, req: &Request) -> Result<(Vec<Link>, Option<String>)> {
+        let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY).with_context(|| format!("open index {}", db.display()))?;
+        let text = |p: PathBuf| p.to_string_lossy().into_owned();
+        let row = |r: &rusqlite::Row| Ok(Link { path: r.get(0)?, link_text: r.get(1)?, resolved: r.get(2)?, error: r.get(3)? });
+        // Every query keeps to rows after the cursor and reads one past the limit, to know whether there is more
+        const COLUMNS: &str = "SELECT path, link_text, resolved, error FROM symlinks WHERE (:cursor IS NULL OR path > :cursor) AND";
+        let cursor = req.cursor.as_deref();
+        let take = req.limit.map_or(usize::MAX, |n| n.saturating_add(1));
+        let sql_limit = i64::try_from(take).unwrap_or(-1);
+        let links = match &req.query {
+            Query::Target { path, under } => {
+                absolute(path)?;
+                let key = target_key(path);
+                let mut st = conn.prepare(&format!("{} (resolved = :key OR (:under AND substr(resolved, 1, length(:below)) = :below)) ORDER BY path LIMIT :limit", COLUMNS))?;
+                let rows = st.query_map(named_params! { ":key": key, ":below": below(&key), ":under": under, ":limit": sql_limit, ":cursor": cursor }, row)?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()?
+            }
+            Query::Prefix { path } => {
+                absolute(path)?;
+                let key = text(scan::lexical_absolute(path));
+                let mut st = conn.prepare(&format!("{} (path = :key OR substr(path, 1, length(:below)) = :below) ORDER BY path LIMIT :limit", COLUMNS))?;
+                let rows = st.query_map(named_params! { ":key": key, ":below": below(&key), ":limit": sql_limit, ":cursor": cursor }, row)?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()?
+            }
+            Query::Glob { pattern } => {
+                let glob = glob(pattern)?;
+                let mut st = conn.prepare(&format!("{} 1 ORDER BY path", COLUMNS))?;
+                let rows = st.query_map(named_params! { ":cursor": cursor }, row)?;
+                rows.filter(|l| l.as_ref().map_or(true, |l| glob.is_match(&l.path))).take(take).collect::<rusqlite::Result<Vec<_>>>()?
+            }
+            Query::Subscribe | Query::Delete { .. } | Query::Relink { .. } => Vec::new(),
+        };
+        Ok(page(links, req.limit))
+    }
+
+    /// `query` against the daemon's in-memory index.
+    fn query_memory(index: &MemIndex, req: &Request) -> Result<(Vec<Link>, Option<String>)> {
+        let cursor = req.cursor.as_deref();
+        let after = |p: &&str| cursor.is_none_or(|c| *p > c);
+        let take = req.limit.map_or(usize::MAX, |n| n.saturating_add(1));
+        let link = |p: &str| index.get(p).map(|e| Link { path: p.to_string(), link_text: e.link_text.clone(), resolved: e.resolved.clone(), error: e.error.clone() });
+        let paths: Vec<&str> = match &req.query {
+            Query::Target { path, under } => {
+                absolute(path)?;
+                index.pointing_at(&target_key(path), *under).into_iter().filter(after).take(take).collect()
+            }
+            Query::Prefix { path } => {
+                absolute(path)?;
+                index.at_or_below(&scan::lexical_absolute(path).to_string_lossy()).filter(after).take(take).collect()
+            }
+            Query::Glob { pattern } => {
+                let glob = glob(pattern)?;
+                index.paths().filter(after).filter(|p| glob.is_match(p)).take(take).collect()
+            }
+            Query::Subscribe | Query::Delete { .. } | Query::Relink { .. } => Vec::new(),
+        };
+        Ok(page(paths.into_iter().filter_map(link).collect(), req.limit))
+    }
+
+    /// Answer one connection's requests until it closes or subscribes.
+    fn serve(stream: UnixStream, shared: &Shared) {
+        let Ok(read) = stream.try_clone() else { return };
+        let uid = peer_uid(&stream);
+        let mut out = stream;
+        for line in BufReader::new(read).lines() {
+            let Ok(line) = line else { return };
+            if line.trim().is_empty() { continue; }
+            let failed = |e: anyhow::Error| json!({ "ok": false, "error": format!("{:#}", e) });
+            let resp = match serde_json::from_str::<Request>(&line) {
+                Err(e) => json!({ "ok": false, "error": format!("bad request: {}", e) }),
+                Ok(req) if !shared.allows(uid, &req.query) => {
+                    let who = uid.map_or("an unidentified client".to_string(), |u| format!("uid {}", u));
+                    json!({ "ok": false, "error": format!("permission denied: {} may not {}", who, req.query.op()) })
+                }
+                Ok(Request { query: Query::Subscribe, .. }) => {
+                    if writeln!(out, "{}", json!({ "ok": true, "subscribed": true })).is_ok() && out.set_write_timeout(Some(SUBSCRIBER_TIMEOUT)).is_ok() {
+                        if let Ok(mut v) = shared.subscribers.lock() { v.push(out); }
+                    }
+                    return;
+                }
+                Ok(req) if req.query.mutates() => shared.mutate(&req.query).unwrap_or_else(failed),
+                Ok(req) => match query(&shared.source, &req) {
+                    Ok((links, next)) => json!({ "ok": true, "links": links, "next": next }),
+                    Err(e) => failed(e),
+                },
+            };
+            if writeln!(out, "{}", resp).is_err() { return; }
+        }
+    }
+
+    /// The listening socket of `index watch` or `daemon`; removed when dropped.
+    pub struct Server {
+        path: PathBuf,
+        shared: Arc<Shared>,
+    }
+
+    impl Server {
+        /// Listen on `path` and answer queries against `source` (and changes under `roots`) on a thread per connection.
+        pub fn start(path: &Path, source: Source, roots: &[PathBuf], roles: &SocketConfig) -> Result<Server> {
+            if UnixStream::connect(path).is_ok() { anyhow::bail!("{} is already being served", path.display()); }
+            // Left behind by a watcher that was killed
+            let _ = std::fs::remove_file(path);
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+            }
+            let listener = UnixListener::bind(path).with_context(|| format!("listen on {}", path.display()))?;
+            // SAFETY: geteuid cannot fail
+            let own_uid = unsafe { libc::geteuid() };
+            // Peers named in the config must be able to connect whatever the umask; `allows` decides what they may do
+            if roles.query_uids.iter().flatten().chain(&roles.write_uids).any(|&u| u != own_uid) {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o666))
+                    .with_context(|| format!("set permissions on {}", path.display()))?;
+            }
+            let shared = Arc::new(Shared {
+                source,
+                roots: roots.iter().map(|r| scan::realpath(r).unwrap_or_else(|_| scan::lexical_absolute(r))).collect(),
+                roles: roles.clone(),
+                own_uid,
+                subscribers: Mutex::new(Vec::new()),
+            });
+            let accept = Arc::clone(&shared);
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let shared = Arc::clone(&accept);
+                    std::thread::spawn(move || serve(stream, &shared));
+                }
+            });
+            Ok(Server { path: path.to_path_buf(), shared })
+        }
+
+        /// Send `event` to every subscriber, dropping those that have gone away or that take longer
+        /// than `SUBSCRIBER_TIMEOUT` to read it.
+        pub fn publish(&self, event: &serde_json::Value) {
+            let line = format!("{}\n", event);
+            if let Ok(mut v) = self.shared.subscribers.lock() { v.retain_mut(|s| s.write_all(line.as_bytes()).is_ok()); }
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// Send `req` and print the response lines (with `follow`, one per page until the last); false if a response reports an error.
+    pub fn send(socket: &Path, mut req: Request, follow: bool) -> Result<bool> {
+        let stream = UnixStream::connect(socket)
+            .with_context(|| format!("connect to {} (is `find-symlinks index watch --socket` or `find-symlinks daemon` running?)", socket.display()))?;
+        let subscribe = matches!(req.query, Query::Subscribe);
+        let mut out = stream.try_clone()?;
+        writeln!(out, "{}", serde_json::to_string(&req)?)?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            println!("{}", line);
+            let resp: serde_json::Value = serde_json::from_str(&line).unwrap_or_default();
+            if resp["ok"] == false { return Ok(false); }
+            if subscribe { continue; }
+            match resp["next"].as_str().filter(|_| follow) {
+                Some(next) => {
+                    req.cursor = Some(next.to_string());
+                    writeln!(out, "{}", serde_json::to_string(&req)?)?;
+                }
+                None => break,
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(not(unix))]
+pub struct Server;
+
+#[cfg(not(unix))]
+impl Server {
+    pub fn start(_path: &Path, _source: Source, _roots: &[PathBuf], _roles: &crate::config::SocketConfig) -> Result<Server> {
+        anyhow::bail!("--socket needs Unix domain sockets")
+    }
+
+    pub fn publish(&self, _event: &serde_json::Value) {}
+}
+
+pub fn client(opts: ClientOpts) -> Result<()> {
+    let socket = match opts.socket.or_else(default_path) {
+        Some(p) => p,
+        None => anyhow::bail!("no data directory on this platform; pass --socket"),
+    };
+    // Paths are sent absolute, as the watcher sees them
+    let query = match opts.request {
+        ClientRequest::Target { path, under } => Query::Target { path: scan::lexical_absolute(&path), under },
+        ClientRequest::Prefix { path } => Query::Prefix { path: scan::lexical_absolute(&path) },
+        ClientRequest::Glob { pattern } => Query::Glob { pattern },
+        ClientRequest::Subscribe => Query::Subscribe,
+        ClientRequest::Delete { path } => Query::Delete { path: scan::lexical_absolute(&path) },
+        ClientRequest::Relink { path, to } => Query::Relink { path: scan::lexical_absolute(&path), to },
+        ClientRequest::Raw { request } => {
+            let req: Request = serde_json::from_str(&request).map_err(|e| anyhow::anyhow!("bad request: {}", e))?;
+            return send(&socket, req, opts.follow);
+        }
+    };
+    send(&socket, Request { query, limit: opts.limit, cursor: opts.cursor }, opts.follow)
+}
+
+#[cfg(unix)]
+fn send(socket: &Path, req: Request, follow: bool) -> Result<()> {
+    if !unix::send(socket, req, follow)? { std::process::exit(1); }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &Path, _req: Request, _follow: bool) -> Result<()> {
+    anyhow::bail!("client needs Unix domain sockets")
+}
@@ -0,0 +1,110 @@
+//! `--export-sqlite`: write a scan's results into a SQLite database for ad-hoc SQL.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::scan::{lexical_absolute, realpath, ScanResult, Target};
+
+pub(crate) const SCHEMA: &str = "
+CREATE TABLE targets (
+    id       INTEGER PRIMARY KEY,
+    given    TEXT NOT NULL,
+    resolved TEXT
+);
+CREATE TABLE symlinks (
+    path      TEXT PRIMARY KEY,
+    link_text TEXT,
+    resolved  TEXT,
+    error     TEXT
+);
+CREATE TABLE matches (
+    path      TEXT NOT NULL,
+    target_id INTEGER NOT NULL REFERENCES targets(id),
+    broken    INTEGER NOT NULL
+);
+CREATE TABLE copies (
+    path      TEXT NOT NULL,
+    target_id INTEGER NOT NULL REFERENCES targets(id)
+);
+CREATE TABLE hardlinks (
+    path      TEXT NOT NULL,
+    target_id INTEGER NOT NULL REFERENCES targets(id)
+);
+CREATE TABLE errors (
+    path    TEXT,
+    message TEXT NOT NULL
+);
+CREATE TABLE stats (
+    key   TEXT PRIMARY KEY,
+    value
+);
+CREATE INDEX symlinks_resolved ON symlinks(resolved);
+CREATE INDEX matches_path ON matches(path);
+CREATE INDEX matches_target ON matches(target_id);
+CREATE INDEX copies_target ON copies(target_id);
+";
+
+/// Write `result` to a fresh database at `path`, replacing it atomically.
+///
+/// With `absolute_paths`, walked paths are stored absolute instead of relative to the working directory.
+pub fn export(path: &Path, roots: &[PathBuf], targets: &[Target], result: &ScanResult, elapsed_secs: f64, absolute_paths: bool) -> Result<()> {
+    let text = |p: &Path| -> String {
+        if absolute_paths { lexical_absolute(p).to_string_lossy().into_owned() } else { p.to_string_lossy().into_owned() }
+    };
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("sqlite.tmp");
+    let _ = fs::remove_file(&tmp);
+    let mut db = Connection::open(&tmp).with_context(|| format!("create {}", tmp.display()))?;
+    let tx = db.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+    {
+        let mut st = tx.prepare("INSERT INTO targets (id, given, resolved) VALUES (?1, ?2, ?3)")?;
+        for (i, t) in targets.iter().enumerate() {
+            st.execute(params![i as i64, t.given, t.resolved.as_deref().map(text)])?;
+        }
+        let mut st = tx.prepare("INSERT INTO symlinks (path, link_text, resolved, error) VALUES (?1, ?2, ?3, ?4)")?;
+        for l in &result.links {
+            // Link text is stored as written (`../t` stays relative); only paths are made absolute
+            st.execute(params![text(&l.path), l.link_text.as_deref().map(|t| t.to_string_lossy().into_owned()), l.resolved.as_deref().map(text), l.error])?;
+        }
+        let mut st = tx.prepare("INSERT INTO matches (path, target_id, broken) VALUES (?1, ?2, ?3)")?;
+        for m in &result.matches {
+            st.execute(params![text(&m.path), m.target as i64, m.broken])?;
+        }
+        let mut st = tx.prepare("INSERT INTO copies (path, target_id) VALUES (?1, ?2)")?;
+        for c in &result.copies {
+            st.execute(params![text(&c.path), c.target as i64])?;
+        }
+        let mut st = tx.prepare("INSERT INTO hardlinks (path, target_id) VALUES (?1, ?2)")?;
+        for h in &result.hardlinks {
+            st.execute(params![text(&h.path), h.target as i64])?;
+        }
+        let mut st = tx.prepare("INSERT INTO errors (path, message) VALUES (?1, ?2)")?;
+        for e in &result.errors {
+            st.execute(params![e.path.as_deref().map(text), e.message])?;
+        }
+        let mut st = tx.prepare("INSERT INTO stats (key, value) VALUES (?1, ?2)")?;
+        st.execute(params!["folders", result.dirs as i64])?;
+        st.execute(params!["files", result.files as i64])?;
+        st.execute(params!["symlinks", result.symlinks as i64])?;
+        st.execute(params!["matches", result.matches.len() as i64])?;
+        st.execute(params!["errors", result.errors.len() as i64])?;
+        st.execute(params!["aborted", result.aborted as i64])?;
+        st.execute(params!["elapsed_secs", elapsed_secs])?;
+        st.execute(params!["walk_secs", result.walk_time.as_secs_f64()])?;
+        st.execute(params!["resolve_secs", result.resolve_time.as_secs_f64()])?;
+        st.execute(params!["cwd", std::env::current_dir().ok().as_deref().map(text)])?;
+        // One line per scan root
+        let roots: Vec<String> = roots.iter().filter_map(|r| realpath(r).ok()).map(|r| text(&r)).collect();
+        st.execute(params!["root", roots.join("\n")])?;
+    }
+    tx.commit()?;
+    db.close().map_err(|(_, e)| e)?;
+    fs::rename(&tmp, path).with_context(|| format!("write {}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! `--template` format strings: one line per match, with `{placeholder}`s filled in.
+//!
+//! `{{` and `}}` are literal braces; `\t`, `\n`, `\0`, and `\\` are escapes, so a shell-quoted
+//! `"{path}\t{target}"` gives tab-separated columns. `{}` is short for `{path}`. The same
+//! placeholders fill in `--exec` arguments.
+
+use std::cell::OnceCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::ScanOpts;
+use crate::scan::{self, Match, Target};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field { Path, Name, Target, MatchedTarget, LinkText, Resolved, Broken, Root, Depth, Mtime, Size, Uid, Gid, Owner, Group }
+
+const FIELDS: &[(&str, Field)] = &[
+    ("path", Field::Path),
+    ("name", Field::Name),
+    ("target", Field::Target),
+    ("matched_target", Field::MatchedTarget),
+    ("link_text", Field::LinkText),
+    ("resolved", Field::Resolved),
+    ("broken", Field::Broken),
+    ("root", Field::Root),
+    ("depth", Field::Depth),
+    ("mtime", Field::Mtime),
+    ("size", Field::Size),
+    ("uid", Field::Uid),
+    ("gid", Field::Gid),
+    ("owner", Field::Owner),
+    ("group", Field::Group),
+];
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Lit(String),
+    Field(Field),
+}
+
+/// A compiled `--template`.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+/// What a match is rendered against.
+pub struct Context<'a> {
+    pub opts: &'a ScanOpts,
+    pub targets: &'a [Target],
+    /// The `--root`s; empty for a manifest, whose links are not on this filesystem
+    pub roots: &'a [PathBuf],
+    pub from_manifest: bool,
+}
+
+impl Template {
+    /// Parse a template; usable as a clap `value_parser`.
+    pub fn parse(src: &str) -> Result<Template, String> {
+        let mut pieces = Vec::new();
+        let mut lit = String::new();
+        let mut chars = src.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => lit.push('\t'),
+                    Some('n') => lit.push('\n'),
+                    Some('0') => lit.push('\0'),
+                    Some('\\') => lit.push('\\'),
+                    Some(o) => { lit.push('\\'); lit.push(o); }
+                    None => lit.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => { chars.next(); lit.push('{'); }
+                '}' if chars.peek() == Some(&'}') => { chars.next(); lit.push('}'); }
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    let Some(f) = field_named(&name) else {
+                        let known: Vec<&str> = FIELDS.iter().map(|(n, _)| *n).collect();
+                        return Err(format!("unknown placeholder {{{}}} (known: {})", name, known.join(", ")));
+                    };
+                    if !lit.is_empty() { pieces.push(Piece::Lit(std::mem::take(&mut lit))); }
+                    pieces.push(Piece::Field(f));
+                }
+                c => lit.push(c),
+            }
+        }
+        if !lit.is_empty() { pieces.push(Piece::Lit(lit)); }
+        Ok(Template { pieces })
+    }
+
+    /// Parse an `--exec` argument: only known `{placeholder}`s are replaced, and backslashes and
+    /// other braces (`awk '{print}'`) are kept as they are.
+    pub fn parse_arg(src: &str) -> Result<Template, String> {
+        let mut pieces = Vec::new();
+        let mut lit = String::new();
+        let mut rest = src;
+        while let Some(open) = rest.find('{') {
+            let field = rest[open + 1..].find('}').and_then(|len| Some((field_named(&rest[open + 1..open + 1 + len])?, open + len + 2)));
+            let Some((f, end)) = field else {
+                lit.push_str(&rest[..=open]);
+                rest = &rest[open + 1..];
+                continue;
+            };
+            lit.push_str(&rest[..open]);
+            if !lit.is_empty() { pieces.push(Piece::Lit(std::mem::take(&mut lit))); }
+            pieces.push(Piece::Field(f));
+            rest = &rest[end..];
+        }
+        lit.push_str(rest);
+        if !lit.is_empty() { pieces.push(Piece::Lit(lit)); }
+        Ok(Template { pieces })
+    }
+
+    /// Whether there is any placeholder in it.
+    pub fn has_fields(&self) -> bool {
+        self.pieces.iter().any(|p| matches!(p, Piece::Field(_)))
+    }
+
+    /// Whether it is nothing but the path, which can then be passed on as it is rather than as text.
+    pub fn is_path(&self) -> bool {
+        matches!(self.pieces.as_slice(), [Piece::Field(Field::Path)])
+    }
+
+    /// The line for `m` (without a terminator); values that are not available are empty.
+    pub fn render(&self, m: &Match, cx: &Context) -> String {
+        let meta = OnceCell::new();
+        let meta = || meta.get_or_init(|| fs::symlink_metadata(&m.path).ok()).as_ref();
+        let t = &cx.targets[m.target];
+        let located = scan::locate(cx.roots, &m.path);
+        let show = |p: &Path| p.to_string_lossy().into_owned();
+        let mut out = String::new();
+        for piece in &self.pieces {
+            let value = match piece {
+                Piece::Lit(s) => { out.push_str(s); continue; }
+                Piece::Field(Field::Path) => show(&m.path),
+                Piece::Field(Field::Name) => m.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                Piece::Field(Field::Target) => show(&scan::filter_target(&m.path, t)),
+                Piece::Field(Field::MatchedTarget) => t.given.clone(),
+                Piece::Field(Field::LinkText) => fs::read_link(&m.path).map(|l| show(&l)).unwrap_or_default(),
+                Piece::Field(Field::Resolved) if m.broken => String::new(),
+                // Manifest links resolve through the listing, as recorded for the target
+                Piece::Field(Field::Resolved) if cx.from_manifest => show(&scan::filter_target(&m.path, t)),
+                Piece::Field(Field::Resolved) => scan::link_destination(cx.opts, &m.path).map(|r| show(&r)).unwrap_or_default(),
+                Piece::Field(Field::Broken) => m.broken.to_string(),
+                Piece::Field(Field::Root) => located.map(|(r, _)| show(r)).unwrap_or_default(),
+                Piece::Field(Field::Depth) => located.map_or_else(|| scan::depth(&m.path), |(_, d)| d).to_string(),
+                Piece::Field(Field::Mtime) => meta()
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs().to_string())
+                    .unwrap_or_default(),
+                Piece::Field(Field::Size) => meta().map(|m| m.len().to_string()).unwrap_or_default(),
+                Piece::Field(f) => owner_field(*f, meta()),
+            };
+            out.push_str(&value);
+        }
+        out
+    }
+}
+
+/// The field `{name}` stands for; `{}` is the path.
+fn field_named(name: &str) -> Option<Field> {
+    if name.is_empty() { return Some(Field::Path); }
+    FIELDS.iter().find(|(n, _)| *n == name).map(|&(_, f)| f)
+}
+
+#[cfg(unix)]
+fn owner_field(f: Field, meta: Option<&fs::Metadata>) -> String {
+    use std::os::unix::fs::MetadataExt;
+    let Some(m) = meta else { return String::new() };
+    match f {
+        Field::Uid => m.uid().to_string(),
+        Field::Gid => m.gid().to_string(),
+        // Fall back to the number for ids with no name
+        Field::Owner => crate::users::user_name(m.uid()).unwrap_or_else(|| m.uid().to_string()),
+        _ => crate::users::group_name(m.gid()).unwrap_or_else(|| m.gid().to_string()),
+    }
+}
+
+#[cfg(not(unix))]
+fn owner_field(_f: Field, _meta: Option<&fs::Metadata>) -> String {
+    String::new()
+}
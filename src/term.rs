@@ -0,0 +1,69 @@
+//! Terminal capability probes and the glyph sets shared by the renderers.
+
+/// Whether the current locale/console can display non-ASCII output.
+///
+/// Unix: the first set of `LC_ALL`, `LC_CTYPE`, `LANG` must name a UTF-8 codeset.
+/// Windows: the console output code page must be UTF-8 (65001); legacy code pages are not.
+pub fn supports_utf8() -> bool {
+    #[cfg(windows)]
+    {
+        // SAFETY: plain query of the attached console's code page; returns 0 without a console.
+        let cp = unsafe { windows_sys::Win32::System::Console::GetConsoleOutputCP() };
+        cp == 65001 || std::env::var_os("WT_SESSION").is_some()
+    }
+    #[cfg(not(windows))]
+    {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|k| std::env::var(k).ok())
+            .find(|v| !v.is_empty());
+        match locale {
+            Some(v) => {
+                let v = v.to_ascii_lowercase();
+                v.contains("utf-8") || v.contains("utf8")
+            }
+            None => false,
+        }
+    }
+}
+
+/// Characters used for boxes, truncation, and spinners.
+#[derive(Debug)]
+pub struct Glyphs {
+    pub top_left: &'static str,
+    pub top_right: &'static str,
+    pub bottom_left: &'static str,
+    pub bottom_right: &'static str,
+    pub horizontal: &'static str,
+    pub vertical: &'static str,
+    pub ellipsis: &'static str,
+    /// Spinner frames for indicatif (`None` keeps its default)
+    pub spinner: Option<&'static str>,
+}
+
+pub const UNICODE: Glyphs = Glyphs {
+    top_left: "┌",
+    top_right: "┐",
+    bottom_left: "└",
+    bottom_right: "┘",
+    horizontal: "─",
+    vertical: "│",
+    ellipsis: "…",
+    spinner: None,
+};
+
+pub const ASCII: Glyphs = Glyphs {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    horizontal: "-",
+    vertical: "|",
+    ellipsis: "...",
+    spinner: Some("|/-\\ "),
+};
+
+/// Pick the glyph set: ASCII when forced or when the terminal can't render UTF-8.
+pub fn glyphs(force_ascii: bool) -> &'static Glyphs {
+    if force_ascii || !supports_utf8() { &ASCII } else { &UNICODE }
+}
@@ -0,0 +1,239 @@
+//! `--preset timemachine`: links to a target inside Time Machine backups and local APFS snapshots.
+//!
+//! Each backup is walked as a copy of the original filesystem: link text is resolved
+//! lexically against the link's original location (absolute text in a backup names
+//! the live system, not the backup), so the same link can be compared across backups
+//! to see when it appeared, changed, or went away.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use console::style;
+use ignore::WalkState;
+use serde::Serialize;
+
+use crate::cli::ScanOpts;
+use crate::output::{JsonPath, PathEncoding};
+use crate::scan::{self, Target};
+
+/// Where mounted backup disks appear (macOS, then common Linux mount points).
+const MOUNT_ROOTS: &[&str] = &["/Volumes", "/media", "/run/media", "/mnt"];
+
+/// One point in time: a backup or snapshot and the volumes it holds.
+struct Backup {
+    /// `YYYY-MM-DD-HHMMSS`, which sorts chronologically
+    when: String,
+    source: &'static str,
+    /// (directory in the backup, where that volume was mounted when backed up)
+    volumes: Vec<(PathBuf, PathBuf)>,
+}
+
+/// A read-only local snapshot mount, unmounted and removed on drop.
+struct Mount(PathBuf);
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        let _ = Command::new("umount").arg(&self.0).status();
+        let _ = fs::remove_dir(&self.0);
+    }
+}
+
+/// The timestamp in a backup or snapshot name (`2024-01-02-101112`, `com.apple.TimeMachine.2024-01-02-101112.local`).
+fn timestamp(name: &str) -> Option<String> {
+    let b = name.as_bytes();
+    (0..b.len().saturating_sub(16))
+        .find(|&i| {
+            let s = &b[i..i + 17];
+            s.iter().enumerate().all(|(j, c)| if matches!(j, 4 | 7 | 10) { *c == b'-' } else { c.is_ascii_digit() })
+        })
+        .map(|i| name[i..i + 17].to_string())
+}
+
+/// Backed-up volumes in `dir`: the startup volume (it has `Users` or `System`) was `/`, others `/Volumes/<name>`.
+fn volumes(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let Ok(rd) = fs::read_dir(dir) else { return Vec::new() };
+    let mut out: Vec<(PathBuf, PathBuf)> = rd.flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| {
+            let p = e.path();
+            let root = if p.join("Users").is_dir() || p.join("System").is_dir() { PathBuf::from("/") } else { Path::new("/Volumes").join(e.file_name()) };
+            (p, root)
+        })
+        .collect();
+    out.sort();
+    out
+}
+
+fn subdirs(p: &Path) -> Vec<PathBuf> {
+    let Ok(rd) = fs::read_dir(p) else { return Vec::new() };
+    rd.flatten().filter(|e| e.file_type().is_ok_and(|t| t.is_dir())).map(|e| e.path()).collect()
+}
+
+/// `Backups.backupdb/<machine>/<date>` (HFS+ destinations) and `<date>.backup` (APFS destinations) on mounted disks.
+fn disk_backups() -> Vec<Backup> {
+    let mut disks: Vec<PathBuf> = Vec::new();
+    for root in MOUNT_ROOTS {
+        for d in subdirs(Path::new(root)) {
+            // /media/<user>/<disk>, and Time Machine's own /Volumes/.timemachine/<uuid>/
+            disks.extend(subdirs(&d));
+            disks.push(d);
+        }
+    }
+    let mut out = Vec::new();
+    for disk in disks {
+        for machine in subdirs(&disk.join("Backups.backupdb")) {
+            for b in subdirs(&machine) {
+                let Some(when) = b.file_name().and_then(|n| timestamp(&n.to_string_lossy())) else { continue };
+                out.push(Backup { when, source: "backupdb", volumes: volumes(&b) });
+            }
+        }
+        for b in subdirs(&disk).into_iter().filter(|b| b.extension().is_some_and(|e| e == "backup")) {
+            let Some(when) = b.file_name().and_then(|n| timestamp(&n.to_string_lossy())) else { continue };
+            // Browsed APFS backups nest `<date>.backup/<date>.backup/<volume>`
+            let mut dir = b;
+            while let [only] = subdirs(&dir).as_slice() {
+                if only.extension().is_none_or(|e| e != "backup") { break; }
+                dir = only.clone();
+            }
+            out.push(Backup { when, source: "backup", volumes: volumes(&dir) });
+        }
+    }
+    out
+}
+
+/// Mount each local Time Machine snapshot read-only (macOS, needs root).
+fn local_snapshots() -> (Vec<Backup>, Vec<Mount>) {
+    let (mut out, mut mounts) = (Vec::new(), Vec::new());
+    if cfg!(not(target_os = "macos")) { return (out, mounts); }
+    let Ok(list) = Command::new("tmutil").args(["listlocalsnapshots", "/"]).output() else { return (out, mounts) };
+    // Since Catalina the snapshots are of the data volume, which firmlinks make look like `/`
+    let volume = if Path::new("/System/Volumes/Data").is_dir() { "/System/Volumes/Data" } else { "/" };
+    for (i, name) in String::from_utf8_lossy(&list.stdout).lines().map(str::trim).filter(|l| l.starts_with("com.apple.TimeMachine.")).enumerate() {
+        let Some(when) = timestamp(name) else { continue };
+        let dir = std::env::temp_dir().join(format!("find-symlinks-snapshot-{}-{}", std::process::id(), i));
+        if fs::create_dir_all(&dir).is_err() { continue; }
+        let ok = Command::new("mount_apfs").args(["-o", "rdonly", "-s", name, volume]).arg(&dir).status().is_ok_and(|s| s.success());
+        if !ok {
+            let _ = fs::remove_dir(&dir);
+            eprintln!("warning: could not mount local snapshot {} (mounting needs root)", name);
+            continue;
+        }
+        out.push(Backup { when, source: "local", volumes: vec![(dir.clone(), PathBuf::from("/"))] });
+        mounts.push(Mount(dir));
+    }
+    (out, mounts)
+}
+
+/// Links found in one backup volume: (original path, link text, target index).
+fn scan_volume(opts: &ScanOpts, dir: &Path, root: &Path, targets: &[Target]) -> Vec<(PathBuf, PathBuf, usize)> {
+    let found = Mutex::new(Vec::new());
+    scan::build_subtree_walker(&opts.walk, dir).build_parallel().run(|| {
+        Box::new(|res| {
+            let Ok(e) = res else { return WalkState::Continue };
+            if !e.file_type().is_some_and(|t| t.is_symlink()) { return WalkState::Continue; }
+            let Ok(text) = fs::read_link(e.path()) else { return WalkState::Continue };
+            let Ok(rel) = e.path().strip_prefix(dir) else { return WalkState::Continue };
+            let original = root.join(rel);
+            let dest = scan::lexical_absolute(&original.parent().unwrap_or(Path::new("/")).join(&text));
+            let hit = scan::innermost(targets, |t| t.names(&dest))
+                .or_else(|| scan::match_link_pattern(&text, targets))
+                .or_else(|| scan::match_dest_regex(&dest, targets));
+            if let Some(t) = hit {
+                if let Ok(mut v) = found.lock() { v.push((original, text, t)); }
+            }
+            WalkState::Continue
+        })
+    });
+    found.into_inner().unwrap_or_default()
+}
+
+/// Targets as given: resolved where they still exist, otherwise matched by path alone.
+pub fn targets(opts: &ScanOpts) -> Result<Vec<Target>> {
+    let mut out: Vec<Target> = Vec::new();
+    for given in &opts.targets {
+        let lexical = scan::lexical_absolute(Path::new(given));
+        if out.iter().any(|t| t.lexical() == lexical) { continue; }
+        out.push(Target::recorded(given.clone(), scan::realpath(Path::new(given)).ok(), lexical).under(opts.under));
+    }
+    out.extend(scan::pattern_targets(opts)?);
+    Ok(out)
+}
+
+#[derive(Serialize)]
+struct Change<'a> {
+    snapshot: &'a str,
+    source: &'a str,
+    path: JsonPath<'a>,
+    link_text: String,
+    target: &'a str,
+    /// `new`, `changed`, `same`, or `gone` relative to the previous backup
+    change: &'static str,
+}
+
+pub fn run(opts: &ScanOpts, targets: &[Target], json: bool, encoding: PathEncoding) -> Result<()> {
+    let mut backups = disk_backups();
+    let (local, _mounts) = local_snapshots();
+    backups.extend(local);
+    backups.sort_by(|a, b| a.when.cmp(&b.when).then(a.source.cmp(b.source)));
+    if backups.is_empty() {
+        if json { println!("[]"); } else { println!("{}", style("No Time Machine backups or local snapshots found.").yellow()); }
+        return Ok(());
+    }
+
+    // Per backup, in time order: original path -> (link text, target)
+    let found: Vec<BTreeMap<PathBuf, (PathBuf, usize)>> = backups.iter()
+        .map(|b| {
+            b.volumes.iter()
+                .flat_map(|(dir, root)| scan_volume(opts, dir, root, targets))
+                .map(|(p, text, t)| (p, (text, t)))
+                .collect()
+        })
+        .collect();
+
+    let empty = BTreeMap::new();
+    let mut rows: Vec<Change> = Vec::new();
+    for (i, (b, now)) in backups.iter().zip(&found).enumerate() {
+        let before = if i == 0 { &empty } else { &found[i - 1] };
+        for (p, (text, t)) in now {
+            let change = match before.get(p) {
+                None => "new",
+                Some((old, _)) if old != text => "changed",
+                Some(_) => "same",
+            };
+            rows.push(Change { snapshot: &b.when, source: b.source, path: JsonPath { path: p, encoding }, link_text: text.to_string_lossy().into_owned(), target: &targets[*t].given, change });
+        }
+        for (p, (text, t)) in before.iter().filter(|(p, _)| !now.contains_key(*p)) {
+            rows.push(Change { snapshot: &b.when, source: b.source, path: JsonPath { path: p, encoding }, link_text: text.to_string_lossy().into_owned(), target: &targets[*t].given, change: "gone" });
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    for (b, now) in backups.iter().zip(&found) {
+        let here: Vec<&Change> = rows.iter().filter(|r| r.snapshot == b.when && r.source == b.source).collect();
+        let same = here.iter().filter(|r| r.change == "same").count();
+        println!(
+            "{} {} {}",
+            style(&b.when).bold(),
+            style(format!("({})", b.source)).dim(),
+            style(format!("{} matching, {} unchanged", now.len(), same)).dim(),
+        );
+        for r in here.iter().filter(|r| r.change != "same") {
+            let mark = match r.change {
+                "new" => style("+").green().bold(),
+                "changed" => style("~").yellow().bold(),
+                _ => style("-").red().bold(),
+            };
+            println!("  {} {} {} {}", mark, style(r.path.path.display()).white().bold(), style("->").dim(), style(&r.link_text).cyan());
+        }
+    }
+    println!();
+    println!("{} {}", style("Backups searched:").dim(), style(backups.len()).bold().cyan());
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! `--op-timeout`: filesystem calls that can hang on a dead mount (NFS, FUSE) run on a helper
+//! thread and are given up on when they take too long.
+//!
+//! A hung call cannot be interrupted, so its helper is abandoned (it exits if the call ever
+//! returns) and the calling thread gets a fresh one. Each calling thread keeps its own helper,
+//! so a call costs a channel round trip rather than a thread spawn.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Calls given up on, with the path and what was being done, until the walk's owner takes them
+static ABANDONED: Mutex<Vec<(PathBuf, String)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static HELPER: RefCell<Option<mpsc::Sender<Job>>> = const { RefCell::new(None) };
+}
+
+fn helper() -> mpsc::Sender<Job> {
+    let (tx, rx) = mpsc::channel::<Job>();
+    std::thread::spawn(move || {
+        for job in rx { job(); }
+    });
+    tx
+}
+
+/// `f()`, or `None` if it did not finish within `limit`; with no limit it runs on this thread.
+pub fn call<T: Send + 'static>(limit: Option<Duration>, f: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+    let Some(limit) = limit else { return Some(f()) };
+    let (tx, rx) = mpsc::channel();
+    let job: Job = Box::new(move || { let _ = tx.send(f()); });
+    HELPER.with_borrow_mut(|h| {
+        let sender = h.get_or_insert_with(helper);
+        if let Err(mpsc::SendError(job)) = sender.send(job) {
+            *sender = helper();
+            let _ = sender.send(job);
+        }
+    });
+    let out = rx.recv_timeout(limit).ok();
+    // The helper is stuck in the call; leave it behind
+    if out.is_none() { HELPER.with_borrow_mut(|h| *h = None); }
+    out
+}
+
+/// Note that `op` on `p` was given up on after `limit`.
+pub fn abandoned(p: &Path, op: &str, limit: Duration) {
+    if let Ok(mut v) = ABANDONED.lock() { v.push((p.to_path_buf(), format!("{} timed out after {:?}", op, limit))); }
+}
+
+/// The calls given up on since the last `take`, as (path, message).
+pub fn take() -> Vec<(PathBuf, String)> {
+    ABANDONED.lock().map(|mut v| std::mem::take(&mut *v)).unwrap_or_default()
+}
+
+/// Print calls given up on to stderr.
+pub fn log(abandoned: &[(PathBuf, String)]) {
+    for (p, message) in abandoned { eprintln!("timeout: {}  {}", p.display(), message); }
+}
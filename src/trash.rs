@@ -0,0 +1,139 @@
+//! `--trash`: `--delete` moving each link to the platform trash instead of unlinking it.
+//!
+//! Only the link moves; what it points to is never touched. On Linux and the BSDs the link goes to
+//! a freedesktop.org trash (the home trash on its filesystem, else `.Trash/UID` or `.Trash-UID` at
+//! the top of the mount) with a `.trashinfo` record, so file managers can restore it. On macOS it
+//! is renamed into `~/.Trash` (or the volume's `.Trashes/UID`); on Windows the Recycle Bin takes
+//! it through PowerShell.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Move the symlink `p` to the trash.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn put(p: &Path) -> io::Result<()> {
+    use std::io::Write;
+    let p = crate::scan::lexical_absolute(p);
+    let (dir, top) = trash_dir(&p)?;
+    let (files, info) = (dir.join("files"), dir.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+    // The record is created first, exclusively, so two runs never claim the same name
+    let name = p.file_name().ok_or_else(|| io::Error::other("no file name"))?.to_string_lossy().into_owned();
+    let (record, kept) = (1..)
+        .map(|n| if n == 1 { name.clone() } else { format!("{}.{}", name, n) })
+        .find_map(|kept| {
+            let record = info.join(format!("{}.trashinfo", kept));
+            match fs::OpenOptions::new().write(true).create_new(true).open(&record) {
+                Ok(f) => Some(Ok((f, record, kept))),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .expect("unbounded")
+        .map(|(mut f, record, kept)| (f.write_all(info_text(&p, top.as_deref()).as_bytes()).map(|()| record), kept))?;
+    let record = record?;
+    fs::rename(&p, files.join(&kept)).inspect_err(|_| { let _ = fs::remove_file(&record); })
+}
+
+/// The trash for a link at `p`, and the top of its mount when that is not the home trash.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn trash_dir(p: &Path) -> io::Result<(PathBuf, Option<PathBuf>)> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    let parent = p.parent().unwrap_or(Path::new("/"));
+    let dev = fs::metadata(parent)?.dev();
+    // The home trash lives under the data dir, which may not exist yet; home decides the filesystem
+    if let (Some(home), Some(data)) = (dirs::home_dir(), dirs::data_dir()) {
+        if fs::metadata(&home).is_ok_and(|m| m.dev() == dev) { return Ok((data.join("Trash"), None)); }
+    }
+    let top = mount_top(parent, dev);
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    // `.Trash` counts only if the admin made it a sticky directory (not a symlink to one)
+    let shared = top.join(".Trash");
+    let usable = fs::symlink_metadata(&shared).is_ok_and(|m| m.is_dir() && m.permissions().mode() & 0o1000 != 0);
+    let dir = if usable { shared.join(uid.to_string()) } else { top.join(format!(".Trash-{}", uid)) };
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok((dir, Some(top)))
+}
+
+/// The `.trashinfo` record: where it came from (relative to the mount in a mount's trash), and when.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn info_text(p: &Path, top: Option<&Path>) -> String {
+    let path = top.and_then(|t| p.strip_prefix(t).ok()).unwrap_or(p);
+    let encoded: String = crate::output::os_bytes(path).iter()
+        .map(|&b| if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect();
+    format!("[Trash Info]\nPath={}\nDeletionDate={}\n", encoded, local_time())
+}
+
+/// Now as `YYYY-MM-DDThh:mm:ss` in local time, as the trash spec wants.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn local_time() -> String {
+    // SAFETY: `time` with a null pointer only returns the time; `localtime_r` fills the zeroed `tm`
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+#[cfg(target_os = "macos")]
+pub fn put(p: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let p = crate::scan::lexical_absolute(p);
+    let parent = p.parent().unwrap_or(Path::new("/"));
+    let dev = fs::metadata(parent)?.dev();
+    let home = dirs::home_dir().filter(|h| fs::metadata(h).is_ok_and(|m| m.dev() == dev));
+    let dir = match home {
+        Some(h) => h.join(".Trash"),
+        // SAFETY: getuid cannot fail
+        None => mount_top(parent, dev).join(".Trashes").join(unsafe { libc::getuid() }.to_string()),
+    };
+    fs::create_dir_all(&dir)?;
+    // Finder's own naming for a clash: "name 2", "name 3", ...
+    let name = p.file_name().ok_or_else(|| io::Error::other("no file name"))?.to_string_lossy().into_owned();
+    let to = (1..)
+        .map(|n| dir.join(if n == 1 { name.clone() } else { format!("{} {}", name, n) }))
+        .find(|to| fs::symlink_metadata(to).is_err())
+        .expect("unbounded");
+    fs::rename(&p, to)
+}
+
+/// The top directory of the filesystem (device `dev`) holding `dir`.
+#[cfg(unix)]
+fn mount_top(dir: &Path, dev: u64) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+    let real = crate::scan::realpath(dir).unwrap_or_else(|_| dir.to_path_buf());
+    real.ancestors()
+        .take_while(|a| fs::metadata(a).is_ok_and(|m| m.dev() == dev))
+        .last()
+        .map_or_else(|| PathBuf::from("/"), Path::to_path_buf)
+}
+
+#[cfg(windows)]
+pub fn put(p: &Path) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+    // The shell's Recycle Bin takes the link itself; a link to a directory goes as a directory
+    let how = if crate::actions::is_dir_link(p) { "DeleteDirectory" } else { "DeleteFile" };
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName Microsoft.VisualBasic; [Microsoft.VisualBasic.FileIO.FileSystem]::{}({}, 'OnlyErrorDialogs', 'SendToRecycleBin')",
+        how, quote(&crate::scan::lexical_absolute(p).to_string_lossy()),
+    );
+    let status = Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+        .status()?;
+    if status.success() { Ok(()) } else { Err(io::Error::other(format!("powershell exited with {}", status))) }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn put(_p: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no trash on this platform"))
+}
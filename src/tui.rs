@@ -0,0 +1,358 @@
+//! `--interactive`: browse the matches in a full-screen list as they are found, and change them.
+//!
+//! The scan runs on another thread and sends each match over as it is confirmed; its `Hooks`
+//! count entries and links for the title while it runs. The list is
+//! kept in the order matches arrive and sorted by path once the scan is done. Changes go through
+//! [`actions::run`] with the confirmation asked in the status line instead of on the terminal.
+
+use std::io::{self, Stderr};
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use num_format::{Locale, ToFormattedString};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::symbols::border;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+
+use crate::actions::{self, Action};
+use crate::cancel::Reason;
+use crate::cli::FindOpts;
+use crate::hooks::{Hooks, Phase};
+use crate::scan::{self, Match, ScanResult, Target};
+use crate::term::Glyphs;
+
+type Term = Terminal<CrosstermBackend<Stderr>>;
+
+/// What the scan thread sends.
+enum Msg {
+    Match(Match),
+    Done(Box<Result<ScanResult>>),
+}
+
+/// How far the scan has got, for the title.
+#[derive(Default)]
+struct Tally {
+    walked: AtomicUsize,
+    /// Links left to check after the walk (0 until it is done)
+    total: AtomicUsize,
+    resolved: AtomicUsize,
+}
+
+impl Hooks for Tally {
+    fn phase(&self, phase: Phase) {
+        if let Phase::Resolving { total } = phase { self.total.store(total, Ordering::Relaxed); }
+    }
+
+    fn walked(&self, _path: &Path, _is_dir: bool, _is_symlink: bool) {
+        self.walked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn resolved(&self) {
+        self.resolved.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+struct Row {
+    m: Match,
+    selected: bool,
+    /// What happened to it: its new link text, or why a change failed
+    note: Option<String>,
+}
+
+enum Mode {
+    Browse,
+    /// Waiting for y/n before running the action on these rows
+    Confirm(Action, Vec<usize>),
+    /// Typing the NEWPATH for `r`
+    Retarget(String, Vec<usize>),
+}
+
+struct App<'a> {
+    targets: &'a [Target],
+    rows: Vec<Row>,
+    list: ListState,
+    mode: Mode,
+    /// The scan's summary once it is done
+    finished: Option<String>,
+    status: String,
+    /// Rows in the list view, for PgUp/PgDn
+    page: usize,
+    glyphs: &'static Glyphs,
+    tally: &'a Tally,
+}
+
+/// Run the scan behind the results browser until the user quits.
+pub fn run(opts: &FindOpts, targets: &[Target], glyphs: &'static Glyphs) -> Result<()> {
+    let tally = Tally::default();
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|s| -> Result<()> {
+        s.spawn(|| {
+            let result = scan::scan(&opts.scan, targets, &tally, &|m| { let _ = tx.send(Msg::Match(m.clone())); });
+            let _ = tx.send(Msg::Done(Box::new(result)));
+        });
+        let mut term = enter()?;
+        let mut app = App::new(targets, glyphs, &tally);
+        let outcome = app.run(&mut term, &rx);
+        leave(&mut term)?;
+        // Quit mid-scan: stop the walk rather than wait for it
+        if app.finished.is_none() { opts.scan.walk.cancel.cancel(Reason::Requested); }
+        outcome
+    })
+}
+
+fn enter() -> Result<Term> {
+    enable_raw_mode()?;
+    io::stderr().execute(EnterAlternateScreen)?;
+    Ok(Terminal::new(CrosstermBackend::new(io::stderr()))?)
+}
+
+fn leave(term: &mut Term) -> Result<()> {
+    disable_raw_mode()?;
+    term.backend_mut().execute(LeaveAlternateScreen)?;
+    term.show_cursor()?;
+    Ok(())
+}
+
+impl<'a> App<'a> {
+    fn new(targets: &'a [Target], glyphs: &'static Glyphs, tally: &'a Tally) -> App<'a> {
+        App { targets, rows: Vec::new(), list: ListState::default(), mode: Mode::Browse, finished: None, status: String::new(), page: 1, glyphs, tally }
+    }
+
+    fn run(&mut self, term: &mut Term, rx: &Receiver<Msg>) -> Result<()> {
+        loop {
+            while let Ok(msg) = rx.try_recv() { self.receive(msg); }
+            term.draw(|f| self.draw(f))?;
+            if !event::poll(Duration::from_millis(100))? { continue; }
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press { continue; }
+            if !self.key(key, term)? { return Ok(()); }
+        }
+    }
+
+    fn receive(&mut self, msg: Msg) {
+        match msg {
+            Msg::Match(m) => {
+                self.rows.push(Row { m, selected: false, note: None });
+                if self.list.selected().is_none() { self.list.select(Some(0)); }
+            }
+            Msg::Done(done) => match *done {
+                Ok(result) => self.finish(&result),
+                Err(e) => self.finished = Some(format!("scan failed: {:#}", e)),
+            },
+        }
+    }
+
+    /// Sort the list by path, keeping the cursor on the same match, and sum up the scan.
+    fn finish(&mut self, result: &ScanResult) {
+        let current = self.current().map(|i| self.rows[i].m.path.clone());
+        self.rows.sort_by(|a, b| a.m.path.cmp(&b.m.path));
+        if let Some(p) = current { self.list.select(self.rows.iter().position(|r| r.m.path == p)); }
+        let mut summary = format!(
+            "{} matches among {} symlinks in {:.1}s",
+            self.rows.len().to_formatted_string(&Locale::en),
+            result.symlinks.to_formatted_string(&Locale::en),
+            (result.walk_time + result.resolve_time).as_secs_f64(),
+        );
+        if !result.errors.is_empty() { summary.push_str(&format!(", {} errors", result.errors.len())); }
+        match result.cancelled {
+            Some(why) => summary.push_str(&format!(" ({}: partial)", why)),
+            None if result.aborted => summary.push_str(" (aborted: partial)"),
+            None => {}
+        }
+        self.finished = Some(summary);
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.list.selected().filter(|&i| i < self.rows.len())
+    }
+
+    /// The selected rows, or the current one if none are.
+    fn picked(&self) -> Vec<usize> {
+        let sel: Vec<usize> = (0..self.rows.len()).filter(|&i| self.rows[i].selected).collect();
+        if sel.is_empty() { self.current().into_iter().collect() } else { sel }
+    }
+
+    fn step(&mut self, by: isize) {
+        if self.rows.is_empty() { return; }
+        let last = self.rows.len() as isize - 1;
+        let at = self.current().map_or(0, |i| i as isize);
+        self.list.select(Some((at + by).clamp(0, last) as usize));
+    }
+
+    /// Handle a key; false to quit.
+    fn key(&mut self, key: KeyEvent, term: &mut Term) -> Result<bool> {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Confirm(action, rows) => {
+                if matches!(key.code, KeyCode::Char('y' | 'Y')) { self.apply(&action, &rows)?; } else { self.status = "Nothing changed".into(); }
+            }
+            Mode::Retarget(mut text, rows) => match key.code {
+                KeyCode::Enter if !text.is_empty() => {
+                    let to = scan::lexical_absolute(Path::new(&text));
+                    self.status = String::new();
+                    self.mode = Mode::Confirm(Action::Retarget(to), rows);
+                }
+                KeyCode::Esc => self.status = "Nothing changed".into(),
+                KeyCode::Backspace => { text.pop(); self.mode = Mode::Retarget(text, rows); }
+                KeyCode::Char(c) => { text.push(c); self.mode = Mode::Retarget(text, rows); }
+                _ => self.mode = Mode::Retarget(text, rows),
+            },
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(false),
+                KeyCode::Down | KeyCode::Char('j') => self.step(1),
+                KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+                KeyCode::PageDown => self.step(self.page as isize),
+                KeyCode::PageUp => self.step(-(self.page as isize)),
+                KeyCode::Home | KeyCode::Char('g') => self.step(isize::MIN / 2),
+                KeyCode::End | KeyCode::Char('G') => self.step(isize::MAX / 2),
+                KeyCode::Char(' ') => {
+                    if let Some(i) = self.current() { self.rows[i].selected ^= true; }
+                    self.step(1);
+                }
+                KeyCode::Char('a') => {
+                    let all = self.rows.iter().all(|r| r.selected);
+                    for r in &mut self.rows { r.selected = !all; }
+                }
+                KeyCode::Char('d') if self.current().is_some() => self.mode = Mode::Confirm(Action::Delete, self.picked()),
+                KeyCode::Char('r') if self.current().is_some() => self.mode = Mode::Retarget(String::new(), self.picked()),
+                KeyCode::Char('e') => {
+                    if let Some(i) = self.current() { self.edit(&self.rows[i].m.path.clone(), term)?; }
+                }
+                _ => {}
+            },
+        }
+        Ok(true)
+    }
+
+    fn apply(&mut self, action: &Action, rows: &[usize]) -> Result<()> {
+        let matches: Vec<Match> = rows.iter().map(|&i| self.rows[i].m.clone()).collect();
+        let report = actions::run(action, &matches, true, false, None, |_| String::new())?;
+        for (p, text) in &report.done {
+            let Some(row) = self.rows.iter_mut().find(|r| r.m.path == *p) else { continue };
+            row.selected = false;
+            row.note = Some(match text {
+                Some(t) => format!("-> {}", t.display()),
+                None => "deleted".into(),
+            });
+        }
+        for (p, e) in &report.failed {
+            if let Some(row) = self.rows.iter_mut().find(|r| r.m.path == *p) { row.note = Some(format!("failed: {}", e)); }
+        }
+        if matches!(action, Action::Delete | Action::Trash) {
+            let current = self.current();
+            self.rows.retain(|r| r.note.as_deref() != Some("deleted"));
+            self.list.select(current.map(|i| i.min(self.rows.len().saturating_sub(1))).filter(|_| !self.rows.is_empty()));
+        }
+        self.status = format!("{} {} symlinks", action.past(), report.done.len());
+        if !report.failed.is_empty() { self.status.push_str(&format!(" ({} failed)", report.failed.len())); }
+        Ok(())
+    }
+
+    /// Open `path` in $VISUAL or $EDITOR (default `vi`), handing it the terminal meanwhile.
+    fn edit(&mut self, path: &Path, term: &mut Term) -> Result<()> {
+        let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".into());
+        let mut words = editor.split_whitespace();
+        let Some(program) = words.next() else { return Ok(()) };
+        leave(term)?;
+        let status = Command::new(program).args(words).arg(path).status();
+        *term = enter()?;
+        term.clear()?;
+        self.status = match status {
+            Ok(s) if s.success() => String::new(),
+            Ok(s) => format!("{} exited with {}", program, s),
+            Err(e) => format!("could not run {}: {}", program, e),
+        };
+        Ok(())
+    }
+
+    fn border(&self) -> border::Set {
+        let g = self.glyphs;
+        border::Set {
+            top_left: g.top_left,
+            top_right: g.top_right,
+            bottom_left: g.bottom_left,
+            bottom_right: g.bottom_right,
+            vertical_left: g.vertical,
+            vertical_right: g.vertical,
+            horizontal_top: g.horizontal,
+            horizontal_bottom: g.horizontal,
+        }
+    }
+
+    fn draw(&mut self, f: &mut Frame) {
+        let [list_area, detail_area, status_area] = Layout::vertical([Constraint::Min(3), Constraint::Length(6), Constraint::Length(1)]).areas(f.size());
+        self.page = list_area.height.saturating_sub(2).max(1) as usize;
+
+        let n = self.rows.len().to_formatted_string(&Locale::en);
+        let title = match &self.finished {
+            Some(summary) => format!(" {} ", summary),
+            None => match self.tally.total.load(Ordering::Relaxed) {
+                0 => format!(" {} matches, scanning{} {} entries ", n, self.glyphs.ellipsis, self.tally.walked.load(Ordering::Relaxed).to_formatted_string(&Locale::en)),
+                total => format!(
+                    " {} matches, checking symlinks{} {}/{} ",
+                    n,
+                    self.glyphs.ellipsis,
+                    self.tally.resolved.load(Ordering::Relaxed).to_formatted_string(&Locale::en),
+                    total.to_formatted_string(&Locale::en),
+                ),
+            },
+        };
+        let items: Vec<ListItem> = self.rows.iter()
+            .map(|r| {
+                let mark = if r.selected { "[x] " } else { "[ ] " };
+                let path = Span::raw(r.m.path.display().to_string());
+                let mut spans = vec![Span::raw(mark), if r.m.broken { path.red() } else { path }];
+                if let Some(note) = &r.note { spans.push(format!("  {}", note).dim()); }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).border_set(self.border()).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, list_area, &mut self.list);
+
+        let details = match self.current() {
+            Some(i) => self.details(&self.rows[i].m),
+            None => vec![Line::from("No matches yet".dim())],
+        };
+        f.render_widget(Paragraph::new(details).block(Block::default().borders(Borders::ALL).border_set(self.border()).title(" Link ")), detail_area);
+
+        let status = match &self.mode {
+            Mode::Confirm(action, rows) => {
+                let to = match action { Action::Retarget(to) => format!(" to {}", to.display()), _ => String::new() };
+                Line::from(format!("{} {} symlinks{}? [y/N]", actions::capitalize(action.verb()), rows.len(), to).bold())
+            }
+            Mode::Retarget(text, rows) => Line::from(format!("Retarget {} symlinks to: {}", rows.len(), text)),
+            Mode::Browse if !self.status.is_empty() => Line::from(self.status.as_str()),
+            Mode::Browse => Line::from("j/k move  space select  a all  d delete  r retarget  e edit  q quit".dim()),
+        };
+        f.render_widget(Paragraph::new(status), status_area);
+    }
+
+    /// Link text against where the link really goes.
+    fn details(&self, m: &Match) -> Vec<Line<'static>> {
+        let text = std::fs::read_link(&m.path).map_or_else(|e| format!("({})", e), |t| t.display().to_string());
+        let resolved = match scan::realpath(&m.path) {
+            Ok(r) => r.display().to_string(),
+            Err(_) => format!("broken: {}", scan::broken_reason(&m.path).unwrap_or_else(|| "does not resolve".into())),
+        };
+        let field = |name: &str, value: String| Line::from(vec![format!("{:<13}", name).dim(), Span::raw(value)]);
+        vec![
+            field("Path", m.path.display().to_string()),
+            field("Link text", text),
+            field("Resolves to", resolved),
+            field("Target", self.targets.get(m.target).map(|t| t.given.clone()).unwrap_or_default()),
+        ]
+    }
+}
@@ -0,0 +1,117 @@
+//! `tune`: time the walk and resolve phases with different settings on this machine, and save the fastest as `[tuning]`
+//! in the machine-wide config file (or, with `--user`, the user's own).
+//!
+//! One untimed walk warms the page cache first, so the runs measure what repeated scans of the tree see
+//! rather than the first cold one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use console::style;
+use num_format::{Locale, ToFormattedString};
+use rayon::prelude::*;
+
+use crate::cli::TuneOpts;
+use crate::config::{self, TuningConfig};
+use crate::scan;
+
+const THREADS: &[usize] = &[1, 2, 4, 8, 12, 16, 24, 32, 48, 64];
+const BATCHES: &[usize] = &[1, 8, 32, 128, 512];
+/// Symlinks timed per resolve run; a sample ranks the settings as well as the whole tree
+const SAMPLE: usize = 100_000;
+/// A setting must beat the one before it by this much to be picked, so noise does not buy more threads
+const MARGIN: f64 = 0.05;
+/// Each setting is timed this many times and its fastest run kept
+const REPEATS: usize = 3;
+
+/// The first of `runs` (value, seconds) unless a later one is clearly faster.
+fn fastest(runs: &[(usize, f64)]) -> usize {
+    let mut best = runs[0];
+    for &r in &runs[1..] {
+        if r.1 < best.1 * (1.0 - MARGIN) { best = r; }
+    }
+    best.0
+}
+
+fn print_run(label: &str, value: usize, secs: f64) {
+    println!("{} {:.3}s", style(format!("{} {}:", label, value)).dim(), secs);
+}
+
+/// Fastest of `REPEATS` runs of `f`, in seconds.
+fn time(mut f: impl FnMut()) -> f64 {
+    (0..REPEATS).map(|_| {
+        let start = Instant::now();
+        f();
+        start.elapsed().as_secs_f64()
+    }).fold(f64::INFINITY, f64::min)
+}
+
+/// Resolve `links` the way a scan does (`stat`, then `realpath`) on a pool of `threads`.
+fn time_resolve(links: &[PathBuf], threads: usize, batch: usize) -> Result<f64> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().context("start resolve threads")?;
+    Ok(time(|| pool.install(|| links.par_iter().with_min_len(batch).for_each(|p| {
+        let _ = fs::metadata(p);
+        let _ = scan::realpath(p);
+    }))))
+}
+
+pub fn run(opts: TuneOpts, config_path: Option<&Path>) -> Result<()> {
+    // Machine-wide unless told otherwise: the timings are the machine's, not the user's
+    let system = config_path.is_none() && !opts.user;
+    let path = if system {
+        config::system_path().context("no machine-wide config location on this platform; pass --user or --config FILE")?
+    } else {
+        config::path(config_path).context("no config directory on this platform; pass --config FILE")?
+    };
+    let cores = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let candidates: Vec<usize> = THREADS.iter().copied().filter(|&n| n <= cores * 2).collect();
+
+    let mut walk = opts.walk.clone();
+    let links = scan::walk_symlinks(&walk);
+    let mut walk_runs = Vec::new();
+    for &n in &candidates {
+        walk.threads = Some(n);
+        let secs = time(|| { scan::walk_symlinks(&walk); });
+        print_run("Walk threads", n, secs);
+        walk_runs.push((n, secs));
+    }
+    let mut tuning = TuningConfig { threads: Some(fastest(&walk_runs)), ..TuningConfig::default() };
+
+    let sample = &links[..links.len().min(SAMPLE)];
+    if sample.is_empty() {
+        println!("{}", style("No symlinks under the root; resolve settings left unset.").yellow());
+    } else {
+        println!("{} {}", style("Symlinks sampled:").dim(), style(sample.len().to_formatted_string(&Locale::en)).bold().cyan());
+        let mut resolve_runs = Vec::new();
+        for &n in &candidates {
+            let secs = time_resolve(sample, n, 1)?;
+            print_run("Resolve threads", n, secs);
+            resolve_runs.push((n, secs));
+        }
+        let threads = fastest(&resolve_runs);
+        let mut batch_runs = Vec::new();
+        for &b in BATCHES {
+            let secs = time_resolve(sample, threads, b)?;
+            print_run("Resolve batch", b, secs);
+            batch_runs.push((b, secs));
+        }
+        tuning.resolve_threads = Some(threads);
+        tuning.resolve_batch = Some(fastest(&batch_runs));
+    }
+
+    println!();
+    let show = |v: Option<usize>| v.map_or("auto".to_string(), |n| n.to_string());
+    println!("{} {}", style("Walk threads:").dim(), style(show(tuning.threads)).bold().green());
+    println!("{} {}", style("Resolve threads:").dim(), style(show(tuning.resolve_threads)).bold().green());
+    println!("{} {}", style("Resolve batch:").dim(), style(show(tuning.resolve_batch)).bold().green());
+    if opts.dry_run {
+        println!("{}", style("Dry run: config not written.").dim());
+        return Ok(());
+    }
+    let saved = config::save_tuning(&path, &tuning);
+    if system { saved.context("the machine-wide config needs an administrator; pass --user to tune for yourself only")?; } else { saved?; }
+    println!("{} {}", style("Saved [tuning] to").dim(), path.display());
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! `--journal` and `undo`: record each change a `find` action makes, and put the links back.
+//!
+//! The journal is JSON lines, one entry per changed link, appended and flushed as each change
+//! lands so an interrupted run can still be undone. `undo` replays it newest first, and only
+//! restores a link that is still as the change left it.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use console::{style, Term};
+use serde::{Deserialize, Serialize};
+
+use crate::actions;
+use crate::cli::UndoOpts;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// The link text before the change
+    pub old: PathBuf,
+    /// The link text after it; `None` if the link was deleted or replaced with a copy
+    pub new: Option<PathBuf>,
+    /// Replaced with a copy of what it resolved to (`--dereference-in-place`)
+    #[serde(default)]
+    pub copy: bool,
+}
+
+/// An open journal file, appended to one entry at a time.
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn open(path: &Path) -> Result<Journal> {
+        let file = OpenOptions::new().create(true).append(true).open(path).with_context(|| format!("open journal {}", path.display()))?;
+        Ok(Journal { file, path: path.to_path_buf() })
+    }
+
+    pub fn record(&mut self, entry: &Entry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line).and_then(|()| self.file.sync_data()).with_context(|| format!("write journal {}", self.path.display()))
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<Entry>> {
+    let file = File::open(path).with_context(|| format!("open journal {}", path.display()))?;
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("read journal {}", path.display()))?;
+        if line.trim().is_empty() { continue; }
+        let entry: Entry = serde_json::from_str(&line).with_context(|| format!("{}:{}: not a journal entry", path.display(), i + 1))?;
+        // A relative path would be restored against whatever directory `undo` runs in
+        if !entry.path.is_absolute() { anyhow::bail!("{}:{}: path is not absolute: {}", path.display(), i + 1, entry.path.display()); }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Whether `path` is still as the change in `e` left it.
+fn unchanged_since(e: &Entry) -> std::io::Result<bool> {
+    let meta = match fs::symlink_metadata(&e.path) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(e.new.is_none() && !e.copy),
+        Err(err) => return Err(err),
+    };
+    Ok(match (&e.new, e.copy) {
+        (_, true) => !meta.file_type().is_symlink(),
+        (Some(new), false) => meta.file_type().is_symlink() && fs::read_link(&e.path)? == *new,
+        (None, false) => false,
+    })
+}
+
+/// Put the link at `e.path` back to `e.old`.
+fn restore(e: &Entry) -> std::io::Result<()> {
+    if e.new.is_none() && !e.copy { return actions::symlink(&e.old, &e.path); }
+    // A link can be renamed over a file, but not over a directory
+    if e.copy && fs::symlink_metadata(&e.path)?.is_dir() {
+        fs::remove_dir_all(&e.path)?;
+        return actions::symlink(&e.old, &e.path);
+    }
+    actions::relink(&e.path, &e.old)
+}
+
+pub fn run(opts: UndoOpts) -> Result<()> {
+    if !opts.yes && !opts.dry_run && !actions::can_prompt() {
+        anyhow::bail!("undo asks before each change; pass --yes to run without a terminal");
+    }
+    actions::allow_junctions(opts.junction_fallback);
+    if !opts.dry_run { actions::check_link_privilege()?; }
+    let entries = load(&opts.journal)?;
+    let term = Term::stderr();
+    let (mut done, mut skipped, mut failed) = (Vec::new(), 0usize, Vec::new());
+    for e in entries.iter().rev() {
+        match unchanged_since(e) {
+            Ok(true) => {}
+            Ok(false) => { failed.push((&e.path, "changed since the journal was written".to_string())); continue; }
+            Err(err) => { failed.push((&e.path, err.to_string())); continue; }
+        }
+        if opts.dry_run { done.push(e); continue; }
+        let what = if e.copy { "the copy at " } else { "" };
+        if !opts.yes && !actions::confirm(&term, &format!("Restore {}{} -> {}?", what, e.path.display(), e.old.display()))? {
+            skipped += 1;
+            continue;
+        }
+        match restore(e) {
+            Ok(()) => done.push(e),
+            Err(err) => failed.push((&e.path, err.to_string())),
+        }
+    }
+    for e in &done {
+        println!("{} -> {}", e.path.display(), e.old.display());
+    }
+    for (p, err) in &failed {
+        println!("{} {}", style(format!("Could not restore {}:", p.display())).red(), err);
+    }
+    let head = if opts.dry_run { format!("Would restore {} symlinks (dry run)", done.len()) } else { format!("Restored {} symlinks", done.len()) };
+    let mut tail = Vec::new();
+    if skipped > 0 { tail.push(format!("{} skipped", skipped)); }
+    if !failed.is_empty() { tail.push(format!("{} failed", failed.len())); }
+    let tail = if tail.is_empty() { String::new() } else { format!(" {}", style(format!("({})", tail.join(", "))).dim()) };
+    println!("{}{}", style(head).bold().green(), tail);
+    if !failed.is_empty() {
+        anyhow::bail!("could not restore {} of the journaled symlinks", failed.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+//! uid/gid to name lookups via the system user database (NSS-aware).
+
+use std::ffi::CStr;
+
+/// Login name for `uid`, if the user database knows it.
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    // SAFETY: all pointers reference live, correctly sized buffers owned by this frame.
+    let rc = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() { return None; }
+    // SAFETY: on success pw_name points into `buf` and is NUL-terminated.
+    Some(unsafe { CStr::from_ptr(pwd.pw_name) }.to_string_lossy().into_owned())
+}
+
+/// Group name for `gid`, if the group database knows it.
+pub fn group_name(gid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    // SAFETY: as above.
+    let rc = unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() { return None; }
+    // SAFETY: on success gr_name points into `buf` and is NUL-terminated.
+    Some(unsafe { CStr::from_ptr(grp.gr_name) }.to_string_lossy().into_owned())
+}
@@ -0,0 +1,204 @@
+//! `--watch`: after the scan, follow filesystem events under the roots and report symlinks that
+//! come to match, stop matching, or get new link text, as they happen.
+//!
+//! Events are batched as for `index watch`. Each path in a batch is looked at again: a symlink is
+//! matched against the targets, a directory created or moved in is walked for the links it
+//! brought, and a path that is gone takes its matches (and those below it) along.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use ::notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::cli::{FindOpts, OutputFormat, WalkOpts};
+use crate::hooks::Quiet;
+use crate::index::{self, Batch};
+use crate::output::{self, JsonPath};
+use crate::results::Matches;
+use crate::scan::{self, Match, Target, TargetIds};
+
+type Events = mpsc::Receiver<::notify::Result<::notify::Event>>;
+
+/// A watcher on the roots, started before the scan so nothing changed during it is missed.
+pub struct Watch {
+    _watcher: RecommendedWatcher,
+    rx: Events,
+    /// Each root as given (the prefix of reported paths) and made absolute (the prefix of events)
+    roots: Vec<(PathBuf, PathBuf)>,
+}
+
+pub fn start(walk: &WalkOpts) -> Result<Watch> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = ::notify::recommended_watcher(tx).context("start filesystem watcher")?;
+    let roots: Vec<(PathBuf, PathBuf)> = walk.roots.iter().map(|r| (r.clone(), scan::lexical_absolute(r))).collect();
+    for (_, abs) in &roots {
+        watcher.watch(abs, RecursiveMode::Recursive).with_context(|| format!("watch {}", abs.display()))?;
+    }
+    Ok(Watch { _watcher: watcher, rx, roots })
+}
+
+/// A match being watched: which target it matched, and its link text.
+#[derive(PartialEq)]
+struct Seen {
+    target: usize,
+    broken: bool,
+    text: Option<PathBuf>,
+}
+
+impl Seen {
+    fn of(m: &Match) -> Seen {
+        Seen { target: m.target, broken: m.broken, text: fs::read_link(&m.path).ok() }
+    }
+}
+
+enum Change {
+    Added(Match),
+    Removed(PathBuf),
+    /// The link text before and after
+    Retargeted(Match, Option<PathBuf>, Option<PathBuf>),
+}
+
+struct State<'a> {
+    opts: &'a FindOpts,
+    targets: &'a [Target],
+    ids: TargetIds,
+    has_patterns: bool,
+    seen: BTreeMap<PathBuf, Seen>,
+}
+
+impl State<'_> {
+    /// Match the symlink `p` again and note how that differs from before.
+    fn check(&mut self, p: PathBuf, out: &mut Vec<Change>) {
+        let hit = scan::match_symlink(&self.opts.scan, &p, self.targets, &self.ids, self.has_patterns);
+        match (self.seen.remove(&p), hit) {
+            (None, None) => {}
+            (Some(_), None) => out.push(Change::Removed(p)),
+            (old, Some((target, broken))) => {
+                let m = Match { path: p, target, broken };
+                let now = Seen::of(&m);
+                match &old {
+                    None => out.push(Change::Added(m.clone())),
+                    Some(old) if *old != now => out.push(Change::Retargeted(m.clone(), old.text.clone(), now.text.clone())),
+                    Some(_) => {}
+                }
+                self.seen.insert(m.path, now);
+            }
+        }
+    }
+
+    /// `p` is no longer a symlink: drop its match, and with `tree` every match below it.
+    fn gone(&mut self, p: &Path, tree: bool, out: &mut Vec<Change>) {
+        let below: Vec<PathBuf> = self.seen.range(p.to_path_buf()..)
+            .map(|(k, _)| k)
+            .take_while(|k| k.starts_with(p) && (tree || *k == p))
+            .cloned()
+            .collect();
+        for k in below {
+            self.seen.remove(&k);
+            out.push(Change::Removed(k));
+        }
+    }
+
+    /// What a batch of events changed.
+    fn apply(&mut self, batch: &Batch, w: &Watch) -> Vec<Change> {
+        let abs_roots: Vec<PathBuf> = w.roots.iter().map(|(_, abs)| abs.clone()).collect();
+        let mut out = Vec::new();
+        for p in batch.changed.iter().filter(|p| index::reachable(p, &abs_roots, &self.opts.scan.walk)) {
+            // Reported as the scan reports paths: under the root as it was given
+            let Some(shown) = w.roots.iter().find_map(|(given, abs)| Some(given.join(p.strip_prefix(abs).ok()?))) else { continue };
+            match fs::symlink_metadata(p) {
+                Ok(m) if m.file_type().is_symlink() => self.check(shown, &mut out),
+                Ok(m) if m.is_dir() && batch.created.contains(p) => {
+                    for l in scan::walk_symlinks(&WalkOpts { roots: vec![shown], ..self.opts.scan.walk.clone() }) { self.check(l, &mut out); }
+                }
+                Ok(m) if m.is_dir() => {}
+                Ok(_) => self.gone(&shown, false, &mut out),
+                Err(_) => self.gone(&shown, true, &mut out),
+            }
+        }
+        out
+    }
+
+    /// The watcher dropped events: scan again and report the difference.
+    fn rescan(&mut self) -> Result<Vec<Change>> {
+        let result = scan::scan(&self.opts.scan, self.targets, &Quiet, &|_| {})?;
+        let mut out = Vec::new();
+        let mut old = std::mem::take(&mut self.seen);
+        for m in &result.matches {
+            let now = Seen::of(&m);
+            match old.remove(&m.path) {
+                None => out.push(Change::Added(m.clone().into_owned())),
+                Some(before) if before != now => out.push(Change::Retargeted(m.clone().into_owned(), before.text, now.text.clone())),
+                Some(_) => {}
+            }
+            self.seen.insert(m.path.clone(), now);
+        }
+        out.extend(old.into_keys().map(Change::Removed));
+        Ok(out)
+    }
+}
+
+/// Report changes to the matches until the process is interrupted.
+pub fn run(w: Watch, opts: &FindOpts, targets: &[Target], matches: &Matches) -> Result<()> {
+    let mut state = State {
+        opts,
+        targets,
+        ids: scan::target_ids(targets),
+        has_patterns: scan::has_patterns(targets),
+        seen: matches.iter().map(|m| (m.path.clone(), Seen::of(&m))).collect(),
+    };
+    let json = matches!(opts.format, OutputFormat::Json | OutputFormat::JsonLines);
+    eprintln!("{}", style("Watching for changes (Ctrl-C to stop)").dim());
+    while let Ok(first) = w.rx.recv() {
+        let batch = Batch::gather(first, &w.rx);
+        let changes = if batch.rescan {
+            eprintln!("warning: watch: events were dropped; scanning again");
+            state.rescan()?
+        } else {
+            state.apply(&batch, &w)
+        };
+        for c in &changes {
+            if json { println!("{}", serde_json::to_string(&event(c, opts, targets)?)?); } else { print_change(c, opts, targets); }
+        }
+    }
+    Ok(())
+}
+
+fn print_change(c: &Change, opts: &FindOpts, targets: &[Target]) {
+    let text = |t: &Option<PathBuf>| t.as_ref().map_or_else(|| "?".to_string(), |t| t.display().to_string());
+    let path = |p: &Path| style(output::display_path(p, opts.quote).into_owned()).white().bold();
+    match c {
+        Change::Added(m) => {
+            let broken = if m.broken { " (broken)" } else { "" };
+            println!("{} {} {}", style("+").green().bold(), path(&m.path), style(format!("-> {}{}", targets[m.target].given, broken)).dim());
+        }
+        Change::Removed(p) => println!("{} {}", style("-").red().bold(), path(p)),
+        Change::Retargeted(m, old, new) => {
+            println!("{} {} {}", style("~").yellow().bold(), path(&m.path), style(format!("{} -> {}", text(old), text(new))).dim());
+        }
+    }
+}
+
+/// One change as a JSON line: the `--json-lines` fields plus `event`.
+fn event(c: &Change, opts: &FindOpts, targets: &[Target]) -> Result<serde_json::Value> {
+    let encoding = opts.path_encoding;
+    Ok(match c {
+        Change::Added(m) => {
+            let mut v = crate::json_line(m, &targets[m.target], encoding)?;
+            v["event"] = "added".into();
+            v
+        }
+        Change::Removed(p) => serde_json::json!({ "event": "removed", "path": JsonPath { path: p, encoding } }),
+        Change::Retargeted(m, old, new) => {
+            let mut v = crate::json_line(m, &targets[m.target], encoding)?;
+            v["event"] = "retargeted".into();
+            v["from"] = old.as_ref().map(|t| t.to_string_lossy()).into();
+            v["to"] = new.as_ref().map(|t| t.to_string_lossy()).into();
+            v
+        }
+    })
+}
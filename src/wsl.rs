@@ -0,0 +1,60 @@
+//! WSL interop: Windows path forms in link text and targets.
+//!
+//! Under WSL the Windows drives are mounted at `/mnt/<letter>` (or under the
+//! `[automount] root` of `/etc/wsl.conf`). A Windows symlink or junction whose
+//! target cannot be shown as a Linux path keeps its Windows text (`C:\Users\me`,
+//! `\??\C:\Users\me`), which never resolves as is; on the drive mount it names the
+//! same file. Windows-side links into the distro go through `\\wsl$\<distro>\...`
+//! (or `\\wsl.localhost\...`), the same path seen from the other side.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Whether this is a WSL distro (the interop handler is registered, or the kernel says so).
+pub fn detected() -> bool {
+    static WSL: OnceLock<bool> = OnceLock::new();
+    *WSL.get_or_init(|| {
+        std::env::var_os("WSL_DISTRO_NAME").is_some()
+            || Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists()
+            || fs::read_to_string("/proc/sys/kernel/osrelease").is_ok_and(|r| r.to_ascii_lowercase().contains("microsoft"))
+    })
+}
+
+/// Where the drives are mounted: `[automount] root` in `/etc/wsl.conf`, else `/mnt`.
+fn mount_root() -> &'static Path {
+    static ROOT: OnceLock<PathBuf> = OnceLock::new();
+    ROOT.get_or_init(|| {
+        let conf = fs::read_to_string("/etc/wsl.conf").unwrap_or_default();
+        let mut section = String::new();
+        for line in conf.lines().map(str::trim) {
+            if let Some(s) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) { section = s.trim().to_ascii_lowercase(); continue; }
+            let Some((k, v)) = line.split_once('=') else { continue };
+            if section == "automount" && k.trim() == "root" {
+                return PathBuf::from(v.trim().trim_matches('"'));
+            }
+        }
+        PathBuf::from("/mnt")
+    })
+}
+
+/// `C:\a\b` (also `C:/a/b`, `\??\C:\a`, `\\?\C:\a`) as `/mnt/c/a/b`; with `unc`, `\\wsl$\<distro>\a` as `/a`.
+///
+/// `None` if `p` is not a Windows path, or is a UNC path to another distro or host.
+pub fn to_linux(p: &Path, unc: bool) -> Option<PathBuf> {
+    let s = p.to_str()?.replace('\\', "/");
+    let s = ["//?/", "/??/", "//./"].iter().find_map(|pre| s.strip_prefix(pre)).unwrap_or(&s);
+    let b = s.as_bytes();
+    if b.len() >= 2 && b[0].is_ascii_alphabetic() && b[1] == b':' && (b.len() == 2 || b[2] == b'/') {
+        let drive = mount_root().join(s[..1].to_ascii_lowercase());
+        return Some(s[2..].split('/').filter(|c| !c.is_empty()).fold(drive, |acc, c| acc.join(c)));
+    }
+    if !unc { return None; }
+    let rest = s.strip_prefix("UNC/").map_or_else(|| s.strip_prefix("//"), Some)?;
+    let (host, rest) = rest.split_once('/')?;
+    if !host.eq_ignore_ascii_case("wsl$") && !host.eq_ignore_ascii_case("wsl.localhost") { return None; }
+    let (distro, rest) = rest.split_once('/').unwrap_or((rest, ""));
+    // Outside WSL there is no distro name to check against
+    if std::env::var("WSL_DISTRO_NAME").is_ok_and(|d| !d.eq_ignore_ascii_case(distro)) { return None; }
+    Some(rest.split('/').filter(|c| !c.is_empty()).fold(PathBuf::from("/"), |acc, c| acc.join(c)))
+}
@@ -0,0 +1,176 @@
+//! `--with-xattrs` / `--with-quarantine`: security-relevant extended attributes of links and their targets.
+//!
+//! Read through libc on Linux and macOS; other platforms report none.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Attribute name prefixes worth showing in an audit.
+const PREFIXES: &[&str] = &[
+    "security.",
+    "system.posix_acl_",
+    "trusted.",
+    "user.",
+    "com.apple.quarantine",
+    "com.apple.provenance",
+    "com.apple.rootless",
+];
+
+/// Interesting attributes of `p` itself (`follow == false`) or of what it resolves to.
+pub fn read(p: &Path, follow: bool) -> BTreeMap<String, String> {
+    sys::list(p, follow)
+        .into_iter()
+        .filter(|n| PREFIXES.iter().any(|pre| n.starts_with(pre)))
+        .filter_map(|n| {
+            let v = sys::get(p, &n, follow)?;
+            Some((n, render(&v)))
+        })
+        .collect()
+}
+
+/// Gatekeeper flag set once the user has approved opening the item.
+const QTN_FLAG_USER_APPROVED: u32 = 0x0040;
+
+/// A parsed `com.apple.quarantine` value: `flags;timestamp;agent;event UUID` (hex flags and seconds).
+#[derive(Serialize)]
+pub struct Quarantine {
+    pub flags: String,
+    pub approved: bool,
+    /// Seconds since the epoch when the item was quarantined
+    pub timestamp: Option<u64>,
+    /// The app that downloaded or created the item
+    pub agent: String,
+    pub event: Option<String>,
+}
+
+impl Quarantine {
+    fn parse(v: &[u8]) -> Option<Quarantine> {
+        let text = String::from_utf8_lossy(v.strip_suffix(&[0]).unwrap_or(v)).into_owned();
+        let mut parts = text.split(';');
+        let flags = parts.next().filter(|f| !f.is_empty())?.to_string();
+        let bits = u32::from_str_radix(&flags, 16).ok()?;
+        Some(Quarantine {
+            approved: bits & QTN_FLAG_USER_APPROVED != 0,
+            timestamp: parts.next().and_then(|t| u64::from_str_radix(t, 16).ok()),
+            agent: parts.next().unwrap_or("").to_string(),
+            event: parts.next().filter(|e| !e.is_empty()).map(str::to_string),
+            flags,
+        })
+    }
+
+    /// `Safari, 2024-01-02 10:11, flags 0083 (not approved)`.
+    pub fn describe(&self) -> String {
+        let when = self.timestamp.map(|t| local_time(t).unwrap_or_else(|| format!("@{}", t)));
+        let agent = if self.agent.is_empty() { "unknown agent" } else { &self.agent };
+        format!(
+            "{}{}, flags {} ({})",
+            agent,
+            when.map(|w| format!(", {}", w)).unwrap_or_default(),
+            self.flags,
+            if self.approved { "approved" } else { "not approved" },
+        )
+    }
+}
+
+/// `com.apple.quarantine` of `p` itself (`follow == false`) or of what it resolves to (macOS only).
+pub fn quarantine(p: &Path, follow: bool) -> Option<Quarantine> {
+    Quarantine::parse(&sys::get(p, "com.apple.quarantine", follow)?)
+}
+
+#[cfg(unix)]
+fn local_time(secs: u64) -> Option<String> {
+    let t = libc::time_t::try_from(secs).ok()?;
+    // SAFETY: `tm` is plain old data, fully written by localtime_r on success.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() { return None; }
+    Some(format!("{:04}-{:02}-{:02} {:02}:{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday, tm.tm_hour, tm.tm_min))
+}
+
+#[cfg(not(unix))]
+fn local_time(_secs: u64) -> Option<String> {
+    None
+}
+
+/// Text values as text (without C strings' trailing NUL, as in SELinux contexts); binary as `0x` hex.
+fn render(v: &[u8]) -> String {
+    let text = v.strip_suffix(&[0]).unwrap_or(v);
+    match std::str::from_utf8(text) {
+        Ok(s) if !s.chars().any(char::is_control) => s.to_string(),
+        _ => v.iter().fold(String::from("0x"), |mut out, b| { out.push_str(&format!("{:02x}", b)); out }),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+mod sys {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Two-call pattern: ask for the size, then fill a buffer of that size.
+    fn sized(mut call: impl FnMut(*mut libc::c_void, usize) -> isize) -> Option<Vec<u8>> {
+        let n = call(std::ptr::null_mut(), 0);
+        if n < 0 { return None; }
+        let mut buf = vec![0u8; n as usize];
+        let n = call(buf.as_mut_ptr().cast(), buf.len());
+        if n < 0 { return None; }
+        buf.truncate(n as usize);
+        Some(buf)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn list_raw(path: &CString, buf: *mut libc::c_void, len: usize, follow: bool) -> isize {
+        // SAFETY: `path` is NUL-terminated; `buf` is null with len 0 or points to `len` writable bytes.
+        unsafe {
+            if follow { libc::listxattr(path.as_ptr(), buf.cast(), len) } else { libc::llistxattr(path.as_ptr(), buf.cast(), len) }
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn get_raw(path: &CString, name: &CString, buf: *mut libc::c_void, len: usize, follow: bool) -> isize {
+        // SAFETY: as for `list_raw`; `name` is NUL-terminated.
+        unsafe {
+            if follow { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, len) } else { libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf, len) }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn list_raw(path: &CString, buf: *mut libc::c_void, len: usize, follow: bool) -> isize {
+        let opts = if follow { 0 } else { libc::XATTR_NOFOLLOW };
+        // SAFETY: as above.
+        unsafe { libc::listxattr(path.as_ptr(), buf.cast(), len, opts) }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn get_raw(path: &CString, name: &CString, buf: *mut libc::c_void, len: usize, follow: bool) -> isize {
+        let opts = if follow { 0 } else { libc::XATTR_NOFOLLOW };
+        // SAFETY: as above.
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, len, 0, opts) }
+    }
+
+    pub fn list(p: &Path, follow: bool) -> Vec<String> {
+        let Ok(path) = CString::new(p.as_os_str().as_bytes()) else { return Vec::new() };
+        let Some(names) = sized(|buf, len| list_raw(&path, buf, len, follow)) else { return Vec::new() };
+        names.split(|&b| b == 0).filter(|n| !n.is_empty()).map(|n| String::from_utf8_lossy(n).into_owned()).collect()
+    }
+
+    pub fn get(p: &Path, name: &str, follow: bool) -> Option<Vec<u8>> {
+        let path = CString::new(p.as_os_str().as_bytes()).ok()?;
+        let name = CString::new(name).ok()?;
+        sized(|buf, len| get_raw(&path, &name, buf, len, follow))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+mod sys {
+    use std::path::Path;
+
+    pub fn list(_p: &Path, _follow: bool) -> Vec<String> {
+        Vec::new()
+    }
+
+    pub fn get(_p: &Path, _name: &str, _follow: bool) -> Option<Vec<u8>> {
+        None
+    }
+}